@@ -0,0 +1,8 @@
+mod audit;
+mod error;
+mod routes;
+mod server;
+
+pub use error::*;
+pub use routes::*;
+pub use server::*;
@@ -0,0 +1,39 @@
+use super::AdminState;
+use crate::{gametypes::LogType, sql::*};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use log::{info, warn};
+use std::net::SocketAddr;
+
+/// Writes every admin API call to the `logs` table. The account id is taken
+/// from `/accounts/{uid}/...` paths and is 0 for everything else.
+pub async fn audit(
+    State(state): State<AdminState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let uid = path
+        .strip_prefix("/accounts/")
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|uid| uid.parse::<i64>().ok())
+        .unwrap_or_default();
+
+    let response = next.run(request).await;
+    let message = format!("{} {} {}", method, path, response.status().as_u16());
+
+    info!("Admin API {} from {}", message, addr);
+
+    let log = PGLog::new(0, uid, LogType::Admin, message, addr.ip().to_string());
+
+    if let Err(e) = state.accounts.insert_log(&log).await {
+        warn!("Failed to write the admin API audit log: {}", e);
+    }
+
+    response
+}
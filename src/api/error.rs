@@ -0,0 +1,45 @@
+use crate::gametypes::AscendingError;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+pub type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Internal(AscendingError),
+    Unavailable,
+}
+
+impl From<AscendingError> for ApiError {
+    fn from(error: AscendingError) -> Self {
+        ApiError::Internal(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::Internal(error) => {
+                log::error!("Admin API error: {}", error);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+            ApiError::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The login server is not accepting commands".to_string(),
+            ),
+        };
+
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
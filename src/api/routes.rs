@@ -0,0 +1,295 @@
+use super::{audit::audit, ApiError, ApiResult};
+use crate::{
+    certs::normalize_fingerprint, console::*, gametypes::*, server::Mailer, sql::*,
+    store::AccountStore,
+};
+use axum::{
+    extract::{Path, Query, State},
+    middleware,
+    routing::{get, post, put},
+    Json, Router,
+};
+use log::warn;
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+pub const MAX_SEARCH_RESULTS: i64 = 100;
+pub const MAX_LOGIN_HISTORY: i64 = 100;
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub accounts: Arc<dyn AccountStore>,
+    pub mailer: Arc<dyn Mailer>,
+    pub console: ConsoleSender,
+}
+
+impl AdminState {
+    /// Hands a command to the game loop and waits for it to run.
+    pub async fn request(&self, command: Command) -> ApiResult<CommandOutput> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.console
+            .send(ConsoleRequest {
+                command,
+                reply: Some(reply),
+            })
+            .map_err(|_| ApiError::Unavailable)?;
+
+        receiver.await.map_err(|_| ApiError::Unavailable)
+    }
+
+    pub async fn command(&self, command: Command) -> ApiResult<String> {
+        match self.request(command).await? {
+            CommandOutput::Error(message) => Err(ApiError::BadRequest(message)),
+            output => Ok(output.to_string()),
+        }
+    }
+
+    async fn account(&self, uid: i64) -> ApiResult<AccountInfo> {
+        self.accounts
+            .find_account(uid)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("No account with uid {}", uid)))
+    }
+}
+
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/:uid", get(get_account))
+        .route("/accounts/:uid/logins", get(get_logins))
+        .route("/accounts/:uid/ban", post(post_ban))
+        .route("/accounts/:uid/unban", post(post_unban))
+        .route("/accounts/:uid/password-reset", post(post_password_reset))
+        .route("/accounts/:uid/access", put(put_access))
+        .route("/servers", get(get_servers))
+        .route("/keys/rotate", post(post_rotate_keys))
+        .route("/certs/reload", post(post_reload_certs))
+        .route("/certs/revoke", post(post_revoke_cert))
+        .layer(middleware::from_fn_with_state(state.clone(), audit))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub search: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct LimitQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    /// None bans the account permanently.
+    pub hours: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeCertRequest {
+    /// SHA-256 fingerprint, with or without colons.
+    pub fingerprint: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct AccessRequest {
+    pub access: UserAccess,
+}
+
+async fn get_accounts(
+    State(state): State<AdminState>,
+    Query(query): Query<SearchQuery>,
+) -> ApiResult<Json<Vec<AccountInfo>>> {
+    let limit = query
+        .limit
+        .unwrap_or(MAX_SEARCH_RESULTS)
+        .clamp(1, MAX_SEARCH_RESULTS);
+
+    Ok(Json(
+        state.accounts.search_accounts(&query.search, limit).await?,
+    ))
+}
+
+async fn get_account(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+) -> ApiResult<Json<AccountInfo>> {
+    Ok(Json(state.account(uid).await?))
+}
+
+async fn get_logins(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+    Query(query): Query<LimitQuery>,
+) -> ApiResult<Json<Vec<LogEntry>>> {
+    let limit = query
+        .limit
+        .unwrap_or(MAX_LOGIN_HISTORY)
+        .clamp(1, MAX_LOGIN_HISTORY);
+
+    state.account(uid).await?;
+
+    Ok(Json(
+        state.accounts.find_logs(uid, LogType::Login, limit).await?,
+    ))
+}
+
+async fn post_ban(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+    Json(request): Json<BanRequest>,
+) -> ApiResult<Json<Value>> {
+    if let Some(hours) = request.hours {
+        Command::check_ban_hours(hours).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    }
+
+    let account = state.account(uid).await?;
+    let message = state
+        .command(Command::Ban {
+            username: account.username,
+            hours: request.hours,
+        })
+        .await?;
+
+    Ok(Json(json!({ "message": message })))
+}
+
+async fn post_unban(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+) -> ApiResult<Json<Value>> {
+    if !state.accounts.unban_player(uid).await? {
+        return Err(ApiError::NotFound(format!("No account with uid {}", uid)));
+    }
+
+    Ok(Json(
+        json!({ "message": format!("Unbanned account {}.", uid) }),
+    ))
+}
+
+async fn post_password_reset(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+) -> ApiResult<Json<Value>> {
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+
+    if !state.accounts.force_password_reset(uid, &code).await? {
+        return Err(ApiError::NotFound(format!("No account with uid {}", uid)));
+    }
+
+    let account = state.account(uid).await?;
+
+    // The reset already happened, so a failed mail still returns the code to hand out.
+    if let Err(e) = state.mailer.send_password_reset(&account, &code).await {
+        warn!("Could not mail the password reset code to {}: {}", uid, e);
+    }
+
+    Ok(Json(json!({ "code": code })))
+}
+
+async fn put_access(
+    State(state): State<AdminState>,
+    Path(uid): Path<i64>,
+    Json(request): Json<AccessRequest>,
+) -> ApiResult<Json<AccountInfo>> {
+    if !state.accounts.update_access(uid, request.access).await? {
+        return Err(ApiError::NotFound(format!("No account with uid {}", uid)));
+    }
+
+    Ok(Json(state.account(uid).await?))
+}
+
+async fn get_servers(State(state): State<AdminState>) -> ApiResult<Json<Vec<ServerEntry>>> {
+    match state.request(Command::ListServers).await? {
+        CommandOutput::Servers(servers) => Ok(Json(servers)),
+        _ => Err(ApiError::Unavailable),
+    }
+}
+
+async fn post_rotate_keys(State(state): State<AdminState>) -> ApiResult<Json<Value>> {
+    let message = state.command(Command::RotateKeys).await?;
+
+    Ok(Json(json!({ "message": message })))
+}
+
+async fn post_reload_certs(State(state): State<AdminState>) -> ApiResult<Json<Value>> {
+    let message = state.command(Command::ReloadCerts).await?;
+
+    Ok(Json(json!({ "message": message })))
+}
+
+/// Revokes a client certificate and reloads the revocation lists so new handshakes
+/// with it are refused too.
+async fn post_revoke_cert(
+    State(state): State<AdminState>,
+    Json(request): Json<RevokeCertRequest>,
+) -> ApiResult<Json<Value>> {
+    let fingerprint = normalize_fingerprint(&request.fingerprint).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{} is not a SHA-256 fingerprint",
+            request.fingerprint
+        ))
+    })?;
+
+    if !state
+        .accounts
+        .revoke_cert(&fingerprint, &request.reason)
+        .await?
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Certificate {} is already revoked",
+            fingerprint
+        )));
+    }
+
+    let message = state.command(Command::ReloadCerts).await?;
+
+    Ok(Json(
+        json!({ "fingerprint": fingerprint, "message": message }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::NoMailer, store::MemoryAccountStore};
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn refuses_bans_that_have_expired_or_are_too_long() {
+        let (console, mut commands) = mpsc::unbounded_channel();
+        let state = AdminState {
+            accounts: Arc::new(MemoryAccountStore::default()),
+            mailer: Arc::new(NoMailer),
+            console,
+        };
+
+        for hours in [0, -1, MAX_BAN_HOURS + 1, i64::MAX] {
+            let refused = post_ban(
+                State(state.clone()),
+                Path(1),
+                Json(BanRequest { hours: Some(hours) }),
+            )
+            .await;
+
+            assert!(
+                matches!(refused, Err(ApiError::BadRequest(_))),
+                "a ban of {} hours was not refused",
+                hours
+            );
+        }
+
+        assert!(
+            commands.try_recv().is_err(),
+            "a refused ban reached the console"
+        );
+    }
+}
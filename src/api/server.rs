@@ -0,0 +1,99 @@
+use super::{router, AdminState};
+use crate::{
+    certs::{load_certs, load_private_key},
+    containers::AdminApiConfig,
+    gametypes::*,
+};
+use axum::{extract::ConnectInfo, Extension};
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use log::{info, warn};
+use rustls::{crypto::ring as provider, server::WebPkiClientVerifier, RootCertStore, ServerConfig};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+fn build_mtls_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: &str,
+) -> Result<Arc<ServerConfig>> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in load_certs(client_ca_path)? {
+        roots.add(cert)?;
+    }
+
+    let provider = Arc::new(provider::default_provider());
+    let verifier =
+        WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?;
+
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(load_certs(cert_path)?, load_private_key(key_path)?)?;
+
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(Arc::new(config))
+}
+
+/// Runs the admin API until the listener fails. Every connection is served on its own task.
+pub async fn serve_admin_api(config: AdminApiConfig, state: AdminState) -> Result<()> {
+    let addr: SocketAddr = config.listen.parse()?;
+
+    let acceptor = match (&config.tls_cert, &config.tls_key, &config.client_ca) {
+        (Some(cert), Some(key), Some(client_ca)) => {
+            Some(TlsAcceptor::from(build_mtls_config(cert, key, client_ca)?))
+        }
+        _ if addr.ip().is_loopback() => None,
+        _ => return Err(AscendingError::InsecureAdminApi(config.listen)),
+    };
+
+    let listener = TcpListener::bind(addr).await?;
+    let app = router(state);
+
+    info!(
+        "Admin API listening on {}{}",
+        addr,
+        if acceptor.is_some() { " with mTLS" } else { "" }
+    );
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Admin API accept error: {}", e);
+                continue;
+            }
+        };
+
+        let service = TowerToHyperService::new(app.clone().layer(Extension(ConnectInfo(peer))));
+        let acceptor = acceptor.clone();
+
+        tokio::spawn(async move {
+            let result = match acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await
+                    }
+                    Err(e) => {
+                        warn!("Admin API TLS handshake with {} failed: {}", peer, e);
+                        return;
+                    }
+                },
+                None => {
+                    http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                }
+            };
+
+            if let Err(e) = result {
+                warn!("Admin API connection with {} failed: {}", peer, e);
+            }
+        });
+    }
+}
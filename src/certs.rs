@@ -0,0 +1,397 @@
+mod resolver;
+mod verifier;
+
+pub use resolver::*;
+pub use verifier::*;
+
+use crate::{containers::Config, gametypes::*, socket::ListenerKind};
+use chrono::{Datelike, Duration, Utc};
+use log::warn;
+use rcgen::{
+    date_time_ymd, BasicConstraints, Certificate, CertificateParams, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::{
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+pub const CA_CERT_FILE: &str = "ca-crt.pem";
+pub const CA_KEY_FILE: &str = "ca-key.pem";
+pub const SERVER_CERT_FILE: &str = "server.crt";
+pub const SERVER_KEY_FILE: &str = "server-key.pem";
+pub const CLIENT_CERT_FILE: &str = "client.crt";
+pub const CLIENT_KEY_FILE: &str = "client-key.pem";
+pub const GAME_SERVER_CERT_FILE: &str = "game-server.crt";
+pub const GAME_SERVER_KEY_FILE: &str = "game-server-key.pem";
+
+/// Days a generated CA stays valid. Leaf certificates use the `days` they are issued with.
+pub const CA_VALID_DAYS: i64 = 3650;
+
+/// Names the development server certificate is always valid for.
+pub const DEV_SANS: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+pub fn load_certs(filename: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certfile = fs::File::open(filename).map_err(|e| {
+        AscendingError::Certificate(format!("cannot open certificate file {}: {}", filename, e))
+    })?;
+    let mut reader = BufReader::new(certfile);
+
+    let certs = rustls_pemfile::certs(&mut reader)
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| AscendingError::Certificate(format!("cannot parse {}: {}", filename, e)))?;
+
+    if certs.is_empty() {
+        return Err(AscendingError::Certificate(format!(
+            "no certificates found in {}",
+            filename
+        )));
+    }
+
+    Ok(certs)
+}
+
+pub fn load_private_key(filename: &str) -> Result<PrivateKeyDer<'static>> {
+    let keyfile = fs::File::open(filename).map_err(|e| {
+        AscendingError::Certificate(format!("cannot open private key file {}: {}", filename, e))
+    })?;
+    let mut reader = BufReader::new(keyfile);
+
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .map_err(|e| AscendingError::Certificate(format!("cannot parse {}: {}", filename, e)))?
+        {
+            Some(rustls_pemfile::Item::Pkcs1Key(key)) => return Ok(key.into()),
+            Some(rustls_pemfile::Item::Pkcs8Key(key)) => return Ok(key.into()),
+            Some(rustls_pemfile::Item::Sec1Key(key)) => return Ok(key.into()),
+            None => break,
+            _ => {}
+        }
+    }
+
+    Err(AscendingError::Certificate(format!(
+        "no keys found in {} (encrypted keys not supported)",
+        filename
+    )))
+}
+
+/// What an issued certificate is used for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CertKind {
+    /// The login server's TLS certificate.
+    Server,
+    /// A game client identifying itself to the login server.
+    Client,
+    /// A game server, which both connects to the login server and accepts clients.
+    GameServer,
+}
+
+impl CertKind {
+    fn common_name(self) -> &'static str {
+        match self {
+            CertKind::Server => "Ascending Login Server",
+            CertKind::Client => "Ascending Client",
+            CertKind::GameServer => "Ascending Game Server",
+        }
+    }
+
+    fn extended_key_usages(self) -> Vec<ExtendedKeyUsagePurpose> {
+        match self {
+            CertKind::Server => vec![ExtendedKeyUsagePurpose::ServerAuth],
+            CertKind::Client => vec![ExtendedKeyUsagePurpose::ClientAuth],
+            CertKind::GameServer => vec![
+                ExtendedKeyUsagePurpose::ServerAuth,
+                ExtendedKeyUsagePurpose::ClientAuth,
+            ],
+        }
+    }
+}
+
+/// A PEM encoded certificate and its private key.
+pub struct IssuedCert {
+    pub cert: String,
+    pub key: String,
+}
+
+impl IssuedCert {
+    pub fn save(&self, cert_path: &Path, key_path: &Path) -> Result<()> {
+        write_pem(cert_path, &self.cert, false)?;
+        write_pem(key_path, &self.key, true)
+    }
+}
+
+pub struct CertificateAuthority {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl CertificateAuthority {
+    pub fn generate() -> Result<Self> {
+        let mut params = CertificateParams::new(Vec::new())?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Ascending Development CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        set_validity(&mut params, CA_VALID_DAYS);
+
+        let key = KeyPair::generate()?;
+        let cert = params.self_signed(&key)?;
+
+        Ok(Self { cert, key })
+    }
+
+    /// Loads an existing CA so more certificates can be issued from it.
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        let key = KeyPair::from_pem(&fs::read_to_string(key_path)?)?;
+        let params = CertificateParams::from_ca_cert_pem(&fs::read_to_string(cert_path)?)?;
+        let cert = params.self_signed(&key)?;
+
+        Ok(Self { cert, key })
+    }
+
+    pub fn save(&self, cert_path: &Path, key_path: &Path) -> Result<()> {
+        write_pem(cert_path, &self.cert.pem(), false)?;
+        write_pem(key_path, &self.key.serialize_pem(), true)
+    }
+
+    /// Issues a certificate valid for `names`, which may be DNS names or IP addresses.
+    pub fn issue(&self, kind: CertKind, names: &[String], days: i64) -> Result<IssuedCert> {
+        let mut params = CertificateParams::new(names.to_vec())?;
+        params.distinguished_name.push(
+            DnType::CommonName,
+            names.first().map_or(kind.common_name(), |name| name),
+        );
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        params.extended_key_usages = kind.extended_key_usages();
+        params.use_authority_key_identifier_extension = true;
+        set_validity(&mut params, days);
+
+        let key = KeyPair::generate()?;
+        let cert = params.signed_by(&key, &self.cert, &self.key)?;
+
+        Ok(IssuedCert {
+            cert: cert.pem(),
+            key: key.serialize_pem(),
+        })
+    }
+}
+
+/// Makes the certificate valid from yesterday, to allow for clock skew, for `days` days.
+fn set_validity(params: &mut CertificateParams, days: i64) {
+    let date = |days: i64| {
+        let date = Utc::now() + Duration::try_days(days).unwrap_or_default();
+        date_time_ymd(date.year(), date.month() as u8, date.day() as u8)
+    };
+
+    params.not_before = date(-1);
+    params.not_after = date(days);
+}
+
+/// Writes a PEM file, creating its directory. Private keys are only readable by the owner.
+pub fn write_pem(path: &Path, contents: &str, private: bool) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, contents)?;
+
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = private;
+
+    Ok(())
+}
+
+/// Where the development CA key lives: next to `ca_root`.
+pub fn ca_key_path(config: &Config) -> PathBuf {
+    Path::new(&config.ca_root).with_file_name(CA_KEY_FILE)
+}
+
+/// Creates the CA and server certificate named in the config when they are missing,
+/// so a development server starts without any openssl steps. Returns the files written.
+pub fn ensure_dev_certs(config: &Config) -> Result<Vec<PathBuf>> {
+    let server_cert = Path::new(&config.server_cert);
+    let server_key = Path::new(&config.server_key);
+
+    if server_cert.is_file() && server_key.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let ca_cert = Path::new(&config.ca_root);
+    let ca_key = ca_key_path(config);
+    let mut written = Vec::new();
+
+    let ca = if ca_cert.is_file() {
+        if !ca_key.is_file() {
+            return Err(AscendingError::Certificate(format!(
+                "{} exists but {} does not, so a server certificate can not be signed",
+                ca_cert.display(),
+                ca_key.display()
+            )));
+        }
+
+        CertificateAuthority::load(ca_cert, &ca_key)?
+    } else {
+        let ca = CertificateAuthority::generate()?;
+        ca.save(ca_cert, &ca_key)?;
+        written.push(ca_cert.to_path_buf());
+        written.push(ca_key);
+        ca
+    };
+
+    let mut names: Vec<String> = DEV_SANS.iter().map(|name| name.to_string()).collect();
+
+    // Clients may also connect to the specific addresses the server listens on.
+    for (kind, addr) in config.listen_addresses().unwrap_or_default() {
+        let ip = addr.ip().to_string();
+
+        if kind != ListenerKind::GameServers && !addr.ip().is_unspecified() && !names.contains(&ip)
+        {
+            names.push(ip);
+        }
+    }
+
+    ca.issue(CertKind::Server, &names, 365)?
+        .save(server_cert, server_key)?;
+    written.push(server_cert.to_path_buf());
+    written.push(server_key.to_path_buf());
+
+    for path in &written {
+        warn!("Created development certificate file {}", path.display());
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::SanType;
+    use std::process;
+
+    /// A config whose certificate files are in a new directory named after `test`.
+    fn dev_config(test: &str) -> (Config, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ascending-certs-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+
+        let config = Config {
+            server_cert: path(SERVER_CERT_FILE),
+            server_key: path(SERVER_KEY_FILE),
+            ca_root: path(CA_CERT_FILE),
+            ..Config::default()
+        };
+
+        (config, dir)
+    }
+
+    fn sans(cert_path: &str) -> Vec<String> {
+        let pem = fs::read_to_string(cert_path).unwrap();
+
+        CertificateParams::from_ca_cert_pem(&pem)
+            .unwrap()
+            .subject_alt_names
+            .into_iter()
+            .map(|san| match san {
+                SanType::DnsName(name) => name.as_str().to_string(),
+                SanType::IpAddress(ip) => ip.to_string(),
+                san => panic!("unexpected name {:?}", san),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn creates_a_ca_and_server_certificate_once() {
+        let (config, dir) = dev_config("create");
+
+        let written = ensure_dev_certs(&config).unwrap();
+
+        assert_eq!(
+            written,
+            [
+                PathBuf::from(&config.ca_root),
+                ca_key_path(&config),
+                PathBuf::from(&config.server_cert),
+                PathBuf::from(&config.server_key),
+            ]
+        );
+        assert_eq!(sans(&config.server_cert), DEV_SANS);
+        assert_eq!(ensure_dev_certs(&config).unwrap(), Vec::<PathBuf>::new());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn adds_specific_client_addresses_to_the_server_certificate() {
+        let (mut config, dir) = dev_config("sans");
+        config.clients_listen = vec![
+            "0.0.0.0:7010".into(),
+            "192.168.1.20:7012".into(),
+            "127.0.0.1:7013".into(),
+        ];
+        config.servers_listen = vec!["10.0.0.5:7011".into()];
+
+        ensure_dev_certs(&config).unwrap();
+
+        assert_eq!(
+            sans(&config.server_cert),
+            ["localhost", "127.0.0.1", "::1", "192.168.1.20"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn signs_with_an_existing_ca() {
+        let (config, dir) = dev_config("existing-ca");
+        let ca = CertificateAuthority::generate().unwrap();
+        ca.save(Path::new(&config.ca_root), &ca_key_path(&config))
+            .unwrap();
+        let ca_pem = fs::read_to_string(&config.ca_root).unwrap();
+
+        let written = ensure_dev_certs(&config).unwrap();
+
+        assert_eq!(
+            written,
+            [
+                PathBuf::from(&config.server_cert),
+                PathBuf::from(&config.server_key)
+            ]
+        );
+        assert_eq!(fs::read_to_string(&config.ca_root).unwrap(), ca_pem);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_ca_without_its_key() {
+        let (config, dir) = dev_config("ca-without-key");
+        CertificateAuthority::generate()
+            .unwrap()
+            .save(Path::new(&config.ca_root), &dir.join("elsewhere.pem"))
+            .unwrap();
+
+        assert!(matches!(
+            ensure_dev_certs(&config),
+            Err(AscendingError::Certificate(_))
+        ));
+        assert!(!Path::new(&config.server_cert).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use crate::gametypes::*;
+use rustls::{
+    crypto::ring as provider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use std::sync::{Arc, RwLock};
+
+use super::{load_certs, load_private_key};
+
+/// Hands the current certificate to every new handshake. Reloading swaps it for the
+/// files on disk, connections that are already established keep the old one.
+#[derive(Debug)]
+pub struct CertResolver {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Self> {
+        Ok(Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            current: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+        })
+    }
+
+    /// Loads the certificate and key again. The current pair is kept if either fails to load.
+    pub fn reload(&self) -> Result<()> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key);
+        Ok(())
+    }
+
+    pub fn paths(&self) -> [&str; 2] {
+        [&self.cert_path, &self.key_path]
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey> {
+    let certs = load_certs(cert_path)?;
+    let key = provider::default_provider()
+        .key_provider
+        .load_private_key(load_private_key(key_path)?)?;
+
+    let certified = CertifiedKey::new(certs, key);
+
+    // A key from another certificate would fail every handshake rather than this load.
+    certified.keys_match().map_err(|e| {
+        AscendingError::Certificate(format!(
+            "{} is not the key of {}: {}",
+            key_path, cert_path, e
+        ))
+    })?;
+
+    Ok(certified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::{CertKind, CertificateAuthority, IssuedCert};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    fn issue(ca: &CertificateAuthority) -> IssuedCert {
+        ca.issue(CertKind::Server, &["localhost".into()], 1)
+            .unwrap()
+    }
+
+    fn save(dir: &Path, issued: &IssuedCert) -> (String, String) {
+        let cert = dir.join("server.crt");
+        let key = dir.join("server-key.pem");
+        issued.save(&cert, &key).unwrap();
+
+        let path = |path: PathBuf| path.to_string_lossy().into_owned();
+        (path(cert), path(key))
+    }
+
+    #[test]
+    fn keeps_the_current_certificate_when_the_key_does_not_match() {
+        let dir = std::env::temp_dir().join(format!("ascending-resolver-{}", process::id()));
+        let ca = CertificateAuthority::generate().unwrap();
+        let first = issue(&ca);
+        let (cert_path, key_path) = save(&dir, &first);
+        let resolver = CertResolver::new(&cert_path, &key_path).unwrap();
+        let current = resolver.current.read().unwrap().clone();
+
+        let second = issue(&ca);
+        fs::write(&cert_path, &second.cert).unwrap();
+
+        assert!(matches!(
+            CertResolver::new(&cert_path, &key_path),
+            Err(AscendingError::Certificate(_))
+        ));
+        assert!(resolver.reload().is_err());
+        assert!(Arc::ptr_eq(&resolver.current.read().unwrap(), &current));
+
+        fs::write(&key_path, &second.key).unwrap();
+        resolver.reload().unwrap();
+        assert!(!Arc::ptr_eq(&resolver.current.read().unwrap(), &current));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    containers::{ClientAuth, TlsConfig},
+    gametypes::*,
+};
+use rustls::{
+    client::danger::HandshakeSignatureValid,
+    crypto::ring as provider,
+    pki_types::{CertificateDer, CertificateRevocationListDer, UnixTime},
+    server::danger::{ClientCertVerified, ClientCertVerifier},
+    server::WebPkiClientVerifier,
+    CertificateError, DigitallySignedStruct, DistinguishedName, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, fs, io::BufReader, sync::Arc};
+
+use super::load_certs;
+
+/// Checks game client certificates against `ca_root` and the CRL file, then refuses the
+/// fingerprints revoked in the database.
+#[derive(Debug)]
+pub struct ClientVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    revoked: HashSet<String>,
+}
+
+impl ClientVerifier {
+    pub fn new(ca_root: &str, tls: &TlsConfig, revoked: HashSet<String>) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+
+        for cert in load_certs(ca_root)? {
+            roots.add(cert)?;
+        }
+
+        let crls = match &tls.crl_file {
+            Some(path) => load_crls(path)?,
+            None => Vec::new(),
+        };
+
+        let builder = WebPkiClientVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(provider::default_provider()),
+        )
+        .with_crls(crls);
+
+        let inner = match tls.client_auth {
+            ClientAuth::Optional => builder.allow_unauthenticated().build()?,
+            _ => builder.build()?,
+        };
+
+        Ok(Self { inner, revoked })
+    }
+}
+
+impl ClientCertVerifier for ClientVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        self.inner.root_hint_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        if self.revoked.contains(&fingerprint(end_entity)) {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Revoked));
+        }
+
+        self.inner
+            .verify_client_cert(end_entity, intermediates, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// SHA-256 of the DER certificate as lowercase hex, the form stored in `revoked_certs`.
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Accepts fingerprints as printed by openssl, with colons and in upper case.
+pub fn normalize_fingerprint(value: &str) -> Option<String> {
+    let fingerprint: String = value
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    (fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(fingerprint)
+}
+
+pub fn load_crls(filename: &str) -> Result<Vec<CertificateRevocationListDer<'static>>> {
+    let file = fs::File::open(filename).map_err(|e| {
+        AscendingError::Certificate(format!("cannot open revocation list {}: {}", filename, e))
+    })?;
+    let mut reader = BufReader::new(file);
+
+    rustls_pemfile::crls(&mut reader)
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| AscendingError::Certificate(format!("cannot parse {}: {}", filename, e)))
+}
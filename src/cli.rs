@@ -0,0 +1,9 @@
+mod args;
+mod commands;
+mod error;
+mod rotate;
+
+pub use args::*;
+pub use commands::*;
+pub use error::*;
+pub use rotate::*;
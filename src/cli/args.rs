@@ -0,0 +1,146 @@
+use crate::{certs::DEV_SANS, console::MAX_BAN_HOURS, gametypes::UserAccess};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  the command failed, such as a database or network error
+  2  invalid arguments
+  3  invalid configuration
+  4  the account was not found
+  5  the account or revoked certificate already exists";
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Ascending login server", after_help = EXIT_CODES_HELP)]
+pub struct Cli {
+    /// Print results and errors as JSON on stdout.
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Run the login server. This is the default when no command is given.
+    Serve,
+    /// Create or update the database tables.
+    Migrate,
+    /// Load settings.toml and the environment overrides and report every problem found.
+    CheckConfig,
+    /// Create an account. The password is read from the first line of stdin.
+    CreateAccount {
+        username: String,
+        email: String,
+        /// None, Monitor or Admin.
+        #[arg(long, default_value = "none", value_parser = parse_access)]
+        access: UserAccess,
+    },
+    /// Require a password reset on the next login and print the reset code.
+    ResetPassword { username: String },
+    /// Ban an account. Players that are online stay until they reconnect, use the
+    /// console or admin API to also kick them.
+    Ban {
+        username: String,
+        /// Hours until the ban expires. Bans permanently when left out.
+        #[arg(long, value_parser = clap::value_parser!(i64).range(1..=MAX_BAN_HOURS))]
+        hours: Option<i64>,
+    },
+    /// Lift a ban.
+    Unban { username: String },
+    /// List the accounts that are logged in to a game server.
+    ListSessions {
+        #[arg(long, default_value_t = 100)]
+        limit: i64,
+    },
+    /// Create a development CA and server certificate, plus optional client and game
+    /// server certificates. Files that already exist are kept unless --force is given.
+    GenCerts {
+        /// Directory the certificates and keys are written to.
+        #[arg(long, default_value = "keys")]
+        out: PathBuf,
+        /// DNS name or IP address the issued certificates are valid for. Repeat for more.
+        #[arg(long = "san", default_values = DEV_SANS)]
+        sans: Vec<String>,
+        /// Also create client.crt and client-key.pem.
+        #[arg(long)]
+        client: bool,
+        /// Also create game-server.crt and game-server-key.pem.
+        #[arg(long)]
+        game_server: bool,
+        /// Days the issued certificates stay valid.
+        #[arg(long, default_value_t = 365, value_parser = clap::value_parser!(i64).range(1..))]
+        days: i64,
+        /// Replace existing files, including the CA.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Refuse a client certificate. Logins with it are refused at once, and the running
+    /// server is asked through the admin API to refuse its TLS handshakes too.
+    RevokeCert {
+        /// SHA-256 fingerprint of the certificate, or a PEM certificate file.
+        cert: String,
+        #[arg(long, default_value = "")]
+        reason: String,
+        /// Client certificate for an admin API that requires mTLS.
+        #[arg(long, requires = "client_key")]
+        client_cert: Option<String>,
+        #[arg(long, requires = "client_cert")]
+        client_key: Option<String>,
+    },
+    /// Print the protocol schema: every packet ID, field, type and string limit, with
+    /// the protocol version and hash clients send in `Hello`.
+    ProtocolSchema {
+        /// Write the schema to this file instead of printing it.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Ask the running server to rotate its login token keys through the admin API.
+    RotateKeys {
+        /// Client certificate for an admin API that requires mTLS.
+        #[arg(long, requires = "client_key")]
+        client_cert: Option<String>,
+        #[arg(long, requires = "client_cert")]
+        client_key: Option<String>,
+    },
+}
+
+fn parse_access(value: &str) -> Result<UserAccess, String> {
+    match value.to_lowercase().as_str() {
+        "none" => Ok(UserAccess::None),
+        "monitor" => Ok(UserAccess::Monitor),
+        "admin" => Ok(UserAccess::Admin),
+        _ => Err(format!(
+            "'{}' is not an access level, use none, monitor or admin",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_certs_sans(args: &[&str]) -> Vec<String> {
+        let cli = Cli::try_parse_from(["ascending_login_server", "gen-certs"].iter().chain(args))
+            .expect("arguments should parse");
+
+        match cli.command {
+            Some(CliCommand::GenCerts { sans, .. }) => sans,
+            command => panic!("parsed {:?}", command),
+        }
+    }
+
+    #[test]
+    fn gen_certs_defaults_to_the_development_names() {
+        assert_eq!(gen_certs_sans(&[]), DEV_SANS);
+    }
+
+    #[test]
+    fn gen_certs_names_replace_the_defaults() {
+        assert_eq!(
+            gen_certs_sans(&["--san", "login.example.com", "--san", "10.0.0.5"]),
+            ["login.example.com", "10.0.0.5"]
+        );
+    }
+}
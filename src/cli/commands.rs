@@ -0,0 +1,522 @@
+use crate::{
+    certs::*,
+    console::Command,
+    containers::{
+        establish_connection, load_config, load_config_without_tls_files, AccountStoreKind, Config,
+        CONFIG_PATH,
+    },
+    gametypes::*,
+    players::*,
+    socket::ProtocolSchema,
+    sql::*,
+};
+use chrono::Utc;
+use rand::distributions::{Alphanumeric, DistString};
+use regex::Regex;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{request_cert_reload, request_key_rotation, CliCommand, CliError, CliResult};
+
+/// Address written to the account and audit log for changes made from the command line.
+const CLI_ADDRESS: &str = "cli";
+
+/// What a command prints: a message for people, or `data` when `--json` is set.
+pub struct Report {
+    pub message: String,
+    pub data: Value,
+}
+
+/// Runs an admin command and prints its result. Returns the process exit code.
+pub async fn run_cli(command: CliCommand, json: bool) -> i32 {
+    let result = match command {
+        CliCommand::Serve => unreachable!("the server is started from main"),
+        CliCommand::Migrate => migrate().await,
+        CliCommand::CheckConfig => check_config(),
+        CliCommand::CreateAccount {
+            username,
+            email,
+            access,
+        } => create_account(username, email, access).await,
+        CliCommand::ResetPassword { username } => reset_password(username).await,
+        CliCommand::Ban { username, hours } => ban(username, hours).await,
+        CliCommand::Unban { username } => unban(username).await,
+        CliCommand::ListSessions { limit } => list_sessions(limit).await,
+        CliCommand::GenCerts {
+            out,
+            sans,
+            client,
+            game_server,
+            days,
+            force,
+        } => gen_certs(&out, &sans, client, game_server, days, force),
+        CliCommand::RevokeCert {
+            cert,
+            reason,
+            client_cert,
+            client_key,
+        } => revoke_client_cert(cert, reason, client_cert.zip(client_key)).await,
+        CliCommand::ProtocolSchema { out } => protocol_schema(out.as_deref()),
+        CliCommand::RotateKeys {
+            client_cert,
+            client_key,
+        } => rotate_keys(client_cert.zip(client_key)).await,
+    };
+
+    match result {
+        Ok(report) => {
+            if json {
+                println!("{}", report.data);
+            } else {
+                println!("{}", report.message);
+            }
+
+            0
+        }
+        Err(error) => {
+            if json {
+                println!("{}", error.to_json());
+            } else {
+                eprintln!("{}", error);
+            }
+
+            error.exit_code()
+        }
+    }
+}
+
+async fn connect() -> CliResult<(Config, PgPool)> {
+    let config = load_config_without_tls_files(CONFIG_PATH)?;
+
+    // The server's memory store is gone with its process, there is nothing to manage.
+    if config.account_store == AccountStoreKind::Memory {
+        return Err(CliError::Config(vec![
+            "account_store is 'Memory', account commands need the 'Postgres' store.".into(),
+        ]));
+    }
+
+    let conn = establish_connection(&config).await?;
+
+    Ok((config, conn))
+}
+
+async fn account(conn: &PgPool, username: &str) -> CliResult<AccountInfo> {
+    find_account_by_name(conn, username)
+        .await?
+        .ok_or_else(|| CliError::NotFound(format!("No account named {}.", username)))
+}
+
+/// Records the change in the logs table like the admin API does.
+async fn audit(conn: &PgPool, uid: i64, message: String) {
+    let log = PGLog::new(0, uid, LogType::Admin, message, CLI_ADDRESS.into());
+
+    if let Err(e) = insert_log(conn, &log).await {
+        eprintln!("Failed to write the audit log: {}", e);
+    }
+}
+
+async fn migrate() -> CliResult<Report> {
+    let (_, conn) = connect().await?;
+    initiate(&conn).await?;
+
+    Ok(Report {
+        message: "Database is up to date.".into(),
+        data: json!({ "migrated": true }),
+    })
+}
+
+fn check_config() -> CliResult<Report> {
+    load_config(CONFIG_PATH)?;
+
+    Ok(Report {
+        message: "Configuration is valid.".into(),
+        data: json!({ "valid": true }),
+    })
+}
+
+fn protocol_schema(out: Option<&Path>) -> CliResult<Report> {
+    let schema = ProtocolSchema::new();
+    let data = serde_json::to_value(&schema)
+        .map_err(|e| CliError::Failed(format!("Could not serialize the schema: {}", e)))?;
+    let pretty = serde_json::to_string_pretty(&schema)
+        .map_err(|e| CliError::Failed(format!("Could not serialize the schema: {}", e)))?;
+
+    let message = match out {
+        Some(path) => {
+            std::fs::write(path, pretty + "\n").map_err(|e| {
+                CliError::Failed(format!("Could not write {}: {}", path.display(), e))
+            })?;
+
+            format!(
+                "Wrote protocol {} with hash {} to {}.",
+                schema.version,
+                schema.hash,
+                path.display()
+            )
+        }
+        None => pretty,
+    };
+
+    Ok(Report { message, data })
+}
+
+fn read_password() -> CliResult<String> {
+    let stdin = io::stdin();
+
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        let _ = io::stderr().flush();
+    }
+
+    let mut password = String::new();
+    stdin
+        .lock()
+        .read_line(&mut password)
+        .map_err(|e| CliError::Failed(format!("Could not read the password: {}", e)))?;
+
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn create_account(username: String, email: String, access: UserAccess) -> CliResult<Report> {
+    // The same rules players get when registering from the client.
+    if username.is_empty() || username.len() >= 64 || !username.chars().all(is_name_acceptable) {
+        return Err(CliError::Invalid(
+            "Username must be 1 to 63 characters of letters, numbers or !$&_~.".into(),
+        ));
+    }
+
+    let email_regex = Regex::new(EMAIL_PATTERN).map_err(AscendingError::from)?;
+
+    if !email_regex.is_match(&email) {
+        return Err(CliError::Invalid("Email must be an actual email.".into()));
+    }
+
+    let password = read_password()?;
+
+    if password.is_empty() || password.len() >= 128 || !password.chars().all(is_password_acceptable)
+    {
+        return Err(CliError::Invalid(
+            "Password must be 1 to 127 characters of letters, numbers or !$&_%@?~.".into(),
+        ));
+    }
+
+    let (_, conn) = connect().await?;
+
+    match check_existance(&conn, &username, &email).await? {
+        1 => {
+            return Err(CliError::Exists(format!(
+                "Username {} already exists.",
+                username
+            )))
+        }
+        2 => return Err(CliError::Exists(format!("Email {} already exists.", email))),
+        _ => {}
+    }
+
+    let mut player = Player::default();
+    player.username.clone_from(&username);
+    player.email.clone_from(&email);
+    player.access = access;
+
+    let uid = insert_player(&conn, CLI_ADDRESS, &player, hash_password(&password)).await?;
+
+    audit(
+        &conn,
+        uid,
+        format!(
+            "Created account with {:?} access from the command line",
+            access
+        ),
+    )
+    .await;
+
+    Ok(Report {
+        message: format!("Created account {} with uid {}.", username, uid),
+        data: json!({
+            "uid": uid,
+            "username": username,
+            "email": email,
+            "access": access,
+        }),
+    })
+}
+
+async fn reset_password(username: String) -> CliResult<Report> {
+    let (_, conn) = connect().await?;
+    let account = account(&conn, &username).await?;
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+
+    if !force_password_reset(&conn, account.uid, &code).await? {
+        return Err(CliError::NotFound(format!(
+            "No account named {}.",
+            username
+        )));
+    }
+
+    audit(
+        &conn,
+        account.uid,
+        "Forced a password reset from the command line".into(),
+    )
+    .await;
+
+    Ok(Report {
+        message: format!("Password reset code for {}: {}", username, code),
+        data: json!({ "uid": account.uid, "username": username, "code": code }),
+    })
+}
+
+async fn ban(username: String, hours: Option<i64>) -> CliResult<Report> {
+    let (_, conn) = connect().await?;
+    let expires =
+        Command::ban_expires(hours, Utc::now()).map_err(|e| CliError::Invalid(e.to_string()))?;
+
+    let Some(banned) = ban_player(&conn, &username, expires).await? else {
+        return Err(CliError::NotFound(format!(
+            "No account named {}.",
+            username
+        )));
+    };
+
+    audit(
+        &conn,
+        banned.uid,
+        format!("Banned until {:?} from the command line", expires),
+    )
+    .await;
+
+    let mut message = match expires {
+        Some(expires) => format!("Banned {} until {}.", username, expires),
+        None => format!("Banned {} permanently.", username),
+    };
+
+    if let Some(server) = &banned.current_server {
+        message.push_str(&format!(
+            " They are still on {}, kick them from the console to disconnect them now.",
+            server
+        ));
+    }
+
+    Ok(Report {
+        message,
+        data: json!({
+            "uid": banned.uid,
+            "username": username,
+            "expires": expires,
+            "current_server": banned.current_server,
+        }),
+    })
+}
+
+async fn unban(username: String) -> CliResult<Report> {
+    let (_, conn) = connect().await?;
+    let account = account(&conn, &username).await?;
+
+    if !unban_player(&conn, account.uid).await? {
+        return Err(CliError::NotFound(format!(
+            "No account named {}.",
+            username
+        )));
+    }
+
+    audit(&conn, account.uid, "Unbanned from the command line".into()).await;
+
+    Ok(Report {
+        message: format!("Unbanned {}.", username),
+        data: json!({ "uid": account.uid, "username": username }),
+    })
+}
+
+async fn list_sessions(limit: i64) -> CliResult<Report> {
+    if limit <= 0 {
+        return Err(CliError::Invalid("limit must be positive".into()));
+    }
+
+    let (_, conn) = connect().await?;
+    let accounts = find_online_accounts(&conn, limit).await?;
+
+    let message = if accounts.is_empty() {
+        "No players are logged in.".to_string()
+    } else {
+        accounts
+            .iter()
+            .map(|account| {
+                format!(
+                    "{:>8}  {:<24}  {}",
+                    account.uid,
+                    account.username,
+                    account.current_server.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    Ok(Report {
+        message,
+        data: json!(accounts),
+    })
+}
+
+async fn revoke_client_cert(
+    cert: String,
+    reason: String,
+    client_identity: Option<(String, String)>,
+) -> CliResult<Report> {
+    let fingerprint = if Path::new(&cert).is_file() {
+        let certs = load_certs(&cert)?;
+        fingerprint(&certs[0])
+    } else {
+        normalize_fingerprint(&cert).ok_or_else(|| {
+            CliError::Invalid(format!(
+                "{} is neither a certificate file nor a SHA-256 fingerprint.",
+                cert
+            ))
+        })?
+    };
+
+    let (config, conn) = connect().await?;
+
+    if !revoke_cert(&conn, &fingerprint, &reason).await? {
+        return Err(CliError::Exists(format!(
+            "Certificate {} is already revoked.",
+            fingerprint
+        )));
+    }
+
+    audit(
+        &conn,
+        0,
+        format!(
+            "Revoked client certificate {} from the command line",
+            fingerprint
+        ),
+    )
+    .await;
+
+    // Logins check the table, the handshake only checks what the server last loaded.
+    let reload = match &config.admin_api {
+        Some(admin_api) => request_cert_reload(&config, admin_api, client_identity).await,
+        None => Err(CliError::Invalid(
+            "There is no [admin_api] section to reach it through.".into(),
+        )),
+    };
+
+    let message = match &reload {
+        Ok(_) => format!("Revoked certificate {}.", fingerprint),
+        Err(e) => format!(
+            "Revoked certificate {}. Logins with it are refused, its handshakes once \
+             reloadcerts runs on the server. {}",
+            fingerprint, e
+        ),
+    };
+
+    Ok(Report {
+        message,
+        data: json!({ "fingerprint": fingerprint, "reason": reason, "reloaded": reload.is_ok() }),
+    })
+}
+
+async fn rotate_keys(client_identity: Option<(String, String)>) -> CliResult<Report> {
+    let config = load_config_without_tls_files(CONFIG_PATH)?;
+
+    let Some(admin_api) = &config.admin_api else {
+        return Err(CliError::Invalid(
+            "rotate-keys needs the [admin_api] section so it can reach the running server.".into(),
+        ));
+    };
+
+    let message = request_key_rotation(&config, admin_api, client_identity).await?;
+
+    Ok(Report {
+        data: json!({ "message": message }),
+        message,
+    })
+}
+
+fn gen_certs(
+    out: &Path,
+    sans: &[String],
+    client: bool,
+    game_server: bool,
+    days: i64,
+    force: bool,
+) -> CliResult<Report> {
+    let mut created: Vec<PathBuf> = Vec::new();
+    let mut kept: Vec<PathBuf> = Vec::new();
+
+    let ca_cert = out.join(CA_CERT_FILE);
+    let ca_key = out.join(CA_KEY_FILE);
+
+    let new_ca = force || !ca_cert.is_file();
+
+    let ca = if !new_ca && ca_key.is_file() {
+        kept.extend([ca_cert.clone(), ca_key.clone()]);
+        CertificateAuthority::load(&ca_cert, &ca_key)?
+    } else if !new_ca {
+        return Err(CliError::Exists(format!(
+            "{} exists without {}, pass --force to replace the CA.",
+            ca_cert.display(),
+            ca_key.display()
+        )));
+    } else {
+        let ca = CertificateAuthority::generate()?;
+        ca.save(&ca_cert, &ca_key)?;
+        created.extend([ca_cert.clone(), ca_key.clone()]);
+        ca
+    };
+
+    let mut leaves = vec![(CertKind::Server, SERVER_CERT_FILE, SERVER_KEY_FILE)];
+
+    if client {
+        leaves.push((CertKind::Client, CLIENT_CERT_FILE, CLIENT_KEY_FILE));
+    }
+
+    if game_server {
+        leaves.push((
+            CertKind::GameServer,
+            GAME_SERVER_CERT_FILE,
+            GAME_SERVER_KEY_FILE,
+        ));
+    }
+
+    for (kind, cert_file, key_file) in leaves {
+        let cert_path = out.join(cert_file);
+        let key_path = out.join(key_file);
+
+        // A new CA invalidates every certificate signed by the old one.
+        if !new_ca && cert_path.is_file() && key_path.is_file() {
+            kept.extend([cert_path, key_path]);
+            continue;
+        }
+
+        ca.issue(kind, sans, days)?.save(&cert_path, &key_path)?;
+        created.extend([cert_path, key_path]);
+    }
+
+    let list = |paths: &[PathBuf]| {
+        paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let mut lines = Vec::new();
+
+    if !created.is_empty() {
+        lines.push(format!("Created: {}", list(&created).join(", ")));
+    }
+
+    if !kept.is_empty() {
+        lines.push(format!("Kept: {}", list(&kept).join(", ")));
+    }
+
+    Ok(Report {
+        message: lines.join("\n"),
+        data: json!({ "created": list(&created), "kept": list(&kept) }),
+    })
+}
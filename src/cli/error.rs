@@ -0,0 +1,72 @@
+use crate::gametypes::AscendingError;
+use serde_json::json;
+use std::fmt;
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_INVALID: i32 = 2;
+pub const EXIT_CONFIG: i32 = 3;
+pub const EXIT_NOT_FOUND: i32 = 4;
+pub const EXIT_EXISTS: i32 = 5;
+
+/// Why a command failed. Each kind has its own exit code so scripts can tell them apart.
+#[derive(Debug)]
+pub enum CliError {
+    Invalid(String),
+    Config(Vec<String>),
+    NotFound(String),
+    Exists(String),
+    Failed(String),
+}
+
+pub type CliResult<T> = std::result::Result<T, CliError>;
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Invalid(_) => EXIT_INVALID,
+            CliError::Config(_) => EXIT_CONFIG,
+            CliError::NotFound(_) => EXIT_NOT_FOUND,
+            CliError::Exists(_) => EXIT_EXISTS,
+            CliError::Failed(_) => EXIT_FAILURE,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            CliError::Config(errors) => json!({
+                "error": "invalid configuration",
+                "errors": errors,
+            }),
+            error => json!({ "error": error.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Config(errors) => {
+                write!(f, "Invalid configuration:")?;
+
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+
+                Ok(())
+            }
+            CliError::Invalid(message)
+            | CliError::NotFound(message)
+            | CliError::Exists(message)
+            | CliError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<AscendingError> for CliError {
+    fn from(error: AscendingError) -> Self {
+        match error {
+            AscendingError::InvalidConfig(errors) => CliError::Config(errors),
+            error => CliError::Failed(error.to_string()),
+        }
+    }
+}
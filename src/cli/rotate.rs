@@ -0,0 +1,156 @@
+use crate::{
+    certs::{load_certs, load_private_key},
+    containers::{AdminApiConfig, Config},
+};
+use rustls::{
+    crypto::ring as provider,
+    pki_types::{IpAddr as TlsIpAddr, ServerName},
+    ClientConfig, RootCertStore,
+};
+use serde_json::Value;
+use std::{net::SocketAddr, path::Path, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::TlsConnector;
+
+use super::{CliError, CliResult};
+
+/// Sends `POST /keys/rotate` to the admin API of the running server. The JWT keys only
+/// live in memory, so rotating them has to happen inside the server.
+pub async fn request_key_rotation(
+    config: &Config,
+    admin_api: &AdminApiConfig,
+    client_identity: Option<(String, String)>,
+) -> CliResult<String> {
+    post_admin_api(config, admin_api, client_identity, "/keys/rotate").await
+}
+
+/// Sends `POST /certs/reload` so the running server picks up newly revoked certificates.
+pub async fn request_cert_reload(
+    config: &Config,
+    admin_api: &AdminApiConfig,
+    client_identity: Option<(String, String)>,
+) -> CliResult<String> {
+    post_admin_api(config, admin_api, client_identity, "/certs/reload").await
+}
+
+async fn post_admin_api(
+    config: &Config,
+    admin_api: &AdminApiConfig,
+    client_identity: Option<(String, String)>,
+    path: &str,
+) -> CliResult<String> {
+    let addr: SocketAddr = admin_api.listen.parse().map_err(|_| {
+        CliError::Invalid(format!(
+            "admin_api.listen '{}' is invalid",
+            admin_api.listen
+        ))
+    })?;
+
+    let stream = TcpStream::connect(addr).await.map_err(|e| {
+        CliError::Failed(format!("Could not reach the admin API at {}: {}", addr, e))
+    })?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        path, addr
+    );
+
+    let response = if admin_api.tls_cert.is_some() {
+        let Some((cert, key)) = client_identity else {
+            return Err(CliError::Invalid(
+                "The admin API uses mTLS, pass --client-cert and --client-key.".into(),
+            ));
+        };
+
+        let connector = tls_connector(config, admin_api, &cert, &key)?;
+        let server_name = ServerName::IpAddress(TlsIpAddr::from(addr.ip()));
+        let stream = connector.connect(server_name, stream).await.map_err(|e| {
+            CliError::Failed(format!("TLS handshake with the admin API failed: {}", e))
+        })?;
+
+        exchange(stream, &request).await?
+    } else {
+        exchange(stream, &request).await?
+    };
+
+    parse_response(&response)
+}
+
+fn tls_connector(
+    config: &Config,
+    admin_api: &AdminApiConfig,
+    cert: &str,
+    key: &str,
+) -> CliResult<TlsConnector> {
+    // The admin certificate may be signed by either CA.
+    let mut roots = RootCertStore::empty();
+
+    for ca in [Some(&config.ca_root), admin_api.client_ca.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if Path::new(ca).is_file() {
+            roots.add_parsable_certificates(load_certs(ca)?);
+        }
+    }
+
+    let certs = load_certs(cert)?;
+    let key = load_private_key(key)?;
+
+    let tls_config = ClientConfig::builder_with_provider(provider::default_provider().into())
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| {
+            builder
+                .with_root_certificates(roots)
+                .with_client_auth_cert(certs, key)
+        })
+        .map_err(|e| CliError::Failed(format!("Invalid client certificate: {}", e)))?;
+
+    Ok(TlsConnector::from(Arc::new(tls_config)))
+}
+
+async fn exchange<S>(mut stream: S, request: &str) -> CliResult<String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let failed = |e: std::io::Error| CliError::Failed(format!("Admin API request failed: {}", e));
+
+    stream.write_all(request.as_bytes()).await.map_err(failed)?;
+    stream.flush().await.map_err(failed)?;
+
+    let mut response = Vec::new();
+
+    // Servers closing TLS without close_notify still sent a full response.
+    match stream.read_to_end(&mut response).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => {}
+        Err(e) => return Err(failed(e)),
+    }
+
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+fn parse_response(response: &str) -> CliResult<String> {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response, ""));
+    let status: u16 = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| CliError::Failed("The admin API sent an invalid response.".into()))?;
+
+    let body: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let text = |field: &str| body.get(field).and_then(Value::as_str).map(str::to_string);
+
+    if (200..300).contains(&status) {
+        Ok(text("message").unwrap_or_else(|| "Done.".into()))
+    } else {
+        Err(CliError::Failed(format!(
+            "The admin API answered {}: {}",
+            status,
+            text("error").unwrap_or_default()
+        )))
+    }
+}
@@ -0,0 +1,13 @@
+mod command;
+mod handler;
+mod input;
+mod output;
+mod signals;
+mod watch;
+
+pub use command::*;
+pub use handler::*;
+pub use input::*;
+pub use output::*;
+pub use signals::*;
+pub use watch::*;
@@ -0,0 +1,282 @@
+use crate::gametypes::*;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Longest timed ban, about a hundred years. Longer bans should be permanent.
+pub const MAX_BAN_HOURS: i64 = 24 * 365 * 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Command {
+    Help,
+    ListServers,
+    ListClients,
+    Kick {
+        token: usize,
+    },
+    Ban {
+        username: String,
+        /// None bans the account permanently.
+        hours: Option<i64>,
+    },
+    RotateKeys,
+    /// None toggles the current maintenance state.
+    Maintenance(Option<bool>),
+    Broadcast(String),
+    ReloadConfig,
+    /// Loads the TLS certificate and key from disk for new connections.
+    ReloadCerts,
+    Shutdown,
+}
+
+impl Command {
+    /// Parses a single console line such as `kick 12` or `ban someone 24`.
+    pub fn parse(line: &str) -> Result<Command> {
+        let line = line.trim();
+        let (name, rest) = match line.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (line, ""),
+        };
+        let mut args = rest.split_whitespace();
+
+        let command = match name.to_lowercase().as_str() {
+            "" => return Err(AscendingError::InvalidCommand("no command given".into())),
+            "help" | "?" => Command::Help,
+            "servers" => Command::ListServers,
+            "clients" => Command::ListClients,
+            "list" => match args.next() {
+                Some("servers") => Command::ListServers,
+                Some("clients") => Command::ListClients,
+                _ => return Err(usage("list <servers|clients>")),
+            },
+            "kick" => Command::Kick {
+                token: parse_arg(args.next(), "kick <token>")?,
+            },
+            "ban" => {
+                let username = args.next().ok_or_else(|| usage("ban <username> [hours]"))?;
+                let hours = match args.next() {
+                    Some(hours) => {
+                        let hours = parse_arg(Some(hours), "ban <username> [hours]")?;
+                        Command::check_ban_hours(hours)?;
+                        Some(hours)
+                    }
+                    None => None,
+                };
+
+                Command::Ban {
+                    username: username.to_string(),
+                    hours,
+                }
+            }
+            "rotatekeys" | "rotate-keys" => Command::RotateKeys,
+            "maintenance" => match args.next() {
+                Some("on") => Command::Maintenance(Some(true)),
+                Some("off") => Command::Maintenance(Some(false)),
+                None => Command::Maintenance(None),
+                _ => return Err(usage("maintenance [on|off]")),
+            },
+            "broadcast" => {
+                if rest.is_empty() {
+                    return Err(usage("broadcast <message>"));
+                }
+
+                Command::Broadcast(rest.to_string())
+            }
+            "reload" => Command::ReloadConfig,
+            "reloadcerts" | "reload-certs" => Command::ReloadCerts,
+            "shutdown" | "quit" | "exit" => Command::Shutdown,
+            unknown => {
+                return Err(AscendingError::InvalidCommand(format!(
+                    "unknown command `{}`, type `help` for a list of commands",
+                    unknown
+                )))
+            }
+        };
+
+        if args.next().is_some() && !matches!(command, Command::Broadcast(_)) {
+            return Err(AscendingError::InvalidCommand(format!(
+                "too many arguments for `{}`",
+                name
+            )));
+        }
+
+        Ok(command)
+    }
+
+    /// Refuses ban lengths that would already have expired or are longer than
+    /// `MAX_BAN_HOURS`.
+    pub fn check_ban_hours(hours: i64) -> Result<()> {
+        if !(1..=MAX_BAN_HOURS).contains(&hours) {
+            return Err(AscendingError::InvalidCommand(format!(
+                "ban hours must be between 1 and {}",
+                MAX_BAN_HOURS
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Converts the optional ban length from `now` into the date stored in the database.
+    pub fn ban_expires(hours: Option<i64>, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let Some(hours) = hours else {
+            return Ok(None);
+        };
+
+        Command::check_ban_hours(hours)?;
+
+        Duration::try_hours(hours)
+            .and_then(|length| now.checked_add_signed(length))
+            .map(Some)
+            .ok_or_else(|| {
+                AscendingError::InvalidCommand(format!("a ban of {} hours is too long", hours))
+            })
+    }
+}
+
+fn usage(usage: &str) -> AscendingError {
+    AscendingError::InvalidCommand(format!("usage: {}", usage))
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&str>, usage_text: &str) -> Result<T> {
+    arg.and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| usage(usage_text))
+}
+
+pub const COMMAND_HELP: &str = "\
+help                      Shows this list.
+list servers              Lists the connected game servers.
+list clients              Lists the connected clients.
+kick <token>              Disconnects a client.
+ban <username> [hours]    Bans an account, permanently if no hours are given.
+rotatekeys                Rotates the login token keys now.
+maintenance [on|off]      Toggles maintenance mode. Only admins may log in.
+broadcast <message>       Sends a message to every connected client.
+reload                    Reloads the reloadable parts of settings.toml.
+reloadcerts               Reloads the TLS certificate, key and revocation lists.
+shutdown                  Stops the login server.";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Command {
+        Command::parse(line).unwrap()
+    }
+
+    /// The message of the error `line` is refused with.
+    fn refused(line: &str) -> String {
+        match Command::parse(line) {
+            Err(AscendingError::InvalidCommand(message)) => message,
+            other => panic!("`{}` was not refused: {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn parses_every_command() {
+        assert_eq!(parse("help"), Command::Help);
+        assert_eq!(parse("?"), Command::Help);
+        assert_eq!(parse("servers"), Command::ListServers);
+        assert_eq!(parse("list servers"), Command::ListServers);
+        assert_eq!(parse("clients"), Command::ListClients);
+        assert_eq!(parse("list clients"), Command::ListClients);
+        assert_eq!(parse("kick 12"), Command::Kick { token: 12 });
+        assert_eq!(parse("rotatekeys"), Command::RotateKeys);
+        assert_eq!(parse("rotate-keys"), Command::RotateKeys);
+        assert_eq!(parse("maintenance"), Command::Maintenance(None));
+        assert_eq!(parse("maintenance on"), Command::Maintenance(Some(true)));
+        assert_eq!(parse("maintenance off"), Command::Maintenance(Some(false)));
+        assert_eq!(parse("reload"), Command::ReloadConfig);
+        assert_eq!(parse("reloadcerts"), Command::ReloadCerts);
+        assert_eq!(parse("reload-certs"), Command::ReloadCerts);
+
+        for line in ["shutdown", "quit", "exit"] {
+            assert_eq!(parse(line), Command::Shutdown);
+        }
+    }
+
+    #[test]
+    fn ignores_command_case_and_surrounding_whitespace() {
+        assert_eq!(parse("  KICK   7  "), Command::Kick { token: 7 });
+        assert_eq!(parse("List servers"), Command::ListServers);
+    }
+
+    #[test]
+    fn parses_bans_with_and_without_hours() {
+        assert_eq!(
+            parse("ban someone"),
+            Command::Ban {
+                username: "someone".into(),
+                hours: None,
+            }
+        );
+        assert_eq!(
+            parse("ban someone 24"),
+            Command::Ban {
+                username: "someone".into(),
+                hours: Some(24),
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_the_whole_broadcast_message() {
+        assert_eq!(
+            parse("broadcast  Restart in 5 minutes!"),
+            Command::Broadcast("Restart in 5 minutes!".into())
+        );
+    }
+
+    #[test]
+    fn refuses_bad_arguments() {
+        assert_eq!(refused(""), "no command given");
+        assert_eq!(refused("   "), "no command given");
+        assert_eq!(refused("list"), "usage: list <servers|clients>");
+        assert_eq!(refused("list players"), "usage: list <servers|clients>");
+        assert_eq!(refused("kick"), "usage: kick <token>");
+        assert_eq!(refused("kick twelve"), "usage: kick <token>");
+        assert_eq!(refused("kick -1"), "usage: kick <token>");
+        assert_eq!(refused("ban"), "usage: ban <username> [hours]");
+        assert_eq!(refused("ban someone soon"), "usage: ban <username> [hours]");
+        assert_eq!(refused("maintenance maybe"), "usage: maintenance [on|off]");
+        assert_eq!(refused("broadcast"), "usage: broadcast <message>");
+        assert_eq!(refused("kick 1 2"), "too many arguments for `kick`");
+        assert_eq!(refused("shutdown now"), "too many arguments for `shutdown`");
+        assert!(refused("dance").starts_with("unknown command `dance`"));
+    }
+
+    #[test]
+    fn converts_ban_hours_into_an_expiry() {
+        let now = Utc::now();
+
+        assert_eq!(Command::ban_expires(None, now).unwrap(), None);
+        assert_eq!(
+            Command::ban_expires(Some(24), now).unwrap(),
+            Some(now + Duration::try_hours(24).unwrap())
+        );
+    }
+
+    #[test]
+    fn refuses_bans_that_have_expired_or_are_too_long() {
+        let expected = format!("ban hours must be between 1 and {}", MAX_BAN_HOURS);
+
+        assert_eq!(refused("ban someone 0"), expected);
+        assert_eq!(refused("ban someone -5"), expected);
+        assert_eq!(
+            refused(&format!("ban someone {}", MAX_BAN_HOURS + 1)),
+            expected
+        );
+        assert_eq!(refused(&format!("ban someone {}", i64::MAX)), expected);
+        assert_eq!(
+            parse(&format!("ban someone {}", MAX_BAN_HOURS)),
+            Command::Ban {
+                username: "someone".into(),
+                hours: Some(MAX_BAN_HOURS),
+            }
+        );
+
+        let now = Utc::now();
+
+        for hours in [0, -1, MAX_BAN_HOURS + 1, i64::MAX] {
+            assert!(Command::ban_expires(Some(hours), now).is_err());
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use super::*;
+use crate::{containers::Storage, gametypes::*, locales::Message, socket::*};
+use log::{error, info};
+
+/// Runs every command queued since the last tick.
+pub async fn process_console_requests(storage: &mut Storage) {
+    while let Ok(request) = storage.console.try_recv() {
+        let output = match execute_command(storage, request.command).await {
+            Ok(output) => output,
+            Err(e) => CommandOutput::Error(e.to_string()),
+        };
+
+        match request.reply {
+            Some(reply) => {
+                let _ = reply.send(output);
+            }
+            None => println!("{}", output),
+        }
+    }
+}
+
+pub async fn execute_command(storage: &mut Storage, command: Command) -> Result<CommandOutput> {
+    match command {
+        Command::Help => Ok(CommandOutput::Help),
+        Command::ListServers => {
+            let mut entries = Vec::with_capacity(storage.servers.len());
+
+            for (token, info) in &storage.servers {
+                let game_server = storage.server.read().await.game_servers.get(token).cloned();
+                let (queued_packets, queued_bytes) = match game_server {
+                    Some(game_server) => {
+                        let game_server = game_server.lock().await;
+                        (game_server.sends.len(), game_server.sends.bytes())
+                    }
+                    None => (0, 0),
+                };
+
+                entries.push(ServerEntry {
+                    token: token.0,
+                    info: info.clone(),
+                    queued_packets,
+                    queued_bytes,
+                });
+            }
+
+            Ok(CommandOutput::Servers(entries))
+        }
+        Command::ListClients => {
+            let clients: Vec<_> = storage
+                .server
+                .read()
+                .await
+                .clients
+                .values()
+                .cloned()
+                .collect();
+            let mut entries = Vec::with_capacity(clients.len());
+
+            for client in clients {
+                let client = client.lock().await;
+
+                entries.push(ClientEntry {
+                    token: client.token.0,
+                    addr: client.addr.clone(),
+                    queued_packets: client.sends.len(),
+                    queued_bytes: client.sends.bytes(),
+                });
+            }
+
+            entries.sort_by_key(|entry| entry.token);
+            Ok(CommandOutput::Clients(entries))
+        }
+        Command::Kick { token } => {
+            let client = storage
+                .server
+                .read()
+                .await
+                .clients
+                .get(&mio::Token(token))
+                .cloned();
+
+            let Some(client) = client else {
+                return Ok(CommandOutput::Error(format!(
+                    "No client with token {}.",
+                    token
+                )));
+            };
+
+            let mut client = client.lock().await;
+
+            info!(addr = client.addr, token = token; "Console kicked a client");
+            let message = storage.locales.text(&client.locale, Message::Kicked, &[]);
+            send_infomsg(storage, &mut client, message, true).await?;
+            client.set_to_closing(storage).await?;
+
+            Ok(CommandOutput::Done(format!("Kicked client {}.", token)))
+        }
+        Command::Ban { username, hours } => {
+            let expires = match Command::ban_expires(hours, storage.clock.now()) {
+                Ok(expires) => expires,
+                Err(e) => return Ok(CommandOutput::Error(e.to_string())),
+            };
+
+            let Some(banned) = storage.accounts.ban_player(&username, expires).await? else {
+                return Ok(CommandOutput::Error(format!(
+                    "No account named {}.",
+                    username
+                )));
+            };
+
+            info!(uid = banned.uid; "Console banned {} until {:?}", username, expires);
+            storage.metrics.bans.inc();
+
+            if let Some(current) = banned.current_server {
+                kill_game_client(storage, &current, banned.uid).await?;
+            }
+
+            Ok(CommandOutput::Done(match expires {
+                Some(expires) => format!("Banned {} until {}.", username, expires),
+                None => format!("Banned {} permanently.", username),
+            }))
+        }
+        Command::RotateKeys => {
+            storage.keys.rotate(storage.clock.now());
+            info!("Console rotated the login token keys");
+
+            Ok(CommandOutput::Done("Login token keys rotated.".into()))
+        }
+        Command::Maintenance(state) => {
+            storage.maintenance = state.unwrap_or(!storage.maintenance);
+            info!("Maintenance mode set to {}", storage.maintenance);
+
+            Ok(CommandOutput::Done(format!(
+                "Maintenance mode is {}.",
+                if storage.maintenance { "on" } else { "off" }
+            )))
+        }
+        Command::Broadcast(message) => {
+            let clients: Vec<_> = storage
+                .server
+                .read()
+                .await
+                .clients
+                .values()
+                .cloned()
+                .collect();
+            let count = clients.len();
+
+            for client in clients {
+                let mut client = client.lock().await;
+                send_infomsg(storage, &mut client, message.clone(), false).await?;
+            }
+
+            Ok(CommandOutput::Done(format!(
+                "Broadcast sent to {} client(s).",
+                count
+            )))
+        }
+        Command::ReloadConfig => {
+            storage.reload_config().await?;
+
+            Ok(CommandOutput::Done("Configuration reloaded.".into()))
+        }
+        Command::ReloadCerts => match storage.reload_tls().await {
+            Ok(()) => {
+                info!("TLS certificate reloaded");
+                Ok(CommandOutput::Done("TLS certificate reloaded.".into()))
+            }
+            Err(e) => {
+                error!(
+                    "Failed to reload the TLS certificate, keeping the current one: {}",
+                    e
+                );
+                Ok(CommandOutput::Error(format!(
+                    "Keeping the current certificate: {}",
+                    e
+                )))
+            }
+        },
+        Command::Shutdown => {
+            info!("Shutdown requested from the console");
+            storage.shutdown = true;
+
+            Ok(CommandOutput::Done("Shutting down.".into()))
+        }
+    }
+}
+
+/// Asks the game server the player is on to drop them.
+pub async fn kill_game_client(storage: &mut Storage, server_name: &str, uid: i64) -> Result<()> {
+    let game_server = match storage.server_names.get(server_name) {
+        Some(token) => storage.server.read().await.game_servers.get(token).cloned(),
+        None => None,
+    };
+
+    if let Some(game_server) = game_server {
+        let mut game_server = game_server.lock().await;
+        send_kill_client(storage, &mut game_server, uid).await?;
+    }
+
+    Ok(())
+}
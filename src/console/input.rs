@@ -0,0 +1,56 @@
+use super::{Command, CommandOutput};
+use log::{error, info};
+use tokio::sync::{mpsc, oneshot};
+
+pub struct ConsoleRequest {
+    pub command: Command,
+    /// When None the output is printed to the console instead.
+    pub reply: Option<oneshot::Sender<CommandOutput>>,
+}
+
+pub type ConsoleSender = mpsc::UnboundedSender<ConsoleRequest>;
+pub type ConsoleReceiver = mpsc::UnboundedReceiver<ConsoleRequest>;
+
+// Returns None once stdin is closed.
+fn read_line() -> Option<String> {
+    let mut rv = String::new();
+
+    match std::io::stdin().read_line(&mut rv) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(rv.replace("\r\n", "").replace('\n', "")),
+    }
+}
+
+/// Reads commands from stdin on its own thread so a blocking read never stalls the game loop.
+/// The commands are handed to the game loop which runs them between ticks.
+pub fn spawn_console(sender: ConsoleSender) {
+    let result = std::thread::Builder::new()
+        .name("console".into())
+        .spawn(move || {
+            while let Some(line) = read_line() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match Command::parse(&line) {
+                    Ok(command) => {
+                        let request = ConsoleRequest {
+                            command,
+                            reply: None,
+                        };
+
+                        if sender.send(request).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+
+            info!("Console input closed.");
+        });
+
+    if let Err(e) = result {
+        error!("Failed to start the console: {}", e);
+    }
+}
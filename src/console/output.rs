@@ -0,0 +1,75 @@
+use crate::containers::GameServerInfo;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::COMMAND_HELP;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerEntry {
+    pub token: usize,
+    pub info: GameServerInfo,
+    /// Packets and bytes waiting to be sent to the game server.
+    pub queued_packets: usize,
+    pub queued_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientEntry {
+    pub token: usize,
+    pub addr: String,
+    /// Packets and bytes waiting to be sent to the client.
+    pub queued_packets: usize,
+    pub queued_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CommandOutput {
+    Help,
+    Servers(Vec<ServerEntry>),
+    Clients(Vec<ClientEntry>),
+    Done(String),
+    Error(String),
+}
+
+impl fmt::Display for CommandOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandOutput::Help => write!(f, "{}", COMMAND_HELP),
+            CommandOutput::Servers(servers) => {
+                writeln!(f, "{} game server(s) connected.", servers.len())?;
+
+                for server in servers {
+                    writeln!(
+                        f,
+                        "[{}] {} {}:{} players {}/{} queued {} packets {} bytes",
+                        server.token,
+                        server.info.name,
+                        server.info.ip,
+                        server.info.port,
+                        server.info.players_on,
+                        server.info.max_players,
+                        server.queued_packets,
+                        server.queued_bytes
+                    )?;
+                }
+
+                Ok(())
+            }
+            CommandOutput::Clients(clients) => {
+                writeln!(f, "{} client(s) connected.", clients.len())?;
+
+                for client in clients {
+                    writeln!(
+                        f,
+                        "[{}] {} queued {} packets {} bytes",
+                        client.token, client.addr, client.queued_packets, client.queued_bytes
+                    )?;
+                }
+
+                Ok(())
+            }
+            CommandOutput::Done(message) => write!(f, "{}", message),
+            CommandOutput::Error(message) => write!(f, "Error: {}", message),
+        }
+    }
+}
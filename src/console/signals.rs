@@ -0,0 +1,79 @@
+use super::{Command, ConsoleRequest, ConsoleSender};
+use log::{error, info, warn};
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+/// Turns SIGINT/SIGTERM into a shutdown command so connections are drained.
+/// A second signal exits right away.
+pub fn spawn_shutdown_signals(sender: ConsoleSender) {
+    tokio::spawn(async move {
+        if let Err(e) = wait_for_signal().await {
+            error!("Failed to listen for shutdown signals: {}", e);
+            return;
+        }
+
+        info!("Shutdown signal received.");
+
+        let request = ConsoleRequest {
+            command: Command::Shutdown,
+            reply: None,
+        };
+
+        if sender.send(request).is_err() {
+            return;
+        }
+
+        if wait_for_signal().await.is_ok() {
+            warn!("Second shutdown signal received, exiting without draining.");
+            log::logger().flush();
+            std::process::exit(1);
+        }
+    });
+}
+
+/// Reloads settings.toml on SIGHUP. Does nothing on platforms without it.
+pub fn spawn_reload_signal(sender: ConsoleSender) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading the configuration.");
+
+            let request = ConsoleRequest {
+                command: Command::ReloadConfig,
+                reply: None,
+            };
+
+            if sender.send(request).is_err() {
+                return;
+            }
+        }
+    });
+
+    #[cfg(not(unix))]
+    let _ = sender;
+}
@@ -0,0 +1,75 @@
+use super::{Command, ConsoleRequest, ConsoleSender};
+use crate::gametypes::*;
+use log::{info, warn};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    path::{self, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// Time to wait for more changes before reloading, since the certificate and key are
+/// usually replaced one after the other.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Queues `Command::ReloadCerts` whenever one of `files` is written or replaced.
+pub fn spawn_cert_watcher(files: &[&str], sender: ConsoleSender) -> Result<()> {
+    let files = files
+        .iter()
+        .map(path::absolute)
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = events_tx.send(event);
+    })?;
+
+    // Watching the directories also catches files that are replaced instead of written to.
+    let mut dirs: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|file| file.parent().map(PathBuf::from))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+
+    for dir in &dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+
+    tokio::spawn(async move {
+        // Dropping the watcher stops it.
+        let _watcher = watcher;
+
+        while let Some(event) = events.recv().await {
+            match event {
+                Ok(notify::Event { kind, paths, .. }) => {
+                    if matches!(kind, EventKind::Access(_))
+                        || !paths.iter().any(|path| files.contains(path))
+                    {
+                        continue;
+                    }
+                }
+                Err(e) => {
+                    warn!("Certificate watcher error: {}", e);
+                    continue;
+                }
+            }
+
+            tokio::time::sleep(SETTLE_TIME).await;
+            while events.try_recv().is_ok() {}
+
+            info!("Certificate files changed, reloading them.");
+
+            let request = ConsoleRequest {
+                command: Command::ReloadCerts,
+                reply: None,
+            };
+
+            if sender.send(request).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(())
+}
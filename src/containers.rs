@@ -1,6 +1,8 @@
+mod config;
 mod storage;
 mod tokens;
 
+pub use config::*;
 pub use storage::*;
 pub use tokens::*;
 
@@ -0,0 +1,1495 @@
+use crate::{
+    gametypes::*,
+    logging::LoggingConfig,
+    metrics::MetricsConfig,
+    socket::{Cidr, FrameCodec, ListenerKind},
+};
+use log::LevelFilter;
+use mmap_bytey::BUFFER_SIZE;
+use rustls::{crypto::ring as provider, SupportedCipherSuite, SupportedProtocolVersion};
+use serde::{Deserialize, Serialize};
+use std::{fs, net::SocketAddr, path::Path, sync::Arc};
+
+pub const CONFIG_PATH: &str = "settings.toml";
+
+/// Environment variables starting with this override settings.toml. Nested settings are
+/// separated with a double underscore, such as `ASCENDING_LOGGING__FILE_LEVEL=Debug`.
+/// Adding `_FILE` to a name reads the value from that file instead, for secrets. Lists
+/// are comma separated. Variables that name no setting are reported as errors.
+pub const ENV_PREFIX: &str = "ASCENDING_";
+
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum ServerLevelFilter {
+    /// A level lower than all log levels.
+    Off,
+    /// Corresponds to the `Error` log level.
+    Error,
+    /// Corresponds to the `Warn` log level.
+    Warn,
+    /// Corresponds to the `Info` log level.
+    Info,
+    /// Corresponds to the `Debug` log level.
+    Debug,
+    /// Corresponds to the `Trace` log level.
+    Trace,
+}
+
+impl ServerLevelFilter {
+    pub fn parse_enum(&self) -> LevelFilter {
+        match self {
+            ServerLevelFilter::Off => LevelFilter::Off,
+            ServerLevelFilter::Error => LevelFilter::Error,
+            ServerLevelFilter::Warn => LevelFilter::Warn,
+            ServerLevelFilter::Info => LevelFilter::Info,
+            ServerLevelFilter::Debug => LevelFilter::Debug,
+            ServerLevelFilter::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// `Dev` creates missing TLS certificates on start up. Never use it in production.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Profile {
+    #[default]
+    Production,
+    Dev,
+}
+
+/// Where accounts are kept. `Memory` needs no database, for development and LAN games,
+/// but every account is lost when the server stops.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountStoreKind {
+    #[default]
+    Postgres,
+    Memory,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl TlsVersion {
+    pub const ALL: [TlsVersion; 2] = [TlsVersion::Tls12, TlsVersion::Tls13];
+
+    pub fn protocol(self) -> &'static SupportedProtocolVersion {
+        match self {
+            TlsVersion::Tls12 => &rustls::version::TLS12,
+            TlsVersion::Tls13 => &rustls::version::TLS13,
+        }
+    }
+
+    fn of_suite(suite: &SupportedCipherSuite) -> Self {
+        match suite {
+            SupportedCipherSuite::Tls12(_) => TlsVersion::Tls12,
+            SupportedCipherSuite::Tls13(_) => TlsVersion::Tls13,
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsVersion::Tls12 => write!(f, "TLS 1.2"),
+            TlsVersion::Tls13 => write!(f, "TLS 1.3"),
+        }
+    }
+}
+
+/// Whether game clients present a certificate signed by `ca_root`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientAuth {
+    #[default]
+    Off,
+    /// Certificates are checked when sent, clients without one may still connect.
+    Optional,
+    Required,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Reloads server_cert and server_key for new connections when either file changes.
+    pub watch_certs: bool,
+    pub min_version: TlsVersion,
+    pub max_version: TlsVersion,
+    /// Cipher suites clients may use, named as in the TLS registry such as
+    /// `TLS13_AES_256_GCM_SHA384`. Empty allows every suite of the allowed versions.
+    pub cipher_suites: Vec<String>,
+    /// ALPN protocol IDs, most preferred first. Clients offering ALPN must offer one of
+    /// these, which lets old clients be turned away during the handshake.
+    pub alpn_protocols: Vec<String>,
+    /// Issues stateless session tickets so clients can resume without a full handshake.
+    pub session_tickets: bool,
+    /// Sessions kept in memory for resumption by session ID, 0 disables the cache.
+    pub session_cache_size: usize,
+    pub client_auth: ClientAuth,
+    /// PEM file of certificate revocation lists issued by `ca_root`. Certificates revoked
+    /// in the `revoked_certs` table are refused as well.
+    pub crl_file: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            watch_certs: true,
+            min_version: TlsVersion::Tls12,
+            max_version: TlsVersion::Tls13,
+            cipher_suites: Vec::new(),
+            alpn_protocols: Vec::new(),
+            session_tickets: false,
+            session_cache_size: 256,
+            client_auth: ClientAuth::Off,
+            crl_file: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn protocol_versions(&self) -> Vec<&'static SupportedProtocolVersion> {
+        TlsVersion::ALL
+            .into_iter()
+            .filter(|version| (self.min_version..=self.max_version).contains(version))
+            .map(TlsVersion::protocol)
+            .collect()
+    }
+
+    /// The allowed cipher suites that belong to an allowed version. Unknown names are
+    /// skipped here, `validate` reports them.
+    pub fn cipher_suites(&self) -> Vec<SupportedCipherSuite> {
+        provider::ALL_CIPHER_SUITES
+            .iter()
+            .filter(|suite| {
+                self.cipher_suites.is_empty()
+                    || self
+                        .cipher_suites
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&suite_name(suite)))
+            })
+            .filter(|suite| {
+                (self.min_version..=self.max_version).contains(&TlsVersion::of_suite(suite))
+            })
+            .copied()
+            .collect()
+    }
+
+    fn validate(&self, errors: &mut Vec<String>, check_files: bool) {
+        for protocol in &self.alpn_protocols {
+            if protocol.is_empty() || protocol.len() > 255 {
+                errors.push(format!(
+                    "tls.alpn_protocols: '{}' must be 1 to 255 bytes",
+                    protocol
+                ));
+            }
+        }
+
+        for (i, protocol) in self.alpn_protocols.iter().enumerate() {
+            if self.alpn_protocols[..i].contains(protocol) {
+                errors.push(format!(
+                    "tls.alpn_protocols: '{}' is listed twice",
+                    protocol
+                ));
+            }
+        }
+
+        if let Some(crl_file) = &self.crl_file {
+            if self.client_auth == ClientAuth::Off {
+                errors.push(
+                    "tls.crl_file: only used when client_auth is Optional or Required".into(),
+                );
+            } else if check_files && !Path::new(crl_file).is_file() {
+                errors.push(format!("tls.crl_file: file '{}' does not exist", crl_file));
+            }
+        }
+
+        if self.min_version > self.max_version {
+            errors.push(format!(
+                "tls: min_version {} is above max_version {}",
+                self.min_version, self.max_version
+            ));
+            return;
+        }
+
+        for name in &self.cipher_suites {
+            let Some(suite) = provider::ALL_CIPHER_SUITES
+                .iter()
+                .find(|suite| name.eq_ignore_ascii_case(&suite_name(suite)))
+            else {
+                errors.push(format!(
+                    "tls.cipher_suites: '{}' is not a supported cipher suite, use one of {}",
+                    name,
+                    provider::ALL_CIPHER_SUITES
+                        .iter()
+                        .map(suite_name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                continue;
+            };
+
+            let version = TlsVersion::of_suite(suite);
+
+            if version < self.min_version {
+                errors.push(format!(
+                    "tls.cipher_suites: '{}' is a {} suite but min_version is {}",
+                    name, version, self.min_version
+                ));
+            } else if version > self.max_version {
+                errors.push(format!(
+                    "tls.cipher_suites: '{}' is a {} suite but max_version is {}",
+                    name, version, self.max_version
+                ));
+            }
+        }
+
+        let suites = self.cipher_suites();
+
+        for version in TlsVersion::ALL
+            .into_iter()
+            .filter(|version| (self.min_version..=self.max_version).contains(version))
+        {
+            if !suites
+                .iter()
+                .any(|suite| TlsVersion::of_suite(suite) == version)
+            {
+                errors.push(format!(
+                    "tls.cipher_suites: no {} suite is allowed, add one or change min_version and max_version",
+                    version
+                ));
+            }
+        }
+    }
+}
+
+fn suite_name(suite: &SupportedCipherSuite) -> String {
+    format!("{:?}", suite.suite())
+}
+
+/// What happens when a packet would take a send queue past `max_queued_packets`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueOverflow {
+    /// Close the connection, it is not reading fast enough to keep up.
+    #[default]
+    Disconnect,
+    /// Append the packet to the last queued buffer while it has room, then disconnect.
+    Coalesce,
+}
+
+/// Deadlines and limits that keep idle or stalled connections from holding tokens.
+/// Timeouts are in seconds, 0 disables one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionConfig {
+    /// Time to finish the PROXY header, TLS handshake and WebSocket upgrade.
+    pub handshake_timeout: u64,
+    /// Time from the end of the handshake to the first complete packet.
+    pub first_packet_timeout: u64,
+    /// Time a client may go between complete packets. Game servers are not limited.
+    pub idle_timeout: u64,
+    /// Client connections open at once from one IP address, 0 for no limit. Trusted
+    /// proxies are not limited as every client behind them shares their address.
+    pub max_per_ip: usize,
+    /// Largest packet accepted on each kind of listener, counting the packet ID and
+    /// fields after the length prefix.
+    pub max_client_packet: usize,
+    pub max_websocket_packet: usize,
+    pub max_game_server_packet: usize,
+    /// Packets waiting to be sent to one connection, 0 for no limit.
+    pub max_queued_packets: usize,
+    /// Bytes waiting to be sent to one connection, 0 for no limit. Going over it always
+    /// closes the connection.
+    pub max_queued_bytes: usize,
+    pub overflow: QueueOverflow,
+    /// Bytes written to one connection each time it can be written to, so a busy
+    /// connection does not hold up the others. At least one packet is always written, 0
+    /// writes everything queued.
+    pub write_budget: usize,
+}
+
+impl ConnectionConfig {
+    /// Framing for connections made to a listener of `kind`.
+    pub fn codec(&self, kind: ListenerKind) -> FrameCodec {
+        FrameCodec::new(match kind {
+            ListenerKind::Clients => self.max_client_packet,
+            ListenerKind::WebSockets => self.max_websocket_packet,
+            ListenerKind::GameServers => self.max_game_server_packet,
+        })
+    }
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            handshake_timeout: 10,
+            first_packet_timeout: 10,
+            idle_timeout: 120,
+            max_per_ip: 10,
+            max_client_packet: 8192,
+            max_websocket_packet: 8192,
+            max_game_server_packet: 8192,
+            max_queued_packets: 1024,
+            max_queued_bytes: 4 * 1024 * 1024,
+            overflow: QueueOverflow::Disconnect,
+            write_budget: 64 * 1024,
+        }
+    }
+}
+
+/// Compression offered to clients and game servers that ask for it in `Hello`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// Agree to the compression a connection asks for. When off every `Hello` is
+    /// answered with `None`.
+    pub enabled: bool,
+    /// Frames whose packet ID and fields are larger than this many bytes are compressed.
+    pub threshold: usize,
+    /// DEFLATE level from 0, fastest, to 9, smallest.
+    pub level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 512,
+            level: 6,
+        }
+    }
+}
+
+/// Where the translated player messages are read from, see `locales::Locales`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalizationConfig {
+    /// Directory of catalog files named after their locale, such as `de.toml`.
+    pub directory: String,
+    /// Used for clients that send no locale or one without a catalog.
+    pub default_locale: String,
+}
+
+impl Default for LocalizationConfig {
+    fn default() -> Self {
+        Self {
+            directory: "data/locales".into(),
+            default_locale: "en".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// Addresses for browser clients, such as `0.0.0.0:7014`. They use the same
+    /// certificate and TLS settings as `clients_listen`.
+    pub listen: Vec<String>,
+    /// Origin headers allowed to connect, such as `https://play.example.com`. Must list
+    /// at least one unless `allow_any_origin` is set.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Skips the Origin check so any web page may connect on a player's behalf. Only
+    /// for development.
+    #[serde(default)]
+    pub allow_any_origin: bool,
+}
+
+impl WebSocketConfig {
+    /// Origins the upgrade request must come from, None when any may connect.
+    pub fn origins(&self) -> Option<Arc<[String]>> {
+        (!self.allow_any_origin).then(|| self.allowed_origins.clone().into())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyProtocolConfig {
+    /// Load balancers that send a PROXY header, as addresses or CIDRs such as
+    /// `10.0.0.0/8`. Connections from them must start with one, others never may.
+    pub trusted_proxies: Vec<String>,
+    /// Read the header on clients_listen and the WebSocket addresses.
+    pub clients: bool,
+    /// Read the header on servers_listen.
+    pub game_servers: bool,
+}
+
+impl Default for ProxyProtocolConfig {
+    fn default() -> Self {
+        Self {
+            trusted_proxies: Vec::new(),
+            clients: true,
+            game_servers: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminApiConfig {
+    /// Address and port the admin API listens on. Anything other than a loopback
+    /// address requires the TLS settings below so clients are verified with mTLS.
+    pub listen: String,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    /// CA used to verify the client certificates of the dashboard and support tools.
+    pub client_ca: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub profile: Profile,
+    /// Addresses game clients connect to, such as `0.0.0.0:7010` and `[::]:7010`.
+    pub clients_listen: Vec<String>,
+    /// Addresses game servers connect to, best kept on a private interface.
+    pub servers_listen: Vec<String>,
+    pub server_cert: String,
+    pub server_key: String,
+    pub ca_root: String,
+    pub tls: TlsConfig,
+    pub maxconnections: usize,
+    pub connections: ConnectionConfig,
+    pub compression: CompressionConfig,
+    pub account_store: AccountStoreKind,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    pub host: String,
+    pub port: u16,
+    pub enable_backtrace: bool,
+    pub logging: LoggingConfig,
+    /// Seconds to wait for queued packets to be sent when shutting down.
+    pub shutdown_timeout: u64,
+    /// Starts the server in maintenance mode, only Admin accounts may log in.
+    pub maintenance: bool,
+    /// Shown to players when they request the server list. Empty sends nothing.
+    pub motd: String,
+    pub localization: LocalizationConfig,
+    pub websocket: Option<WebSocketConfig>,
+    pub proxy_protocol: Option<ProxyProtocolConfig>,
+    pub admin_api: Option<AdminApiConfig>,
+    pub metrics: Option<MetricsConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            profile: Profile::Production,
+            clients_listen: vec!["0.0.0.0:7010".into()],
+            servers_listen: vec!["127.0.0.1:7011".into()],
+            server_cert: "keys/server.crt".into(),
+            server_key: "keys/server-key.pem".into(),
+            ca_root: "keys/ca-crt.pem".into(),
+            tls: TlsConfig::default(),
+            maxconnections: 500,
+            connections: ConnectionConfig::default(),
+            compression: CompressionConfig::default(),
+            account_store: AccountStoreKind::Postgres,
+            database: "ascending".into(),
+            username: "server".into(),
+            password: String::new(),
+            host: "127.0.0.1".into(),
+            port: 5432,
+            enable_backtrace: false,
+            logging: LoggingConfig::default(),
+            shutdown_timeout: 10,
+            maintenance: false,
+            motd: String::new(),
+            localization: LocalizationConfig::default(),
+            websocket: None,
+            proxy_protocol: None,
+            admin_api: None,
+            metrics: None,
+        }
+    }
+}
+
+impl Config {
+    /// Checks the settings that would otherwise fail somewhere later during start up.
+    /// Every problem is returned so they can all be fixed in one go.
+    pub fn validate(&self) -> Vec<String> {
+        self.check(true)
+    }
+
+    /// Like `validate`, but skips the certificate files only the server reads, for
+    /// commands that just use the database or the admin API.
+    pub fn validate_without_tls_files(&self) -> Vec<String> {
+        self.check(false)
+    }
+
+    fn check(&self, check_files: bool) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        let listeners = match self.listen_addresses() {
+            Ok(addresses) => addresses.len(),
+            Err(e) => {
+                errors.extend(e);
+                self.clients_listen.len()
+                    + self.servers_listen.len()
+                    + self.websocket.as_ref().map_or(0, |ws| ws.listen.len())
+            }
+        };
+
+        // The lowest tokens are used by the listeners.
+        if self.maxconnections <= listeners {
+            errors.push(format!(
+                "maxconnections: must be greater than the {} listen addresses",
+                listeners
+            ));
+        }
+
+        for (name, path) in [
+            ("server_cert", &self.server_cert),
+            ("server_key", &self.server_key),
+            ("ca_root", &self.ca_root),
+        ] {
+            // The dev profile creates missing certificates when the server starts.
+            if check_files && self.profile != Profile::Dev && !Path::new(path).is_file() {
+                errors.push(format!("{}: file '{}' does not exist", name, path));
+            }
+        }
+
+        for (name, value) in [
+            ("database", &self.database),
+            ("username", &self.username),
+            ("password", &self.password),
+            ("host", &self.host),
+        ] {
+            if self.account_store == AccountStoreKind::Postgres && value.is_empty() {
+                errors.push(format!("{}: must be set", name));
+            }
+        }
+
+        self.tls.validate(&mut errors, check_files);
+
+        if self.logging.file_path.is_empty() {
+            errors.push("logging.file_path: must be set".into());
+        }
+
+        for (name, max) in [
+            ("max_client_packet", self.connections.max_client_packet),
+            (
+                "max_websocket_packet",
+                self.connections.max_websocket_packet,
+            ),
+            (
+                "max_game_server_packet",
+                self.connections.max_game_server_packet,
+            ),
+        ] {
+            if !(1..=BUFFER_SIZE).contains(&max) {
+                errors.push(format!(
+                    "connections.{}: must be 1 to {}, the size of a packet buffer",
+                    name, BUFFER_SIZE
+                ));
+            }
+        }
+
+        let max_queued_bytes = self.connections.max_queued_bytes;
+
+        if max_queued_bytes > 0 && max_queued_bytes < BUFFER_SIZE {
+            errors.push(format!(
+                "connections.max_queued_bytes: must be 0 or at least {}, the largest packet",
+                BUFFER_SIZE
+            ));
+        }
+
+        if self.compression.level > 9 {
+            errors.push("compression.level: must be 0 to 9".into());
+        }
+
+        if self.localization.default_locale.is_empty() {
+            errors.push("localization.default_locale: must be set".into());
+        }
+
+        if let Some(websocket) = &self.websocket {
+            if websocket.allowed_origins.is_empty() && !websocket.allow_any_origin {
+                errors.push(
+                    "websocket.allowed_origins: must list at least one origin, or set allow_any_origin = true"
+                        .into(),
+                );
+            }
+        }
+
+        if let Some(proxy_protocol) = &self.proxy_protocol {
+            if proxy_protocol.trusted_proxies.is_empty() {
+                errors.push("proxy_protocol.trusted_proxies: must list at least one proxy".into());
+            }
+
+            for proxy in &proxy_protocol.trusted_proxies {
+                if let Err(e) = proxy.parse::<Cidr>() {
+                    errors.push(format!("proxy_protocol.trusted_proxies: {}", e));
+                }
+            }
+        }
+
+        if let Some(admin_api) = &self.admin_api {
+            if admin_api.listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!(
+                    "admin_api.listen: '{}' is not an address and port",
+                    admin_api.listen
+                ));
+            }
+
+            let tls = [
+                &admin_api.tls_cert,
+                &admin_api.tls_key,
+                &admin_api.client_ca,
+            ];
+
+            if tls.iter().any(|v| v.is_some()) && !tls.iter().all(|v| v.is_some()) {
+                errors
+                    .push("admin_api: tls_cert, tls_key and client_ca must be set together".into());
+            }
+        }
+
+        if let Some(metrics) = &self.metrics {
+            if metrics.listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!(
+                    "metrics.listen: '{}' is not an address and port",
+                    metrics.listen
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Every address to listen on and who it is for, in the order the listeners are
+    /// registered. Addresses that do not parse or would clash when bound are returned
+    /// as errors.
+    pub fn listen_addresses(
+        &self,
+    ) -> std::result::Result<Vec<(ListenerKind, SocketAddr)>, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut addresses: Vec<(ListenerKind, SocketAddr)> = Vec::new();
+
+        let mut lists = vec![
+            (
+                "clients_listen",
+                ListenerKind::Clients,
+                &self.clients_listen,
+            ),
+            (
+                "servers_listen",
+                ListenerKind::GameServers,
+                &self.servers_listen,
+            ),
+        ];
+
+        if let Some(websocket) = &self.websocket {
+            lists.push((
+                "websocket.listen",
+                ListenerKind::WebSockets,
+                &websocket.listen,
+            ));
+        }
+
+        for (name, kind, list) in lists {
+            if list.is_empty() {
+                errors.push(format!("{}: must list at least one address", name));
+            }
+
+            for value in list {
+                let addr = match value.parse::<SocketAddr>() {
+                    Ok(addr) if addr.port() == 0 => {
+                        errors.push(format!("{}: '{}' must not use port 0", name, value));
+                        continue;
+                    }
+                    Ok(addr) => addr,
+                    Err(_) => {
+                        errors.push(format!(
+                            "{}: '{}' is not an address and port, such as 0.0.0.0:7010 or [::]:7010",
+                            name, value
+                        ));
+                        continue;
+                    }
+                };
+
+                // A wildcard address takes the port on every interface of its family.
+                let clash = addresses.iter().any(|(_, other)| {
+                    other.port() == addr.port()
+                        && (other.ip() == addr.ip()
+                            || (other.is_ipv4() == addr.is_ipv4()
+                                && (other.ip().is_unspecified() || addr.ip().is_unspecified())))
+                });
+
+                if clash {
+                    errors.push(format!(
+                        "{}: '{}' is already used by another listen address",
+                        name, value
+                    ));
+                    continue;
+                }
+
+                addresses.push((kind, addr));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(addresses)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Names of the settings that differ from `other` but only take effect after a restart.
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+
+        macro_rules! compare {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != other.$field {
+                        changed.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+
+        compare!(
+            profile,
+            clients_listen,
+            servers_listen,
+            server_cert,
+            server_key,
+            ca_root,
+            tls,
+            account_store,
+            database,
+            username,
+            password,
+            host,
+            port,
+            enable_backtrace,
+            websocket,
+            proxy_protocol,
+            admin_api,
+            metrics
+        );
+
+        changed
+    }
+}
+
+/// Loads the configuration: built in defaults, then settings.toml if it exists, then
+/// environment overrides. The result is validated and every problem found is returned.
+pub fn load_config(path: &str) -> Result<Config> {
+    load(path, Config::validate)
+}
+
+/// Loads the configuration like `load_config` without requiring the server's TLS
+/// files, see `Config::validate_without_tls_files`.
+pub fn load_config_without_tls_files(path: &str) -> Result<Config> {
+    load(path, Config::validate_without_tls_files)
+}
+
+fn load(path: &str, validate: fn(&Config) -> Vec<String>) -> Result<Config> {
+    let mut errors = Vec::new();
+
+    let mut table = match fs::read_to_string(path) {
+        Ok(data) => match data.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                errors.push(format!("{}: {}", path, e));
+                toml::Table::new()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    remove_renamed_settings(&mut table, &mut errors);
+    apply_env(
+        &mut table,
+        &settings_schema(),
+        std::env::vars(),
+        &mut errors,
+    );
+
+    let config = match deserialize_config(table) {
+        Ok(config) => config,
+        Err(e) => {
+            errors.extend(e);
+            return Err(AscendingError::InvalidConfig(errors));
+        }
+    };
+
+    errors.extend(validate(&config));
+
+    if errors.is_empty() {
+        Ok(config)
+    } else {
+        Err(AscendingError::InvalidConfig(errors))
+    }
+}
+
+/// Settings from earlier releases and the ones that replaced them.
+const RENAMED_SETTINGS: [(&str, Option<&str>, &str); 4] = [
+    (
+        "listen",
+        None,
+        "clients_listen and servers_listen, such as ['0.0.0.0:7010']",
+    ),
+    (
+        "clients_port",
+        None,
+        "clients_listen, such as ['0.0.0.0:7010']",
+    ),
+    (
+        "servers_port",
+        None,
+        "servers_listen, such as ['127.0.0.1:7011']",
+    ),
+    (
+        "websocket",
+        Some("port"),
+        "websocket.listen, such as ['0.0.0.0:7014']",
+    ),
+];
+
+/// Reports settings that were renamed, naming their replacements, and removes them so
+/// the rest of the file is still checked.
+fn remove_renamed_settings(table: &mut toml::Table, errors: &mut Vec<String>) {
+    for (key, name, replacement) in RENAMED_SETTINGS {
+        let removed = match name {
+            None => table.remove(key).is_some(),
+            Some(name) => table
+                .get_mut(key)
+                .and_then(toml::Value::as_table_mut)
+                .is_some_and(|section| section.remove(name).is_some()),
+        };
+
+        if removed {
+            let path = name.map_or(key.to_string(), |name| format!("{}.{}", key, name));
+
+            errors.push(format!("{}: was replaced by {}", path, replacement));
+        }
+    }
+}
+
+/// Deserializes the merged settings. Serde stops at the first mistake, so on failure
+/// each setting and section entry is tried on its own to report all of them.
+fn deserialize_config(table: toml::Table) -> std::result::Result<Config, Vec<String>> {
+    let first = match Config::deserialize(toml::Value::Table(table.clone())) {
+        Ok(config) => return Ok(config),
+        Err(e) => e,
+    };
+
+    let single = |path: &[&String], value: &toml::Value| {
+        let path: Vec<String> = path.iter().map(|part| part.to_string()).collect();
+        let mut table = toml::Table::new();
+        insert(&mut table, &path, value.clone());
+        Config::deserialize(toml::Value::Table(table)).err()
+    };
+    let mut errors = Vec::new();
+
+    for (key, value) in &table {
+        let Some(error) = single(&[key], value) else {
+            continue;
+        };
+        let before = errors.len();
+
+        if let toml::Value::Table(section) = value {
+            for (name, value) in section {
+                // Entries tried alone miss the section's other required settings.
+                if let Some(e) = single(&[key, name], value) {
+                    if !e.message().starts_with("missing field") {
+                        errors.push(format!("{}.{}: {}", key, name, e.message()));
+                    }
+                }
+            }
+        }
+
+        if errors.len() == before {
+            errors.push(format!("{}: {}", key, error.message()));
+        }
+    }
+
+    if errors.is_empty() {
+        errors.push(first.message().to_string());
+    }
+
+    Err(errors)
+}
+
+/// `ASCENDING_TEST_` variables configure the test harness, see `testing::TestServer`.
+const TEST_ENV_PREFIX: &str = "ASCENDING_TEST_";
+
+/// Every setting with a value, including the optional ones `Config::default` leaves
+/// out, so environment variables can be checked against it and given the right type.
+fn settings_schema() -> toml::Table {
+    let config = Config {
+        tls: TlsConfig {
+            crl_file: Some(String::new()),
+            ..TlsConfig::default()
+        },
+        websocket: Some(WebSocketConfig {
+            listen: Vec::new(),
+            allowed_origins: Vec::new(),
+            allow_any_origin: false,
+        }),
+        proxy_protocol: Some(ProxyProtocolConfig::default()),
+        admin_api: Some(AdminApiConfig {
+            listen: String::new(),
+            tls_cert: Some(String::new()),
+            tls_key: Some(String::new()),
+            client_ca: Some(String::new()),
+        }),
+        metrics: Some(MetricsConfig {
+            listen: String::new(),
+        }),
+        ..Config::default()
+    };
+
+    toml::Table::try_from(config).expect("settings schema should always serialize")
+}
+
+/// Whether `path` names a setting in `schema`, or an entry of a map such as
+/// `logging.modules`, which is empty in the schema.
+fn is_setting(schema: &toml::Table, path: &[String]) -> bool {
+    let mut table = schema;
+
+    for (index, part) in path.iter().enumerate() {
+        let last = index + 1 == path.len();
+
+        match table.get(part) {
+            Some(toml::Value::Table(next)) if !last => {
+                if next.is_empty() {
+                    return true;
+                }
+
+                table = next;
+            }
+            Some(_) => return last,
+            None => return false,
+        }
+    }
+
+    false
+}
+
+/// The setting an `ASCENDING_*_FILE` variable reads from a file, if it is one.
+fn file_setting<'a>(schema: &toml::Table, key: &'a str) -> Option<&'a str> {
+    let name = &key[ENV_PREFIX.len()..];
+    let setting = name.strip_suffix("_FILE")?;
+
+    (!is_setting(schema, &env_path(name)) && is_setting(schema, &env_path(setting)))
+        .then_some(setting)
+}
+
+fn env_path(name: &str) -> Vec<String> {
+    name.split("__").map(|part| part.to_lowercase()).collect()
+}
+
+/// Applies `ASCENDING_*` variables onto the parsed file. The type of each value is taken
+/// from `schema` so a numeric password still ends up a string. A `_FILE` suffix reads
+/// the value from that file, unless the name with the suffix is a setting itself such
+/// as `ASCENDING_TLS__CRL_FILE`.
+fn apply_env(
+    table: &mut toml::Table,
+    schema: &toml::Table,
+    vars: impl Iterator<Item = (String, String)>,
+    errors: &mut Vec<String>,
+) {
+    let mut vars: Vec<(String, String)> = vars
+        .filter(|(key, _)| key.starts_with(ENV_PREFIX) && !key.starts_with(TEST_ENV_PREFIX))
+        .collect();
+
+    // Secrets read from files win over plain variables of the same name.
+    vars.sort_by_key(|(key, _)| file_setting(schema, key).is_some());
+
+    for (key, value) in &vars {
+        let (name, value) = match file_setting(schema, key) {
+            Some(name) => match fs::read_to_string(value) {
+                Ok(contents) => (name, contents.trim_end_matches(['\r', '\n']).to_string()),
+                Err(e) => {
+                    errors.push(format!("{}: could not read '{}': {}", key, value, e));
+                    continue;
+                }
+            },
+            None => (&key[ENV_PREFIX.len()..], value.clone()),
+        };
+
+        let path = env_path(name);
+
+        if path.iter().any(|part| part.is_empty()) || !is_setting(schema, &path) {
+            errors.push(format!("{}: is not a setting", key));
+            continue;
+        }
+
+        let default = lookup(schema, &path);
+
+        match env_value(&value, default) {
+            Some(value) => insert(table, &path, value),
+            None => errors.push(format!(
+                "{}: '{}' is not a valid {}",
+                key,
+                value,
+                default.map(|v| v.type_str()).unwrap_or("value")
+            )),
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+
+    for part in parents {
+        table = table.get(part)?.as_table()?;
+    }
+
+    table.get(last)
+}
+
+fn insert(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+
+    for part in parents {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+
+        table = entry.as_table_mut().unwrap();
+    }
+
+    table.insert(last.clone(), value);
+}
+
+/// Converts an environment value into the type the default has. Settings without a
+/// default, such as the optional sections, are guessed.
+fn env_value(value: &str, default: Option<&toml::Value>) -> Option<toml::Value> {
+    match default {
+        Some(toml::Value::String(_)) => Some(toml::Value::String(value.to_string())),
+        Some(toml::Value::Integer(_)) => value.parse().ok().map(toml::Value::Integer),
+        Some(toml::Value::Float(_)) => value.parse().ok().map(toml::Value::Float),
+        Some(toml::Value::Boolean(_)) => value.parse().ok().map(toml::Value::Boolean),
+        Some(toml::Value::Array(_)) => Some(toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_string()))
+                .collect(),
+        )),
+        Some(_) => None,
+        None => Some(
+            value
+                .parse()
+                .map(toml::Value::Integer)
+                .or_else(|_| value.parse().map(toml::Value::Boolean))
+                .unwrap_or_else(|_| toml::Value::String(value.to_string())),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> toml::Table {
+        toml.parse().unwrap()
+    }
+
+    fn path(path: &str) -> Vec<String> {
+        path.split('.').map(String::from).collect()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// The settings `apply_env` makes of `vars` on an empty file, and its errors.
+    fn from_env(env: &[(&str, &str)]) -> (toml::Table, Vec<String>) {
+        let mut settings = toml::Table::new();
+        let mut errors = Vec::new();
+
+        apply_env(&mut settings, &settings_schema(), vars(env), &mut errors);
+        (settings, errors)
+    }
+
+    /// A configuration that passes `validate` without certificate files on disk.
+    fn valid_config() -> Config {
+        Config {
+            profile: Profile::Dev,
+            password: "secret".into(),
+            ..Config::default()
+        }
+    }
+
+    fn validate(change: impl FnOnce(&mut Config)) -> Vec<String> {
+        let mut config = valid_config();
+        change(&mut config);
+        config.validate()
+    }
+
+    #[test]
+    fn looks_up_nested_settings() {
+        let settings = table("port = 1\n[tls]\nwatch_certs = true");
+
+        assert_eq!(
+            lookup(&settings, &path("port")),
+            Some(&toml::Value::Integer(1))
+        );
+        assert_eq!(
+            lookup(&settings, &path("tls.watch_certs")),
+            Some(&toml::Value::Boolean(true))
+        );
+        assert_eq!(lookup(&settings, &path("tls.missing")), None);
+        assert_eq!(lookup(&settings, &path("port.nested")), None);
+        assert_eq!(lookup(&settings, &[]), None);
+    }
+
+    #[test]
+    fn inserts_nested_settings() {
+        let mut settings = table("port = 1\n[tls]\nwatch_certs = true");
+
+        insert(&mut settings, &path("tls.crl_file"), "crl.pem".into());
+        insert(
+            &mut settings,
+            &path("metrics.listen"),
+            "127.0.0.1:9100".into(),
+        );
+        insert(&mut settings, &path("port.nested"), 2.into());
+
+        assert_eq!(
+            settings,
+            table(
+                r#"
+                port = { nested = 2 }
+                tls = { watch_certs = true, crl_file = "crl.pem" }
+                metrics = { listen = "127.0.0.1:9100" }
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn converts_env_values_to_the_type_of_the_setting() {
+        let string = toml::Value::String(String::new());
+        let integer = toml::Value::Integer(0);
+        let boolean = toml::Value::Boolean(false);
+        let array = toml::Value::Array(Vec::new());
+        let section = toml::Value::Table(toml::Table::new());
+
+        assert_eq!(env_value("1234", Some(&string)), Some("1234".into()));
+        assert_eq!(env_value("42", Some(&integer)), Some(42.into()));
+        assert_eq!(env_value("many", Some(&integer)), None);
+        assert_eq!(env_value("true", Some(&boolean)), Some(true.into()));
+        assert_eq!(env_value("yes", Some(&boolean)), None);
+        assert_eq!(
+            env_value(" a, b ,,c ", Some(&array)),
+            Some(vec!["a", "b", "c"].into())
+        );
+        assert_eq!(
+            env_value("", Some(&array)),
+            Some(toml::Value::Array(Vec::new()))
+        );
+        assert_eq!(env_value("x", Some(&section)), None);
+        assert_eq!(env_value("7", None), Some(7.into()));
+        assert_eq!(env_value("false", None), Some(false.into()));
+        assert_eq!(env_value("Warn", None), Some("Warn".into()));
+    }
+
+    #[test]
+    fn applies_env_overrides_by_path() {
+        let (settings, errors) = from_env(&[
+            ("ASCENDING_PASSWORD", "1234"),
+            ("ASCENDING_CONNECTIONS__MAX_PER_IP", "5"),
+            ("ASCENDING_TLS__CRL_FILE", "crl.pem"),
+            ("ASCENDING_WEBSOCKET__LISTEN", "0.0.0.0:7014,[::]:7014"),
+            ("ASCENDING_LOGGING__MODULES__SQLX", "Warn"),
+            ("ASCENDING_TEST_PASSWORD", "ignored"),
+            ("OTHER_PORT", "1"),
+        ]);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            settings,
+            table(
+                r#"
+                password = "1234"
+                connections = { max_per_ip = 5 }
+                tls = { crl_file = "crl.pem" }
+                websocket = { listen = ["0.0.0.0:7014", "[::]:7014"] }
+                logging = { modules = { sqlx = "Warn" } }
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn reads_file_settings_from_files() {
+        let file = std::env::temp_dir().join(format!("ascending-secret-{}", std::process::id()));
+        fs::write(&file, "from-file\n").unwrap();
+        let file_name = file.to_string_lossy().into_owned();
+
+        let (settings, errors) = from_env(&[
+            ("ASCENDING_PASSWORD_FILE", &file_name),
+            ("ASCENDING_PASSWORD", "plain"),
+            ("ASCENDING_USERNAME_FILE", "/nonexistent/secret"),
+        ]);
+        fs::remove_file(&file).unwrap();
+
+        assert_eq!(settings, table(r#"password = "from-file""#));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("ASCENDING_USERNAME_FILE: could not read"));
+    }
+
+    #[test]
+    fn reports_unknown_and_invalid_env_settings() {
+        let (settings, errors) = from_env(&[
+            ("ASCENDING_PASWORD", "typo"),
+            ("ASCENDING_TLS__WATCH_CERT", "true"),
+            ("ASCENDING_TLS____WATCH_CERTS", "true"),
+            ("ASCENDING_PORT", "high"),
+        ]);
+
+        assert!(settings.is_empty());
+        assert_eq!(
+            errors,
+            [
+                "ASCENDING_PASWORD: is not a setting",
+                "ASCENDING_TLS__WATCH_CERT: is not a setting",
+                "ASCENDING_TLS____WATCH_CERTS: is not a setting",
+                "ASCENDING_PORT: 'high' is not a valid integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_every_unknown_or_mistyped_setting() {
+        let errors = deserialize_config(table(
+            r#"
+            pasword = "typo"
+            port = "high"
+            [tls]
+            watch_cert = true
+            [connections]
+            max_per_ip = 5
+            idle_timout = 10
+            [metrics]
+            lisen = "127.0.0.1:9100"
+            "#,
+        ))
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors[0].starts_with("connections.idle_timout: unknown field"));
+        assert!(errors[1].starts_with("metrics.lisen: unknown field"));
+        assert!(errors[2].starts_with("pasword: unknown field"));
+        assert!(errors[3].starts_with("port: invalid type"));
+        assert!(errors[4].starts_with("tls.watch_cert: unknown field"));
+    }
+
+    #[test]
+    fn reports_missing_settings_of_optional_sections() {
+        let errors = deserialize_config(table("[admin_api]\ntls_cert = 'cert.pem'")).unwrap_err();
+
+        assert_eq!(errors, ["admin_api: missing field `listen`"]);
+    }
+
+    #[test]
+    fn reports_renamed_settings_by_their_replacements() {
+        let mut settings = table(
+            r#"
+            listen = "0.0.0.0"
+            clients_port = 7010
+            servers_port = 7011
+            [websocket]
+            port = 7014
+            listen = ["0.0.0.0:7014"]
+            "#,
+        );
+        let mut errors = Vec::new();
+
+        remove_renamed_settings(&mut settings, &mut errors);
+
+        assert_eq!(
+            errors,
+            [
+                "listen: was replaced by clients_listen and servers_listen, such as ['0.0.0.0:7010']",
+                "clients_port: was replaced by clients_listen, such as ['0.0.0.0:7010']",
+                "servers_port: was replaced by servers_listen, such as ['127.0.0.1:7011']",
+                "websocket.port: was replaced by websocket.listen, such as ['0.0.0.0:7014']",
+            ]
+        );
+        assert!(deserialize_config(settings).is_ok());
+    }
+
+    #[test]
+    fn default_settings_file_has_only_known_settings() {
+        let settings = table(include_str!("../../settings.toml.default"));
+
+        deserialize_config(settings).unwrap();
+    }
+
+    #[test]
+    fn accepts_a_valid_configuration() {
+        assert_eq!(valid_config().validate(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn validates_certificates_and_database_settings() {
+        let errors = validate(|config| {
+            config.profile = Profile::Production;
+            config.server_cert = "/nonexistent/server.crt".into();
+            config.password = String::new();
+        });
+
+        assert!(
+            errors.contains(&"server_cert: file '/nonexistent/server.crt' does not exist".into())
+        );
+        assert!(errors.contains(&"password: must be set".into()));
+        assert!(validate(|config| {
+            config.account_store = AccountStoreKind::Memory;
+            config.password = String::new();
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn database_commands_do_not_need_tls_files() {
+        let mut config = valid_config();
+        config.profile = Profile::Production;
+        config.server_cert = "/nonexistent/server.crt".into();
+        config.tls.client_auth = ClientAuth::Required;
+        config.tls.crl_file = Some("/nonexistent/crl.pem".into());
+
+        let errors = config.validate();
+        assert!(
+            errors.contains(&"server_cert: file '/nonexistent/server.crt' does not exist".into())
+        );
+        assert!(errors.contains(&"tls.crl_file: file '/nonexistent/crl.pem' does not exist".into()));
+        assert_eq!(config.validate_without_tls_files(), Vec::<String>::new());
+
+        config.password = String::new();
+        assert_eq!(
+            config.validate_without_tls_files(),
+            ["password: must be set"]
+        );
+    }
+
+    #[test]
+    fn validates_listen_addresses() {
+        assert_eq!(
+            validate(|config| config.clients_listen = vec!["0.0.0.0".into()]),
+            ["clients_listen: '0.0.0.0' is not an address and port, such as 0.0.0.0:7010 or [::]:7010"]
+        );
+        assert_eq!(
+            validate(|config| config.servers_listen = vec!["127.0.0.1:0".into()]),
+            ["servers_listen: '127.0.0.1:0' must not use port 0"]
+        );
+        assert_eq!(
+            validate(|config| config.servers_listen = vec!["127.0.0.1:7010".into()]),
+            ["servers_listen: '127.0.0.1:7010' is already used by another listen address"]
+        );
+        assert_eq!(
+            validate(|config| config.servers_listen.clear()),
+            ["servers_listen: must list at least one address"]
+        );
+        assert!(validate(|config| {
+            config.clients_listen = vec!["0.0.0.0:7010".into(), "[::]:7010".into()];
+        })
+        .is_empty());
+        assert_eq!(
+            validate(|config| config.maxconnections = 2),
+            ["maxconnections: must be greater than the 2 listen addresses"]
+        );
+    }
+
+    #[test]
+    fn websockets_need_allowed_origins_or_any_origin() {
+        let websocket = |origins: &[&str], allow_any_origin: bool| WebSocketConfig {
+            listen: vec!["127.0.0.1:7014".into()],
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            allow_any_origin,
+        };
+
+        assert_eq!(
+            validate(|config| config.websocket = Some(websocket(&[], false))),
+            ["websocket.allowed_origins: must list at least one origin, or set allow_any_origin = true"]
+        );
+        assert!(validate(|config| {
+            config.websocket = Some(websocket(&["https://play.example.com"], false));
+        })
+        .is_empty());
+        assert!(validate(|config| config.websocket = Some(websocket(&[], true))).is_empty());
+
+        assert_eq!(websocket(&[], true).origins(), None);
+        assert_eq!(
+            websocket(&["https://play.example.com"], false).origins(),
+            Some(Arc::from(["https://play.example.com".to_string()]))
+        );
+    }
+
+    #[test]
+    fn validates_limits() {
+        assert_eq!(
+            validate(|config| config.connections.max_client_packet = 0),
+            [format!(
+                "connections.max_client_packet: must be 1 to {}, the size of a packet buffer",
+                BUFFER_SIZE
+            )]
+        );
+        assert_eq!(
+            validate(|config| config.connections.max_queued_bytes = 1),
+            [format!(
+                "connections.max_queued_bytes: must be 0 or at least {}, the largest packet",
+                BUFFER_SIZE
+            )]
+        );
+        assert!(validate(|config| config.connections.max_queued_bytes = 0).is_empty());
+        assert_eq!(
+            validate(|config| config.compression.level = 10),
+            ["compression.level: must be 0 to 9"]
+        );
+        assert_eq!(
+            validate(|config| config.logging.file_path.clear()),
+            ["logging.file_path: must be set"]
+        );
+    }
+
+    #[test]
+    fn validates_optional_sections() {
+        assert_eq!(
+            validate(|config| config.proxy_protocol = Some(ProxyProtocolConfig::default())),
+            ["proxy_protocol.trusted_proxies: must list at least one proxy"]
+        );
+        assert_eq!(
+            validate(|config| {
+                config.admin_api = Some(AdminApiConfig {
+                    listen: "localhost".into(),
+                    tls_cert: Some("cert.pem".into()),
+                    tls_key: None,
+                    client_ca: None,
+                })
+            }),
+            [
+                "admin_api.listen: 'localhost' is not an address and port",
+                "admin_api: tls_cert, tls_key and client_ca must be set together",
+            ]
+        );
+        assert_eq!(
+            validate(|config| {
+                config.metrics = Some(MetricsConfig {
+                    listen: "9100".into(),
+                })
+            }),
+            ["metrics.listen: '9100' is not an address and port"]
+        );
+    }
+}
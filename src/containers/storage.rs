@@ -1,14 +1,21 @@
 use crate::{
+    certs::{CertResolver, ClientVerifier},
+    console::{ConsoleReceiver, ConsoleSender},
     containers::{IndexMap, IndexSet},
     gametypes::*,
-    socket::Server,
+    locales::Locales,
+    logging::configure_logging,
+    metrics::Metrics,
+    server::{Clock, Mailer},
+    socket::{packet_struct, Server, MAX_ADDRESS_LENGTH, MAX_SERVER_NAME_LENGTH},
+    store::AccountStore,
 };
-use log::LevelFilter;
+use log::{info, warn};
 use mio::Poll;
 use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
 use rustls::{
     crypto::{ring as provider, CryptoProvider},
-    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{NoServerSessionStorage, ServerSessionMemoryCache},
     ServerConfig,
 };
 use serde::{Deserialize, Serialize};
@@ -16,18 +23,20 @@ use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     ConnectOptions, PgPool,
 };
-use std::{fs, io::BufReader, sync::Arc};
-use tokio::sync::RwLock;
+use std::{collections::HashSet, sync::Arc};
+use tokio::sync::{mpsc, RwLock};
 
-use super::{RotatableJwtKey, KEY_LENGTH};
+use super::{load_config, ClientAuth, Config, RotatableJwtKey, CONFIG_PATH, KEY_LENGTH};
 
-#[derive(Clone, Debug, Serialize, Deserialize, MByteBufferRead, MByteBufferWrite)]
-pub struct GameServerInfo {
-    pub name: String,
-    pub ip: String,
-    pub port: u16,
-    pub players_on: u64,
-    pub max_players: u64,
+packet_struct! {
+    #[derive(Clone, Debug, Serialize, Deserialize, MByteBufferRead, MByteBufferWrite)]
+    pub struct GameServerInfo {
+        pub name: String [max = MAX_SERVER_NAME_LENGTH],
+        pub ip: String [max = MAX_ADDRESS_LENGTH],
+        pub port: u16,
+        pub players_on: u64,
+        pub max_players: u64,
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, MByteBufferRead, MByteBufferWrite)]
@@ -43,12 +52,25 @@ pub struct Storage {
     pub servers_ids: IndexSet<mio::Token>,
     pub poll: RwLock<mio::Poll>,
     pub server: Arc<RwLock<Server>>,
-    pub pgconn: PgPool,
+    pub accounts: Arc<dyn AccountStore>,
+    pub mailer: Arc<dyn Mailer>,
+    /// Bans and key rotation are checked against this instead of the system time.
+    pub clock: Arc<dyn Clock>,
     pub config: Config,
     pub keys: RotatableJwtKey,
+    /// Serves the TLS certificate to new client handshakes, see `Command::ReloadCerts`.
+    pub cert_resolver: Arc<CertResolver>,
+    pub metrics: Metrics,
+    /// Translations of player messages, reloaded with the configuration.
+    pub locales: Locales,
+    pub console: ConsoleReceiver,
+    pub console_sender: ConsoleSender,
+    /// When set only Admin accounts may log in and registration is closed.
+    pub maintenance: bool,
+    pub shutdown: bool,
 }
 
-async fn establish_connection(config: &Config) -> Result<PgPool> {
+pub async fn establish_connection(config: &Config) -> Result<PgPool> {
     let mut connect_opts = PgConnectOptions::new();
     connect_opts = connect_opts.log_statements(log::LevelFilter::Debug);
     connect_opts = connect_opts.database(&config.database);
@@ -63,143 +85,182 @@ async fn establish_connection(config: &Config) -> Result<PgPool> {
         .await?)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum ServerLevelFilter {
-    /// A level lower than all log levels.
-    Off,
-    /// Corresponds to the `Error` log level.
-    Error,
-    /// Corresponds to the `Warn` log level.
-    Warn,
-    /// Corresponds to the `Info` log level.
-    Info,
-    /// Corresponds to the `Debug` log level.
-    Debug,
-    /// Corresponds to the `Trace` log level.
-    Trace,
-}
-
-impl ServerLevelFilter {
-    pub fn parse_enum(&self) -> LevelFilter {
-        match self {
-            ServerLevelFilter::Off => LevelFilter::Off,
-            ServerLevelFilter::Error => LevelFilter::Error,
-            ServerLevelFilter::Warn => LevelFilter::Warn,
-            ServerLevelFilter::Info => LevelFilter::Info,
-            ServerLevelFilter::Debug => LevelFilter::Debug,
-            ServerLevelFilter::Trace => LevelFilter::Trace,
+fn build_tls_config(
+    cert_resolver: Arc<CertResolver>,
+    config: &Config,
+    revoked: HashSet<String>,
+) -> Result<Arc<rustls::ServerConfig>> {
+    let tls = &config.tls;
+    let builder = ServerConfig::builder_with_provider(
+        CryptoProvider {
+            cipher_suites: tls.cipher_suites(),
+            ..provider::default_provider()
         }
-    }
-}
+        .into(),
+    )
+    .with_protocol_versions(&tls.protocol_versions())?;
 
-#[derive(Deserialize)]
-pub struct Config {
-    pub listen: String,
-    pub clients_port: u16,
-    pub servers_port: u16,
-    pub server_cert: String,
-    pub server_key: String,
-    pub ca_root: String,
-    pub maxconnections: usize,
-    pub database: String,
-    pub username: String,
-    pub password: String,
-    pub host: String,
-    pub port: u16,
-    pub enable_backtrace: bool,
-    pub level_filter: ServerLevelFilter,
-}
+    let builder = match tls.client_auth {
+        ClientAuth::Off => builder.with_no_client_auth(),
+        ClientAuth::Optional | ClientAuth::Required => builder.with_client_cert_verifier(Arc::new(
+            ClientVerifier::new(&config.ca_root, tls, revoked)?,
+        )),
+    };
 
-pub fn read_config(path: &str) -> Config {
-    let data = fs::read_to_string(path).unwrap();
-    toml::from_str(&data).unwrap()
-}
+    let mut tls_config = builder.with_cert_resolver(cert_resolver);
 
-fn load_certs(filename: &str) -> Vec<CertificateDer<'static>> {
-    let certfile = fs::File::open(filename).expect("cannot open certificate file");
-    let mut reader = BufReader::new(certfile);
-    rustls_pemfile::certs(&mut reader)
-        .map(|result| result.unwrap())
-        .collect()
-}
+    tls_config.alpn_protocols = tls
+        .alpn_protocols
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
 
-fn load_private_key(filename: &str) -> PrivateKeyDer<'static> {
-    let keyfile = fs::File::open(filename).expect("cannot open private key file");
-    let mut reader = BufReader::new(keyfile);
-
-    loop {
-        match rustls_pemfile::read_one(&mut reader).expect("cannot parse private key .pem file") {
-            Some(rustls_pemfile::Item::Pkcs1Key(key)) => return key.into(),
-            Some(rustls_pemfile::Item::Pkcs8Key(key)) => return key.into(),
-            Some(rustls_pemfile::Item::Sec1Key(key)) => return key.into(),
-            None => break,
-            _ => {}
-        }
+    tls_config.session_storage = if tls.session_cache_size > 0 {
+        ServerSessionMemoryCache::new(tls.session_cache_size)
+    } else {
+        Arc::new(NoServerSessionStorage {})
+    };
+
+    if tls.session_tickets {
+        tls_config.ticketer = provider::Ticketer::new()?;
+    } else if tls.session_cache_size == 0 {
+        // Nothing could resume from a TLS 1.3 ticket, so do not send any.
+        tls_config.send_tls13_tickets = 0;
     }
 
-    panic!(
-        "no keys found in {:?} (encrypted keys not supported)",
-        filename
-    );
+    Ok(Arc::new(tls_config))
 }
 
-fn build_tls_config(
-    server_certs_path: &str,
-    server_key_path: &str,
-    _ca_root_path: &str,
-) -> Result<Arc<rustls::ServerConfig>> {
-    let certs = load_certs(server_certs_path);
-    let private_key = load_private_key(server_key_path);
-
-    let config = ServerConfig::builder_with_provider(
-        CryptoProvider {
-            cipher_suites: provider::ALL_CIPHER_SUITES.to_vec(),
-            ..provider::default_provider()
-        }
-        .into(),
-    )
-    .with_protocol_versions(rustls::ALL_VERSIONS)
-    .unwrap()
-    .with_no_client_auth()
-    .with_single_cert(certs, private_key)?;
-
-    Ok(Arc::new(config))
+/// Certificates revoked in the account store, only needed when clients send certificates.
+async fn load_revoked_certs(
+    accounts: &dyn AccountStore,
+    config: &Config,
+) -> Result<HashSet<String>> {
+    match config.tls.client_auth {
+        ClientAuth::Off => Ok(HashSet::new()),
+        ClientAuth::Optional | ClientAuth::Required => accounts.find_revoked_certs().await,
+    }
 }
 
 impl Storage {
-    pub async fn new(config: Config) -> Option<Self> {
-        assert_ne!(
-            config.clients_port, config.servers_port,
-            "Client Port and Server Port can not be the same."
-        );
-
-        let mut poll = Poll::new().ok()?;
-        let tls_config =
-            build_tls_config(&config.server_cert, &config.server_key, &config.ca_root).unwrap();
-        let server = Server::new(
-            &mut poll,
-            &config.listen,
-            config.clients_port,
-            config.servers_port,
-            config.maxconnections,
-            tls_config,
-        )
-        .ok()?;
-
-        let pgconn = establish_connection(&config).await.unwrap();
-        crate::sql::initiate(&pgconn).await.unwrap();
-        let keys = RotatableJwtKey::new(KEY_LENGTH);
-
-        Some(Self {
+    pub async fn new(
+        config: Config,
+        accounts: Arc<dyn AccountStore>,
+        mailer: Arc<dyn Mailer>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
+        let mut poll = Poll::new()?;
+        let cert_resolver = Arc::new(CertResolver::new(&config.server_cert, &config.server_key)?);
+        let revoked = load_revoked_certs(accounts.as_ref(), &config).await?;
+        let tls_config = build_tls_config(cert_resolver.clone(), &config, revoked)?;
+        let server = Server::new(&mut poll, &config, tls_config)?;
+
+        let keys = RotatableJwtKey::new(KEY_LENGTH, clock.now());
+        let metrics = Metrics::new()?;
+        let locales = Locales::load(&config.localization)?;
+        let (console_sender, console) = mpsc::unbounded_channel();
+        let maintenance = config.maintenance;
+
+        Ok(Self {
             servers: IndexMap::default(),
             server_names: IndexMap::default(),
             client_ids: IndexSet::default(),
             servers_ids: IndexSet::default(),
             poll: RwLock::new(poll),
             server: Arc::new(RwLock::new(server)),
-            pgconn,
+            accounts,
+            mailer,
+            clock,
             config,
             keys,
+            cert_resolver,
+            metrics,
+            locales,
+            console,
+            console_sender,
+            maintenance,
+            shutdown: false,
         })
     }
+
+    /// Forgets a game server once its connection has closed so it leaves the server list.
+    pub fn remove_game_server_info(&mut self, token: mio::Token) {
+        if let Some(info) = self.servers.swap_remove(&token) {
+            if self.server_names.get(&info.name) == Some(&token) {
+                self.server_names.swap_remove(&info.name);
+            }
+        }
+    }
+
+    /// Reloads the server certificate and, when clients send certificates, the CA, CRL
+    /// file and revoked certificates they are checked against. Only new connections are
+    /// affected. Nothing changes when any of them fails to load.
+    pub async fn reload_tls(&self) -> Result<()> {
+        let tls_config = match self.config.tls.client_auth {
+            ClientAuth::Off => None,
+            ClientAuth::Optional | ClientAuth::Required => {
+                let revoked = load_revoked_certs(self.accounts.as_ref(), &self.config).await?;
+                Some(build_tls_config(
+                    self.cert_resolver.clone(),
+                    &self.config,
+                    revoked,
+                )?)
+            }
+        };
+
+        self.cert_resolver.reload()?;
+
+        if let Some(tls_config) = tls_config {
+            self.server.write().await.set_tls_config(tls_config);
+        }
+
+        Ok(())
+    }
+
+    /// Reloads the settings that are safe to change while running: connection limits,
+    /// compression, logging, maintenance mode, the MOTD and the locale catalogs.
+    /// Everything else is reported as needing a restart. The running configuration is
+    /// kept when the new one is invalid.
+    pub async fn reload_config(&mut self) -> Result<()> {
+        let config = load_config(CONFIG_PATH)?;
+
+        for setting in self.config.restart_required(&config) {
+            warn!(
+                "{} changed in {} but needs a restart to apply.",
+                setting, CONFIG_PATH
+            );
+        }
+
+        configure_logging(&config.logging);
+
+        if config.maxconnections != self.config.maxconnections {
+            self.server
+                .write()
+                .await
+                .set_max_connections(config.maxconnections);
+        }
+
+        if config.maintenance != self.config.maintenance {
+            self.maintenance = config.maintenance;
+            info!("Maintenance mode set to {}", self.maintenance);
+        }
+
+        self.config.logging = config.logging;
+        self.config.maxconnections = config.maxconnections;
+        self.config.connections = config.connections;
+        self.config.compression = config.compression;
+        self.config.shutdown_timeout = config.shutdown_timeout;
+        self.config.maintenance = config.maintenance;
+        self.config.motd = config.motd;
+
+        match Locales::load(&config.localization) {
+            Ok(locales) => {
+                self.locales = locales;
+                self.config.localization = config.localization;
+            }
+            Err(e) => warn!("Keeping the current locale catalogs: {}", e),
+        }
+
+        Ok(())
+    }
 }
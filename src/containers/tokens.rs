@@ -29,13 +29,13 @@ pub struct RotatableKey {
 }
 
 impl RotatableKey {
-    pub fn new(key_size: usize) -> Self {
+    pub fn new(key_size: usize, now: DateTime<Utc>) -> Self {
         let keys = [gen_token(key_size), gen_token(key_size)];
 
         Self {
             keys,
             active_id: 0,
-            last_updated: Utc::now(),
+            last_updated: now,
         }
     }
 
@@ -67,7 +67,7 @@ impl RotatableKey {
         self.last_updated
     }
 
-    pub fn rotate(&mut self) {
+    pub fn rotate(&mut self, now: DateTime<Utc>) {
         self.active_id = (self.active_id + 1) % 2;
 
         let key = loop {
@@ -81,7 +81,7 @@ impl RotatableKey {
         };
 
         self.keys[self.active_id] = key;
-        self.last_updated = Utc::now();
+        self.last_updated = now;
     }
 }
 
@@ -92,8 +92,8 @@ pub struct RotatableJwtKey {
 }
 
 impl RotatableJwtKey {
-    pub fn new(key_size: usize) -> Self {
-        let rotatable_key = RotatableKey::new(key_size);
+    pub fn new(key_size: usize, now: DateTime<Utc>) -> Self {
+        let rotatable_key = RotatableKey::new(key_size, now);
         let keys = rotatable_key.keys();
 
         let keys = [
@@ -148,8 +148,8 @@ impl RotatableJwtKey {
         None
     }
 
-    pub fn rotate(&mut self) {
-        self.rotatable_key.rotate();
+    pub fn rotate(&mut self, now: DateTime<Utc>) {
+        self.rotatable_key.rotate(now);
         let active_id = self.rotatable_key.active_id();
         self.keys[active_id] = Key::from_secret(self.rotatable_key.active_key().as_bytes());
     }
@@ -1,5 +1,7 @@
 mod handledata;
 mod mainloop;
+mod shutdown;
 
 pub use handledata::{client_handle_data, game_server_handle_data};
 pub use mainloop::game_loop;
+pub use shutdown::drain_connections;
@@ -1,39 +1,45 @@
 use super::routes;
 use crate::{containers::Storage, gametypes::*, socket::*};
 
-pub async fn client_packet_mapper(
-    storage: &mut Storage,
-    data: &mut MByteBuffer,
-    client: &mut Client,
-    id: ClientPacket,
-) -> Result<()> {
-    match id {
-        ClientPacket::Register => routes::handle_register(storage, data, client).await,
-        ClientPacket::Login => routes::handle_login(storage, data, client).await,
-        ClientPacket::RequestServers => {
-            routes::handle_server_list_request(storage, data, client).await
+/// Builds a mapper that reads each packet into the struct named after its ID in `$module`
+/// and passes it to its handler. The match fails to compile if an ID is left out.
+macro_rules! packet_mapper {
+    (
+        $name:ident($conn:ident: $conn_ty:ty, $id:ident => $module:ident) {
+            $($packet:ident => $handler:path),* $(,)?
         }
-        ClientPacket::OnlineCheck => Ok(()),
-        _ => Ok(()),
-    }
-}
-
-pub async fn game_server_packet_mapper(
-    storage: &mut Storage,
-    data: &mut MByteBuffer,
-    game_server: &mut GameServer,
-    id: GameServerPacket,
-) -> Result<()> {
-    match id {
-        GameServerPacket::Verification => {
-            routes::handle_verification(storage, data, game_server).await
-        }
-        GameServerPacket::UpdateInfo => {
-            routes::handle_update_server_info(storage, data, game_server).await
+    ) => {
+        pub async fn $name(
+            storage: &mut Storage,
+            data: &mut MByteBuffer,
+            $conn: &mut $conn_ty,
+            id: $id,
+        ) -> Result<()> {
+            match id {
+                $(
+                    $id::$packet => {
+                        let packet = decode::<$module::$packet>(data)?;
+                        $handler(storage, packet, $conn).await
+                    }
+                )*
+            }
         }
-        GameServerPacket::UpdateCount => {
-            routes::handle_update_server_count(storage, data, game_server).await
-        }
-        GameServerPacket::OnlineCheck => Ok(()),
-    }
+    };
 }
+
+packet_mapper!(client_packet_mapper(client: Client, ClientPacket => from_client) {
+    OnlineCheck => routes::handle_online_check,
+    Register => routes::handle_register,
+    Login => routes::handle_login,
+    PasswordReset => routes::handle_password_reset,
+    RequestServers => routes::handle_server_list_request,
+    Hello => routes::handle_hello,
+});
+
+packet_mapper!(game_server_packet_mapper(game_server: GameServer, GameServerPacket => from_game_server) {
+    OnlineCheck => routes::handle_game_server_online_check,
+    Verification => routes::handle_verification,
+    UpdateInfo => routes::handle_update_server_info,
+    UpdateCount => routes::handle_update_server_count,
+    Hello => routes::handle_game_server_hello,
+});
@@ -1,5 +1,10 @@
 use super::{client_packet_mapper, game_server_packet_mapper};
-use crate::{containers::Storage, gametypes::Result, socket::*};
+use crate::{
+    containers::Storage,
+    gametypes::{ErrorCode, Result},
+    socket::*,
+};
+use log::{trace, warn};
 
 pub async fn client_handle_data(
     storage: &mut Storage,
@@ -7,6 +12,27 @@ pub async fn client_handle_data(
     client: &mut Client,
 ) -> Result<()> {
     let id: ClientPacket = data.read()?;
+    let _timer = storage
+        .metrics
+        .handler_latency
+        .with_label_values(&[&format!("{:?}", id)])
+        .start_timer();
+
+    // Builds that do not agree on the protocol could misread any other packet.
+    if !client.hello_accepted && id != ClientPacket::Hello {
+        // Packets already buffered behind the first are dropped with the connection.
+        if client.state == SocketState::Closing {
+            return Ok(());
+        }
+
+        trace!(
+            "Client {} sent {:?} before an accepted Hello",
+            client.addr,
+            id
+        );
+        send_error(storage, client, ErrorCode::Outdated).await?;
+        return client.set_to_closing(storage).await;
+    }
 
     client_packet_mapper(storage, data, client, id).await
 }
@@ -17,6 +43,20 @@ pub async fn game_server_handle_data(
     server: &mut GameServer,
 ) -> Result<()> {
     let id: GameServerPacket = data.read()?;
+    let _timer = storage
+        .metrics
+        .handler_latency
+        .with_label_values(&[&format!("GameServer{:?}", id)])
+        .start_timer();
+
+    if !server.hello_accepted && id != GameServerPacket::Hello {
+        warn!(
+            addr = server.addr, token = server.token.0;
+            "Game server {} sent {:?} before an accepted Hello, disconnecting it.",
+            server.addr, id
+        );
+        return server.set_to_closing(storage).await;
+    }
 
     game_server_packet_mapper(storage, data, server, id).await
 }
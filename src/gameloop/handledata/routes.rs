@@ -1,97 +1,80 @@
 use crate::{
-    containers::{Storage, UserClaim},
+    containers::{GameServerInfo, Storage, UserClaim},
     gametypes::*,
+    locales::Message,
     players::*,
     socket::*,
     sql::*,
 };
 use jsonwebtoken::{Algorithm, Header, Validation};
-use log::info;
+use log::{info, warn};
 use rand::distributions::{Alphanumeric, DistString};
 use regex::Regex;
 
+pub async fn handle_online_check(
+    _storage: &mut Storage,
+    _packet: from_client::OnlineCheck,
+    _client: &mut Client,
+) -> Result<()> {
+    Ok(())
+}
+
 pub async fn handle_register(
     storage: &mut Storage,
-    data: &mut MByteBuffer,
+    packet: from_client::Register,
     client: &mut Client,
 ) -> Result<()> {
-    let username = data.read::<String>()?;
-    let password = data.read::<String>()?;
-    let email = data.read::<String>()?;
-    let sprite_id = data.read::<u8>()?;
-    let appmajor = data.read::<u16>()? as usize;
-    let appminior = data.read::<u16>()? as usize;
-    let apprevision = data.read::<u16>()? as usize;
-    let server_name = data.read::<String>()?;
+    let from_client::Register {
+        username,
+        password,
+        email,
+        sprite_id,
+        app_major,
+        app_minor,
+        app_revision,
+        server_name,
+    } = packet;
+    if APP_MAJOR > app_major as usize
+        && APP_MINOR > app_minor as usize
+        && APP_REVISION > app_revision as usize
+    {
+        return send_error(storage, client, ErrorCode::Outdated).await;
+    }
 
-    if APP_MAJOR > appmajor && APP_MINOR > appminior && APP_REVISION > apprevision {
-        return send_infomsg(storage, client, "Client needs to be updated.".into(), true).await;
+    if storage.maintenance {
+        return send_error(storage, client, ErrorCode::RegistrationClosed).await;
     }
 
-    let email_regex = Regex::new(
-        r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@([a-z0-9]+([\-\.]{1}[a-z0-9]+)*\.[a-z]{2,6})",
-    )?;
+    let email_regex = Regex::new(EMAIL_PATTERN)?;
 
     if !username.chars().all(is_name_acceptable) || !password.chars().all(is_password_acceptable) {
-        return send_infomsg(
-            storage,
-            client,
-            "Username or Password contains unaccepted Characters".into(),
-            true,
-        )
-        .await;
+        return send_error(storage, client, ErrorCode::InvalidCharacters).await;
     }
 
     if username.len() >= 64 {
-        return send_infomsg(
-            storage,
-            client,
-            "Username has too many Characters, 64 Characters Max".into(),
-            true,
-        )
-        .await;
+        return send_error(storage, client, ErrorCode::UsernameTooLong).await;
     }
 
     if password.len() >= 128 {
-        return send_infomsg(
-            storage,
-            client,
-            "Password has too many Characters, 128 Characters Max".into(),
-            true,
-        )
-        .await;
+        return send_error(storage, client, ErrorCode::PasswordTooLong).await;
     }
 
-    if !email_regex.is_match(&email) || sprite_id >= 6 {
-        return send_infomsg(
-            storage,
-            client,
-            "Email must be an actual email.".into(),
-            true,
-        )
-        .await;
+    if !email_regex.is_match(&email) {
+        return send_error(storage, client, ErrorCode::InvalidEmail).await;
     }
 
-    match check_existance(storage, &username, &email).await {
+    if sprite_id >= 6 {
+        return send_error(storage, client, ErrorCode::InvalidSprite).await;
+    }
+
+    match storage.accounts.check_existance(&username, &email).await {
         Ok(i) => match i {
             0 => {}
             1 => {
-                return send_infomsg(
-                    storage,
-                    client,
-                    "Username Exists. Please try Another.".into(),
-                    true,
-                )
-                .await;
+                return send_error(storage, client, ErrorCode::UsernameTaken).await;
             }
             2 => {
-                return send_infomsg(
-                    storage,
-                    client,
-                    "Email Already Exists. Please Try Another.".into(),
-                    true,
-                )
-                .await;
+                return send_error(storage, client, ErrorCode::EmailTaken).await;
             }
             _ => return Err(AscendingError::RegisterFail),
         },
@@ -107,12 +90,14 @@ pub async fn handle_register(
     player.code.clone_from(&code);
 
     info!(
+        addr = client.addr, token = client.token.0, server = server_name;
         "New Player {} with IP {}, sending Login Token.",
         &username, &client.addr
     );
 
     match new_player(storage, client, &player, password).await {
         Ok(uid) => {
+            storage.metrics.registrations.inc();
             let claim = UserClaim { server_name, uid };
 
             let token = storage
@@ -121,76 +106,115 @@ pub async fn handle_register(
 
             send_login(storage, client, token, &code).await
         }
-        Err(_) => {
-            send_infomsg(
-                storage,
-                client,
-                "There was an Issue Creating the player account. Please Contact Support.".into(),
-                true,
-            )
-            .await
-        }
+        Err(_) => send_error(storage, client, ErrorCode::RegistrationFailed).await,
     }
 }
 
 pub async fn handle_login(
     storage: &mut Storage,
-    data: &mut MByteBuffer,
+    packet: from_client::Login,
     client: &mut Client,
 ) -> Result<()> {
-    let email = data.read::<String>()?;
-    let password = data.read::<String>()?;
-    let appmajor = data.read::<u16>()? as usize;
-    let appminior = data.read::<u16>()? as usize;
-    let apprevision = data.read::<u16>()? as usize;
-    let reconnect_code = data.read::<String>()?;
-    let server_name = data.read::<String>()?;
-
+    let from_client::Login {
+        email,
+        password,
+        app_major,
+        app_minor,
+        app_revision,
+        reconnect_code,
+        server_name,
+    } = packet;
     if let Some(token) = storage.server_names.get(&server_name) {
         if let Some(server) = storage.servers.get(token) {
             if server.players_on + 1 >= server.max_players {
-                return send_infomsg(
+                storage.metrics.login("server_full");
+                return send_error_with(
                     storage,
                     client,
-                    "Server is full please try another.".into(),
-                    true,
+                    ErrorCode::ServerFull,
+                    &[("server", &server_name)],
                 )
                 .await;
             }
         } else {
-            return send_infomsg(storage, client, "Can't find Server.".into(), true).await;
+            storage.metrics.login("unknown_server");
+            return send_error_with(
+                storage,
+                client,
+                ErrorCode::UnknownServer,
+                &[("server", &server_name)],
+            )
+            .await;
         }
     } else {
-        return send_infomsg(storage, client, "Can't find Server.".into(), true).await;
+        storage.metrics.login("unknown_server");
+        return send_error_with(
+            storage,
+            client,
+            ErrorCode::UnknownServer,
+            &[("server", &server_name)],
+        )
+        .await;
     }
 
-    if APP_MAJOR > appmajor && APP_MINOR > appminior && APP_REVISION > apprevision {
-        return send_infomsg(storage, client, "Client needs to be updated.".into(), true).await;
+    if APP_MAJOR > app_major as usize
+        && APP_MINOR > app_minor as usize
+        && APP_REVISION > app_revision as usize
+    {
+        storage.metrics.login("outdated");
+        return send_error(storage, client, ErrorCode::Outdated).await;
     }
 
     if email.len() >= 64 || password.len() >= 128 {
-        return send_infomsg(
-            storage,
-            client,
-            "Account does not Exist or Password is not Correct.".into(),
-            true,
-        )
-        .await;
+        storage.metrics.login("bad_credentials");
+        return send_error(storage, client, ErrorCode::BadCredentials).await;
     }
 
-    let player: PlayerWithPassword = match find_player(storage, &email, &password).await? {
-        Some(player) => player,
-        None => {
-            return send_infomsg(
-                storage,
-                client,
-                "Account does not Exist or Password is not Correct.".into(),
-                true,
-            )
-            .await;
+    // Resumed TLS sessions skip the handshake's revocation check, and certificates
+    // revoked from the command line only reach it on the next reloadcerts.
+    if let Some(fingerprint) = client.cert_fingerprint() {
+        if storage.accounts.is_cert_revoked(&fingerprint).await? {
+            storage.metrics.login("cert_revoked");
+            return send_error(storage, client, ErrorCode::CertificateRevoked).await;
         }
+    }
+
+    let player: PlayerWithPassword = match find_player(storage, &email, &password).await {
+        Ok(Some(player)) => player,
+        Ok(None) | Err(AscendingError::IncorrectPassword) => {
+            storage.metrics.login("bad_credentials");
+            return send_error(storage, client, ErrorCode::BadCredentials).await;
+        }
+        Err(e) => return Err(e),
     };
 
+    if player.is_banned(storage.clock.now()) {
+        storage.metrics.login("banned");
+
+        let Some(expires) = player.ban_expires else {
+            return send_error(storage, client, ErrorCode::Banned).await;
+        };
+
+        let expires = expires.format("%Y-%m-%d %H:%M UTC").to_string();
+        let message = storage.locales.text(
+            &client.locale,
+            Message::BannedUntil,
+            &[("expires", &expires)],
+        );
+
+        return send_alert(storage, client, ErrorCode::Banned, message, true).await;
+    }
+
+    if player.pass_reset_code.is_some() {
+        storage.metrics.login("password_reset");
+        return send_error(storage, client, ErrorCode::PasswordResetRequired).await;
+    }
+
+    if storage.maintenance && player.access != UserAccess::Admin {
+        storage.metrics.login("maintenance");
+        return send_error(storage, client, ErrorCode::Maintenance).await;
+    }
+
     // we need to Add all the player types creations in a sub function that Creates the Defaults and then adds them to World.
     let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
 
@@ -200,13 +224,8 @@ pub async fn handle_login(
                 let token = match storage.server_names.get(&current) {
                     Some(token) => token,
                     None => {
-                        return send_infomsg(
-                            storage,
-                            client,
-                            "Account logged in. Could not Verify Relogin Code.".into(),
-                            true,
-                        )
-                        .await;
+                        storage.metrics.login("relogin_failed");
+                        return send_error(storage, client, ErrorCode::ReloginFailed).await;
                     }
                 };
 
@@ -215,13 +234,8 @@ pub async fn handle_login(
                 let game_server = match game_server {
                     Some(v) => v,
                     None => {
-                        return send_infomsg(
-                            storage,
-                            client,
-                            "Account logged in. Could not Verify Relogin Code.".into(),
-                            true,
-                        )
-                        .await;
+                        storage.metrics.login("relogin_failed");
+                        return send_error(storage, client, ErrorCode::ReloginFailed).await;
                     }
                 };
 
@@ -229,26 +243,20 @@ pub async fn handle_login(
 
                 send_kill_client(storage, &mut lock, player.uid).await?;
             } else {
-                return send_infomsg(
-                    storage,
-                    client,
-                    "Account logged in. Could not Verify Relogin Code.".into(),
-                    true,
-                )
-                .await;
+                storage.metrics.login("relogin_failed");
+                return send_error(storage, client, ErrorCode::ReloginFailed).await;
             }
         } else {
-            return send_infomsg(
-                storage,
-                client,
-                "Account logged in. Could not Verify Relogin Code.".into(),
-                true,
-            )
-            .await;
+            storage.metrics.login("relogin_failed");
+            return send_error(storage, client, ErrorCode::ReloginFailed).await;
         }
     }
 
+    let cert_fingerprint = client.cert_fingerprint();
+
     info!(
+        uid = player.uid, addr = client.addr, token = client.token.0, server = server_name,
+        cert = cert_fingerprint;
         "Player {} with IP: {}, Logging in to Server: {}",
         &player.username, &client.addr, &server_name
     );
@@ -262,74 +270,496 @@ pub async fn handle_login(
         .keys
         .encode(&Header::new(Algorithm::HS512), &claim)?;
 
-    update_reconnect_code(storage, player.uid, Some(code.clone())).await?;
+    storage
+        .accounts
+        .update_reconnect_code(player.uid, Some(code.clone()))
+        .await?;
+
+    let log = PGLog::new(
+        0,
+        player.uid,
+        LogType::Login,
+        match &cert_fingerprint {
+            Some(fingerprint) => format!(
+                "Logged in to server {} with certificate {}",
+                claim.server_name, fingerprint
+            ),
+            None => format!("Logged in to server {}", claim.server_name),
+        },
+        client.addr.clone(),
+    );
+
+    if let Err(e) = storage.accounts.insert_log(&log).await {
+        warn!(uid = player.uid; "Failed to write the login log: {}", e);
+    }
+
+    storage.metrics.login("success");
     send_login(storage, client, token, &code).await
 }
 
+/// Sets a new password for an account with a forced reset when the code matches.
+pub async fn handle_password_reset(
+    storage: &mut Storage,
+    packet: from_client::PasswordReset,
+    client: &mut Client,
+) -> Result<()> {
+    let from_client::PasswordReset {
+        email,
+        reset_code,
+        password,
+    } = packet;
+
+    if !password.chars().all(is_password_acceptable) {
+        return send_error(storage, client, ErrorCode::InvalidCharacters).await;
+    }
+
+    if password.len() >= 128 {
+        return send_error(storage, client, ErrorCode::PasswordTooLong).await;
+    }
+
+    let player = match storage.accounts.find_login(&email).await? {
+        Some(player)
+            if !reset_code.is_empty()
+                && player.pass_reset_code.as_deref() == Some(reset_code.as_str()) =>
+        {
+            player
+        }
+        _ => return send_error(storage, client, ErrorCode::InvalidResetCode).await,
+    };
+
+    let timer = storage.metrics.argon2_timer("hash");
+    let hashed_password = hash_password(&password);
+    timer.observe_duration();
+
+    storage
+        .accounts
+        .complete_password_reset(player.uid, hashed_password)
+        .await?;
+
+    info!(
+        addr = client.addr, token = client.token.0;
+        "Player {} with IP {} completed a password reset.",
+        player.username, client.addr
+    );
+
+    let message = storage
+        .locales
+        .text(&client.locale, Message::PasswordChanged, &[]);
+
+    send_alert(storage, client, ErrorCode::None, message, false).await
+}
+
+/// Answers with this server's protocol and disconnects clients built against another.
+pub async fn handle_hello(
+    storage: &mut Storage,
+    packet: from_client::Hello,
+    client: &mut Client,
+) -> Result<()> {
+    let accepted = packet.protocol_hash == protocol_hash();
+    // WebSocket messages have no length prefix to carry the compressed flag.
+    let compression = match client.transport {
+        Transport::Tls if accepted && storage.config.compression.enabled => packet.compression,
+        _ => Compression::None,
+    };
+
+    client.locale = packet.locale;
+    send_hello(storage, client, accepted, compression).await?;
+    client.compression = compression;
+    client.hello_accepted = accepted;
+
+    if accepted {
+        return Ok(());
+    }
+
+    info!(
+        addr = client.addr, token = client.token.0;
+        "Client {} uses protocol {} {} instead of {} {}",
+        client.addr, packet.protocol_version, packet.protocol_hash, PROTOCOL_VERSION, protocol_hash()
+    );
+
+    send_error(storage, client, ErrorCode::Outdated).await
+}
+
 pub async fn handle_server_list_request(
     storage: &mut Storage,
-    _data: &mut MByteBuffer,
+    _packet: from_client::RequestServers,
     client: &mut Client,
 ) -> Result<()> {
-    send_server_list(storage, client).await
+    send_server_list(storage, client).await?;
+
+    if !storage.config.motd.is_empty() {
+        let motd = storage.config.motd.clone();
+        send_fltalert(storage, client, motd, FtlType::Message).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_game_server_online_check(
+    _storage: &mut Storage,
+    _packet: from_game_server::OnlineCheck,
+    _game_server: &mut GameServer,
+) -> Result<()> {
+    Ok(())
+}
+
+/// Answers with this server's protocol and disconnects game servers built against another.
+pub async fn handle_game_server_hello(
+    storage: &mut Storage,
+    packet: from_game_server::Hello,
+    game_server: &mut GameServer,
+) -> Result<()> {
+    let accepted = packet.protocol_hash == protocol_hash();
+    let compression = if accepted && storage.config.compression.enabled {
+        packet.compression
+    } else {
+        Compression::None
+    };
+
+    send_game_server_hello(storage, game_server, accepted, compression).await?;
+    game_server.compression = compression;
+    game_server.hello_accepted = accepted;
+
+    if !accepted {
+        warn!(
+            addr = game_server.addr, token = game_server.token.0;
+            "Game server {} uses protocol {} {} instead of {} {}, disconnecting it.",
+            game_server.addr, packet.protocol_version, packet.protocol_hash, PROTOCOL_VERSION, protocol_hash()
+        );
+        game_server.set_to_closing(storage).await?;
+    }
+
+    Ok(())
 }
 
 pub async fn handle_verification(
     storage: &mut Storage,
-    data: &mut MByteBuffer,
+    packet: from_game_server::Verification,
     game_server: &mut GameServer,
 ) -> Result<()> {
-    let token = data.read::<String>()?;
+    let mut validation = Validation::new(Algorithm::HS512);
+    // Login tokens have no expiry, rotating the keys is what invalidates them.
+    validation.required_spec_claims.clear();
 
-    if let Some((_index, data)) = storage
-        .keys
-        .decode::<UserClaim>(&token, &Validation::new(Algorithm::HS512))
-    {
+    if let Some((_index, data)) = storage.keys.decode::<UserClaim>(&packet.token, &validation) {
         let claim: UserClaim = data.claims;
         if let Some(server) = storage.servers.get(&game_server.token) {
             if claim.server_name == server.name {
+                storage
+                    .metrics
+                    .verifications
+                    .with_label_values(&["accepted"])
+                    .inc();
                 return send_verification(storage, game_server, claim.uid, true).await;
             }
         }
     }
 
+    storage
+        .metrics
+        .verifications
+        .with_label_values(&["rejected"])
+        .inc();
     send_verification(storage, game_server, 0, false).await
 }
 
 pub async fn handle_update_server_info(
     storage: &mut Storage,
-    data: &mut MByteBuffer,
+    packet: from_game_server::UpdateInfo,
     game_server: &mut GameServer,
 ) -> Result<()> {
-    let name = data.read::<String>()?;
-    let ip = data.read::<String>()?;
-    let port = data.read::<u16>()?;
-    let players_on = data.read::<u64>()?;
-    let max_players = data.read::<u64>()?;
+    let from_game_server::UpdateInfo {
+        name,
+        ip,
+        port,
+        players_on,
+        max_players,
+    } = packet;
+
+    // A connection may not take over the name of a server that is still connected and
+    // receive its players' logins.
+    if let Some(&holder) = storage.server_names.get(&name) {
+        if holder != game_server.token {
+            warn!(
+                addr = game_server.addr, token = game_server.token.0;
+                "Game server {} asked for the name {} of another connected server, disconnecting it.",
+                game_server.addr, name
+            );
+            return game_server.set_to_closing(storage).await;
+        }
+    }
 
-    if let Some(server) = storage.servers.get_mut(&game_server.token) {
-        server.ip = ip;
-        server.port = port;
-        server.name = name;
-        server.players_on = players_on;
-        server.max_players = max_players;
+    if let Some(old) = storage.servers.get(&game_server.token) {
+        if old.name != name {
+            storage.server_names.swap_remove(&old.name);
+        }
     }
 
+    storage.server_names.insert(name.clone(), game_server.token);
+    storage.servers.insert(
+        game_server.token,
+        GameServerInfo {
+            name,
+            ip,
+            port,
+            players_on,
+            max_players,
+        },
+    );
+
     Ok(())
 }
 
 pub async fn handle_update_server_count(
     storage: &mut Storage,
-    data: &mut MByteBuffer,
+    packet: from_game_server::UpdateCount,
     game_server: &mut GameServer,
 ) -> Result<()> {
-    let players_on = data.read::<u64>()?;
-    let max_players = data.read::<u64>()?;
-
     if let Some(server) = storage.servers.get_mut(&game_server.token) {
-        server.players_on = players_on;
-        server.max_players = max_players;
+        server.players_on = packet.players_on;
+        server.max_players = packet.max_players;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Account, HandlerTest};
+
+    const SERVER: &str = "Test Server";
+
+    async fn register(test: &mut HandlerTest, account: &Account) -> i64 {
+        handle_register(
+            &mut test.storage,
+            account.register(SERVER),
+            &mut test.client,
+        )
+        .await
+        .unwrap();
+        test.expect::<to_client::Login>();
+
+        test.storage
+            .accounts
+            .find_account_by_name(&account.username)
+            .await
+            .unwrap()
+            .expect("registered account should be found")
+            .uid
+    }
+
+    async fn register_error(test: &mut HandlerTest, packet: from_client::Register) -> ErrorCode {
+        handle_register(&mut test.storage, packet, &mut test.client)
+            .await
+            .unwrap();
+        test.expect_error()
+    }
+
+    async fn login_error(test: &mut HandlerTest, packet: from_client::Login) -> ErrorCode {
+        handle_login(&mut test.storage, packet, &mut test.client)
+            .await
+            .unwrap();
+        test.expect_error()
+    }
+
+    #[tokio::test]
+    async fn register_refuses_taken_names_and_emails() {
+        let mut test = HandlerTest::new().await;
+        let account = Account::unique();
+        register(&mut test, &account).await;
+
+        let mut taken = Account::unique();
+        taken.username.clone_from(&account.username);
+        assert_eq!(
+            register_error(&mut test, taken.register(SERVER)).await,
+            ErrorCode::UsernameTaken
+        );
+
+        let mut taken = Account::unique();
+        taken.email.clone_from(&account.email);
+        assert_eq!(
+            register_error(&mut test, taken.register(SERVER)).await,
+            ErrorCode::EmailTaken
+        );
+    }
+
+    #[tokio::test]
+    async fn register_checks_its_fields() {
+        let mut test = HandlerTest::new().await;
+        let account = Account::unique();
+
+        type Change = fn(&mut from_client::Register);
+
+        let cases: [(Change, ErrorCode); 6] = [
+            (|p| p.username.push(' '), ErrorCode::InvalidCharacters),
+            (|p| p.password.push('#'), ErrorCode::InvalidCharacters),
+            (|p| p.username = "a".repeat(64), ErrorCode::UsernameTooLong),
+            (|p| p.password = "a".repeat(128), ErrorCode::PasswordTooLong),
+            (|p| p.email = "not an email".into(), ErrorCode::InvalidEmail),
+            (|p| p.sprite_id = 6, ErrorCode::InvalidSprite),
+        ];
+
+        for (change, code) in cases {
+            let mut packet = account.register(SERVER);
+            change(&mut packet);
+            assert_eq!(register_error(&mut test, packet).await, code);
+        }
+
+        assert!(test
+            .storage
+            .accounts
+            .find_account_by_name(&account.username)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn register_is_closed_during_maintenance() {
+        let mut test = HandlerTest::new().await;
+        test.storage.maintenance = true;
+
+        assert_eq!(
+            register_error(&mut test, Account::unique().register(SERVER)).await,
+            ErrorCode::RegistrationClosed
+        );
+    }
+
+    #[tokio::test]
+    async fn login_refuses_unknown_and_full_servers() {
+        let mut test = HandlerTest::new().await;
+        let account = Account::unique();
+        register(&mut test, &account).await;
+
+        assert_eq!(
+            login_error(&mut test, account.login(SERVER, "")).await,
+            ErrorCode::UnknownServer
+        );
+
+        test.list_server(SERVER, 9, 10);
+        assert_eq!(
+            login_error(&mut test, account.login(SERVER, "")).await,
+            ErrorCode::ServerFull
+        );
+    }
+
+    #[tokio::test]
+    async fn login_refuses_bad_credentials() {
+        let mut test = HandlerTest::new().await;
+        test.list_server(SERVER, 0, 10);
+        let account = Account::unique();
+        register(&mut test, &account).await;
+
+        let mut wrong = account.clone();
+        wrong.password = "Wr0ngPassword".into();
+        assert_eq!(
+            login_error(&mut test, wrong.login(SERVER, "")).await,
+            ErrorCode::BadCredentials
+        );
+
+        assert_eq!(
+            login_error(&mut test, Account::unique().login(SERVER, "")).await,
+            ErrorCode::BadCredentials
+        );
+
+        let mut long = account.clone();
+        long.email = format!("{}@example.com", "a".repeat(64));
+        assert_eq!(
+            login_error(&mut test, long.login(SERVER, "")).await,
+            ErrorCode::BadCredentials
+        );
+    }
+
+    #[tokio::test]
+    async fn login_refuses_banned_and_reset_accounts() {
+        let mut test = HandlerTest::new().await;
+        test.list_server(SERVER, 0, 10);
+
+        let banned = Account::unique();
+        register(&mut test, &banned).await;
+        test.storage
+            .accounts
+            .ban_player(&banned.username, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            login_error(&mut test, banned.login(SERVER, "")).await,
+            ErrorCode::Banned
+        );
+
+        let reset = Account::unique();
+        let uid = register(&mut test, &reset).await;
+        assert!(test
+            .storage
+            .accounts
+            .force_password_reset(uid, "code")
+            .await
+            .unwrap());
+        assert_eq!(
+            login_error(&mut test, reset.login(SERVER, "")).await,
+            ErrorCode::PasswordResetRequired
+        );
+    }
+
+    #[tokio::test]
+    async fn login_is_for_admins_during_maintenance() {
+        let mut test = HandlerTest::new().await;
+        test.list_server(SERVER, 0, 10);
+        let account = Account::unique();
+        let uid = register(&mut test, &account).await;
+        test.storage.maintenance = true;
+
+        assert_eq!(
+            login_error(&mut test, account.login(SERVER, "")).await,
+            ErrorCode::Maintenance
+        );
+
+        test.storage
+            .accounts
+            .update_access(uid, UserAccess::Admin)
+            .await
+            .unwrap();
+        handle_login(
+            &mut test.storage,
+            account.login(SERVER, ""),
+            &mut test.client,
+        )
+        .await
+        .unwrap();
+        test.expect::<to_client::Login>();
+    }
+
+    #[tokio::test]
+    async fn login_refuses_relogin_without_the_code() {
+        let mut test = HandlerTest::new().await;
+        test.list_server(SERVER, 0, 10);
+        let account = Account::unique();
+        let uid = register(&mut test, &account).await;
+
+        handle_login(
+            &mut test.storage,
+            account.login(SERVER, ""),
+            &mut test.client,
+        )
+        .await
+        .unwrap();
+        let login = test.expect::<to_client::Login>();
+        test.storage
+            .accounts
+            .update_current_server(uid, Some(SERVER))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            login_error(&mut test, account.login(SERVER, "")).await,
+            ErrorCode::ReloginFailed
+        );
+
+        // The code matches, but the game server the account is on is not connected.
+        assert_eq!(
+            login_error(&mut test, account.login(SERVER, &login.relogin_code)).await,
+            ErrorCode::ReloginFailed
+        );
+    }
+}
@@ -1,20 +1,41 @@
-use crate::{containers::Storage, socket::*, time_ext::MyInstant};
-use chrono::{Duration, Utc};
+use super::drain_connections;
+use crate::metrics::update_gauges;
+use crate::{
+    console::process_console_requests, containers::Storage, socket::*, time_ext::MyInstant,
+};
+use chrono::Duration;
 
 pub async fn game_loop(storage: &mut Storage) {
     let mut _tick: MyInstant;
     // let mut ping_timer: MyInstant = MyInstant::now();
+    let mut metrics_timer: MyInstant = MyInstant::now();
+    let mut deadline_timer: MyInstant = MyInstant::now();
 
-    loop {
+    while !storage.shutdown {
         let updated_at = storage.keys.last_updated();
+        let now = storage.clock.now();
 
-        if updated_at + Duration::try_hours(8).unwrap_or_default() < Utc::now() {
-            storage.keys.rotate();
+        if updated_at + Duration::try_hours(8).unwrap_or_default() < now {
+            storage.keys.rotate(now);
         }
 
         poll_events(storage).await.unwrap();
         process_packets(storage).await.unwrap();
         process_client_packets(storage).await.unwrap();
+        process_console_requests(storage).await;
+
+        if deadline_timer < MyInstant::now() {
+            close_expired_connections(storage).await.unwrap();
+            deadline_timer = MyInstant::from_dur(1000);
+        }
+
+        if metrics_timer < MyInstant::now() {
+            update_gauges(storage).await;
+            metrics_timer = MyInstant::from_dur(1000);
+        }
+
         tokio::time::sleep(std::time::Duration::from_millis(1)).await;
     }
+
+    drain_connections(storage).await;
 }
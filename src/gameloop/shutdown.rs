@@ -0,0 +1,130 @@
+use crate::{containers::Storage, gametypes::*, locales::Message, socket::*, time_ext::MyInstant};
+use log::{info, warn};
+
+/// Stops accepting connections and tells every client and game server we are going down.
+/// Clients still in their handshake are closed instead.
+pub async fn begin_shutdown(storage: &mut Storage) -> Result<()> {
+    let server = storage.server.clone();
+    server
+        .write()
+        .await
+        .stop_listening(&*storage.poll.read().await)?;
+
+    let (clients, game_servers) = {
+        let server = server.read().await;
+        (
+            server.clients.values().cloned().collect::<Vec<_>>(),
+            server.game_servers.values().cloned().collect::<Vec<_>>(),
+        )
+    };
+
+    let mut unfinished = Vec::new();
+
+    for client in clients {
+        let mut client = client.lock().await;
+
+        // Nothing can be written to a client before its handshake is done, so a notice
+        // would only hold the shutdown until the timeout.
+        if !client.is_ready() {
+            let _ = client.close_socket(storage).await;
+            unfinished.push(client.token);
+            continue;
+        }
+
+        let message = storage
+            .locales
+            .text(&client.locale, Message::ShuttingDown, &[]);
+        send_client_shutdown(storage, &mut client, message).await?;
+    }
+
+    for token in unfinished {
+        server.write().await.remove(token);
+    }
+
+    for game_server in game_servers {
+        let mut game_server = game_server.lock().await;
+        send_game_server_shutdown(storage, &mut game_server).await?;
+    }
+
+    Ok(())
+}
+
+async fn has_pending_sends(storage: &Storage) -> bool {
+    let server = storage.server.read().await;
+
+    for client in server.clients.values() {
+        let client = client.lock().await;
+
+        if !client.sends.is_empty() || client.tls.wants_write() {
+            return true;
+        }
+    }
+
+    for game_server in server.game_servers.values() {
+        if !game_server.lock().await.sends.is_empty() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Keeps the sockets running until every queued packet is sent or the
+/// shutdown timeout passes, then closes everything and the database pool.
+pub async fn drain_connections(storage: &mut Storage) {
+    info!("Shutting down, draining connections.");
+
+    if let Err(e) = begin_shutdown(storage).await {
+        warn!("Failed to notify connections of the shutdown: {}", e);
+    }
+
+    let timeout =
+        i64::try_from(storage.config.shutdown_timeout.saturating_mul(1000)).unwrap_or(i64::MAX);
+    let deadline = MyInstant::from_dur(timeout);
+
+    while MyInstant::now() < deadline {
+        if let Err(e) = poll_events(storage).await {
+            warn!("Poll error while draining: {}", e);
+        }
+
+        if let Err(e) = process_packets(storage).await {
+            warn!("Game server packet error while draining: {}", e);
+        }
+
+        if let Err(e) = process_client_packets(storage).await {
+            warn!("Client packet error while draining: {}", e);
+        }
+
+        if !has_pending_sends(storage).await {
+            break;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+
+    if has_pending_sends(storage).await {
+        warn!("Shutdown timeout reached with packets still queued.");
+    }
+
+    let (clients, game_servers) = {
+        let server = storage.server.read().await;
+        (
+            server.clients.values().cloned().collect::<Vec<_>>(),
+            server.game_servers.values().cloned().collect::<Vec<_>>(),
+        )
+    };
+
+    for client in clients {
+        let _ = client.lock().await.close_socket(storage).await;
+    }
+
+    for game_server in game_servers {
+        let _ = game_server.lock().await.close_socket(storage).await;
+    }
+
+    // Waits for queries still running, such as audit log writes, before closing.
+    storage.accounts.close().await;
+    log::logger().flush();
+
+    info!("Shutdown complete.");
+}
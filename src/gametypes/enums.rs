@@ -1,6 +1,32 @@
 use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
 use serde::{Deserialize, Serialize};
 
+/// Defines an enum sent in packets along with `ALL`, its variants in the order of the
+/// index they are encoded as, so the protocol schema lists exactly what is sent.
+macro_rules! indexed_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+        }
+
+        impl $name {
+            pub const ALL: [$name; [$(stringify!($variant)),*].len()] = [$($name::$variant),*];
+        }
+    };
+}
+
 #[derive(
     Copy,
     Clone,
@@ -41,6 +67,7 @@ pub enum LogType {
     Item,
     Warning,
     Error,
+    Admin,
 }
 
 #[derive(
@@ -81,16 +108,114 @@ impl DeathType {
     }
 }
 
-#[derive(
-    Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, MByteBufferRead, MByteBufferWrite,
-)]
-pub enum FtlType {
-    Message,
-    Error,
-    Item,
-    Quest,
-    Level,
-    Money,
+indexed_enum! {
+    #[derive(
+        Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, MByteBufferRead, MByteBufferWrite,
+    )]
+    pub enum FtlType {
+        Message,
+        Error,
+        Item,
+        Quest,
+        Level,
+        Money,
+    }
+}
+
+indexed_enum! {
+    /// How frames larger than `compression.threshold` are compressed, agreed in `Hello`.
+    #[derive(
+        Copy,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+        Serialize,
+        Deserialize,
+        Default,
+        MByteBufferRead,
+        MByteBufferWrite,
+    )]
+    pub enum Compression {
+        #[default]
+        None,
+        /// Raw DEFLATE, RFC 1951.
+        Deflate,
+    }
+}
+
+indexed_enum! {
+    /// Why a registration or login was refused. Sent with the message so clients can show
+    /// their own text or react, such as opening the update screen for `Outdated`.
+    #[derive(
+        Copy,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        Serialize,
+        Deserialize,
+        Default,
+        MByteBufferRead,
+        MByteBufferWrite,
+    )]
+    pub enum ErrorCode {
+        /// Not a refusal, such as a message from an administrator.
+        #[default]
+        None,
+        Outdated,
+        RegistrationClosed,
+        InvalidCharacters,
+        UsernameTooLong,
+        PasswordTooLong,
+        InvalidEmail,
+        InvalidSprite,
+        UsernameTaken,
+        EmailTaken,
+        RegistrationFailed,
+        UnknownServer,
+        ServerFull,
+        BadCredentials,
+        Banned,
+        PasswordResetRequired,
+        Maintenance,
+        ReloginFailed,
+        InvalidResetCode,
+        CertificateRevoked,
+    }
+}
+
+impl ErrorCode {
+    /// The English text sent with the code.
+    pub fn message(self) -> &'static str {
+        match self {
+            ErrorCode::None => "",
+            ErrorCode::Outdated => "Client needs to be updated.",
+            ErrorCode::RegistrationClosed => "Server is under maintenance. Registration is closed.",
+            ErrorCode::InvalidCharacters => "Username or Password contains unaccepted Characters",
+            ErrorCode::UsernameTooLong => "Username has too many Characters, 64 Characters Max",
+            ErrorCode::PasswordTooLong => "Password has too many Characters, 128 Characters Max",
+            ErrorCode::InvalidEmail => "Email must be an actual email.",
+            ErrorCode::InvalidSprite => "That character sprite does not exist.",
+            ErrorCode::UsernameTaken => "Username Exists. Please try Another.",
+            ErrorCode::EmailTaken => "Email Already Exists. Please Try Another.",
+            ErrorCode::RegistrationFailed => {
+                "There was an Issue Creating the player account. Please Contact Support."
+            }
+            ErrorCode::UnknownServer => "Can't find Server.",
+            ErrorCode::ServerFull => "Server is full please try another.",
+            ErrorCode::BadCredentials => "Account does not Exist or Password is not Correct.",
+            ErrorCode::Banned => "This account has been banned.",
+            ErrorCode::PasswordResetRequired => {
+                "Your password must be reset before you can log in."
+            }
+            ErrorCode::Maintenance => "Server is under maintenance. Please try again later.",
+            ErrorCode::ReloginFailed => "Account logged in. Could not Verify Relogin Code.",
+            ErrorCode::InvalidResetCode => "Email or Password Reset Code is not Correct.",
+            ErrorCode::CertificateRevoked => "This installation's certificate has been revoked.",
+        }
+    }
 }
 
 #[derive(
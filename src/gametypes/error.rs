@@ -22,6 +22,12 @@ pub enum AscendingError {
     PacketReject { num: usize, message: String },
     #[error("Packet id was invalid")]
     InvalidPacket,
+    #[error("Frame length {length} is outside 1 to {max} bytes")]
+    InvalidFrameLength { length: u64, max: usize },
+    #[error("Compressed frame is invalid, unexpected or inflates past {max} bytes")]
+    InvalidCompressedFrame { max: usize },
+    #[error("Packet field {field} is longer than {max} bytes")]
+    FieldTooLong { field: &'static str, max: usize },
     #[error("Password was incorrect")]
     IncorrectPassword,
     #[error("No username was set.")]
@@ -32,6 +38,14 @@ pub enum AscendingError {
     MapNotFound(MapPosition),
     #[error("NPC ID {0:?} not found")]
     NpcNotFound(u64),
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String),
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    InvalidConfig(Vec<String>),
+    #[error("Certificate error: {0}")]
+    Certificate(String),
+    #[error("Admin API address {0} is not a loopback address and mTLS is not configured")]
+    InsecureAdminApi(String),
     #[error("Error: {error}, BackTrace: {backtrace}")]
     AddrParseError {
         #[from]
@@ -116,4 +130,25 @@ pub enum AscendingError {
         #[backtrace]
         backtrace: Box<Backtrace>,
     },
+    #[error("Error: {error}, BackTrace: {backtrace}")]
+    Prometheus {
+        #[from]
+        error: prometheus::Error,
+        #[backtrace]
+        backtrace: Box<Backtrace>,
+    },
+    #[error("Error: {error}, BackTrace: {backtrace}")]
+    Rcgen {
+        #[from]
+        error: rcgen::Error,
+        #[backtrace]
+        backtrace: Box<Backtrace>,
+    },
+    #[error("Error: {error}, BackTrace: {backtrace}")]
+    Notify {
+        #[from]
+        error: notify::Error,
+        #[backtrace]
+        backtrace: Box<Backtrace>,
+    },
 }
@@ -0,0 +1,29 @@
+#![allow(dead_code, clippy::let_and_return)]
+#![recursion_limit = "256"]
+#![feature(let_chains, error_generic_member_access)]
+#![feature(async_closure)]
+
+mod api;
+pub mod certs;
+pub mod cli;
+pub mod console;
+pub mod containers;
+mod gameloop;
+pub mod gametypes;
+mod items;
+mod locales;
+pub mod logging;
+pub mod metrics;
+pub mod players;
+pub mod server;
+mod socket;
+pub mod sql;
+pub mod store;
+#[cfg(test)]
+mod testing;
+mod time_ext;
+
+use gameloop::*;
+
+pub use gametypes::{AscendingError, Result};
+pub use server::{LoginServer, LoginServerBuilder, LoginServerHandle};
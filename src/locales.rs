@@ -0,0 +1,252 @@
+use crate::{containers::LocalizationConfig, gametypes::*};
+use log::{info, warn};
+use std::{collections::HashMap, fs, path::Path};
+
+/// A message shown to players that the catalogs may translate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Error(ErrorCode),
+    /// A ban with an end, `{expires}`.
+    BannedUntil,
+    Kicked,
+    ShuttingDown,
+    /// A forced password reset was completed.
+    PasswordChanged,
+}
+
+impl Message {
+    /// Every message with a catalog entry.
+    pub fn all() -> impl Iterator<Item = Message> {
+        ErrorCode::ALL
+            .into_iter()
+            .filter(|code| *code != ErrorCode::None)
+            .map(Message::Error)
+            .chain([
+                Message::BannedUntil,
+                Message::Kicked,
+                Message::ShuttingDown,
+                Message::PasswordChanged,
+            ])
+    }
+
+    /// Name of the message in the catalog files, such as `ServerFull`.
+    pub fn key(self) -> String {
+        match self {
+            Message::Error(code) => format!("{:?}", code),
+            message => format!("{:?}", message),
+        }
+    }
+
+    /// Text used when no catalog has the message.
+    pub fn english(self) -> &'static str {
+        match self {
+            Message::Error(code) => code.message(),
+            Message::BannedUntil => "This account is banned until {expires}.",
+            Message::Kicked => "You have been disconnected by an administrator.",
+            Message::ShuttingDown => "The server is shutting down.",
+            Message::PasswordChanged => "Your password has been changed. You can now log in.",
+        }
+    }
+}
+
+/// Message catalogs by locale, read from `<directory>/<locale>.toml`. Each file maps
+/// message keys to text in which `{name}` placeholders are filled in when sent.
+#[derive(Clone, Debug, Default)]
+pub struct Locales {
+    default_locale: String,
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Locales {
+    /// Reads every catalog in the configured directory. A missing directory leaves only
+    /// the English built into the server, a catalog that does not parse is an error.
+    pub fn load(config: &LocalizationConfig) -> Result<Self> {
+        let mut catalogs = HashMap::new();
+        let dir = Path::new(&config.directory);
+
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.extension().is_none_or(|ext| ext != "toml") {
+                    continue;
+                }
+
+                let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+
+                let catalog: HashMap<String, String> = toml::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| {
+                        AscendingError::InvalidConfig(vec![format!("{}: {}", path.display(), e)])
+                    })?;
+
+                catalogs.insert(normalize(locale), catalog);
+            }
+        } else {
+            warn!(
+                "Locale directory {} does not exist, messages will be sent in English.",
+                config.directory
+            );
+        }
+
+        let locales = Self {
+            default_locale: normalize(&config.default_locale),
+            catalogs,
+        };
+
+        locales.check();
+        info!(
+            "Loaded {} locale catalogs from {}",
+            locales.catalogs.len(),
+            config.directory
+        );
+
+        Ok(locales)
+    }
+
+    /// Warns about keys that no message uses and a default locale without a catalog.
+    fn check(&self) {
+        let keys: Vec<String> = Message::all().map(Message::key).collect();
+
+        for (locale, catalog) in &self.catalogs {
+            for key in catalog.keys().filter(|key| !keys.contains(key)) {
+                warn!("Locale {} has an unknown message {}", locale, key);
+            }
+        }
+
+        if !self.catalogs.is_empty() && !self.catalogs.contains_key(&self.default_locale) {
+            warn!(
+                "There is no catalog for the default locale {}, English is used instead.",
+                self.default_locale
+            );
+        }
+    }
+
+    /// The message in `locale`, falling back to its language, then the default locale,
+    /// then English, with each `{name}` of `args` filled in.
+    pub fn text(&self, locale: &str, message: Message, args: &[(&str, &str)]) -> String {
+        let locale = normalize(locale);
+        let language = locale.split('-').next().unwrap_or_default();
+        let key = message.key();
+
+        let template = [locale.as_str(), language, self.default_locale.as_str()]
+            .into_iter()
+            .filter_map(|locale| self.catalogs.get(locale))
+            .find_map(|catalog| catalog.get(&key))
+            .map_or(message.english(), String::as_str);
+
+        args.iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+/// Compares locales like `pt_BR` and `pt-br` as the same.
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales(default_locale: &str, catalogs: &[(&str, &[(&str, &str)])]) -> Locales {
+        Locales {
+            default_locale: normalize(default_locale),
+            catalogs: catalogs
+                .iter()
+                .map(|(locale, messages)| {
+                    let catalog = messages
+                        .iter()
+                        .map(|(key, text)| (key.to_string(), text.to_string()))
+                        .collect();
+
+                    (normalize(locale), catalog)
+                })
+                .collect(),
+        }
+    }
+
+    const FULL: Message = Message::Error(ErrorCode::ServerFull);
+    const BANNED: Message = Message::Error(ErrorCode::Banned);
+    const MAINTENANCE: Message = Message::Error(ErrorCode::Maintenance);
+
+    #[test]
+    fn normalizes_locales() {
+        assert_eq!(normalize("pt_BR"), "pt-br");
+        assert_eq!(normalize(" EN-gb "), "en-gb");
+    }
+
+    #[test]
+    fn falls_back_to_the_language_then_the_default_locale_then_english() {
+        let locales = locales(
+            "de",
+            &[
+                ("pt_BR", &[("ServerFull", "BR")]),
+                ("pt", &[("ServerFull", "PT"), ("Banned", "PT banido")]),
+                ("de", &[("ServerFull", "DE"), ("Kicked", "DE getrennt")]),
+            ],
+        );
+
+        assert_eq!(locales.text("pt-br", FULL, &[]), "BR");
+        assert_eq!(locales.text("PT_br", FULL, &[]), "BR");
+        assert_eq!(locales.text("pt-PT", FULL, &[]), "PT");
+        assert_eq!(locales.text("pt-BR", BANNED, &[]), "PT banido");
+        assert_eq!(locales.text("pt-BR", Message::Kicked, &[]), "DE getrennt");
+        assert_eq!(locales.text("fr", FULL, &[]), "DE");
+        assert_eq!(locales.text("", FULL, &[]), "DE");
+        assert_eq!(
+            locales.text("pt-BR", MAINTENANCE, &[]),
+            MAINTENANCE.english()
+        );
+    }
+
+    #[test]
+    fn uses_english_without_catalogs() {
+        let locales = Locales::default();
+
+        for message in Message::all() {
+            assert_eq!(locales.text("de", message, &[]), message.english());
+        }
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let locales = locales(
+            "en",
+            &[("en", &[("ServerFull", "{server} is full, {server}!")])],
+        );
+
+        assert_eq!(
+            locales.text("en", FULL, &[("server", "Main"), ("unused", "x")]),
+            "Main is full, Main!"
+        );
+        assert_eq!(locales.text("en", FULL, &[]), "{server} is full, {server}!");
+        assert_eq!(
+            locales.text(
+                "de",
+                Message::BannedUntil,
+                &[("expires", "2030-01-01 00:00 UTC")]
+            ),
+            "This account is banned until 2030-01-01 00:00 UTC."
+        );
+    }
+
+    #[test]
+    fn english_catalog_has_every_message() {
+        let locales = Locales::load(&LocalizationConfig::default()).unwrap();
+        let english = &locales.catalogs["en"];
+
+        for message in Message::all() {
+            assert!(
+                english
+                    .get(&message.key())
+                    .is_some_and(|text| !text.is_empty()),
+                "{} is missing",
+                message.key()
+            );
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod config;
+mod file;
+mod format;
+mod logger;
+
+pub use config::*;
+pub use file::*;
+pub use format::*;
+pub use logger::*;
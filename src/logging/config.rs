@@ -0,0 +1,45 @@
+use crate::containers::{HashMap, ServerLevelFilter};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub console_level: ServerLevelFilter,
+    pub console_json: bool,
+    pub file_level: ServerLevelFilter,
+    /// Rotated files are kept next to this one with a timestamp suffix.
+    pub file_path: String,
+    pub file_json: bool,
+    /// Size in bytes the log file may grow to before it is rotated. 0 disables size rotation.
+    pub max_file_size: u64,
+    pub rotation: LogRotation,
+    /// How many rotated files are kept. 0 keeps all of them.
+    pub max_files: usize,
+    /// Per module levels, such as `sqlx = "Warn"`. They replace the console and file level
+    /// for that module and everything below it.
+    pub modules: HashMap<String, ServerLevelFilter>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            console_level: ServerLevelFilter::Info,
+            console_json: false,
+            file_level: ServerLevelFilter::Info,
+            file_path: "logs/ServerLog.txt".into(),
+            file_json: true,
+            max_file_size: 10 * 1024 * 1024,
+            rotation: LogRotation::Daily,
+            max_files: 14,
+            modules: HashMap::default(),
+        }
+    }
+}
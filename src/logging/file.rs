@@ -0,0 +1,149 @@
+use super::{LogRotation, LoggingConfig};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Appends lines to the log file and rotates it once it is too large or a new
+/// hour or day starts. Rotated files get a timestamp suffix so they sort by age.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    period: String,
+    max_file_size: u64,
+    rotation: LogRotation,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    pub fn new(config: &LoggingConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.file_path),
+            file: None,
+            size: 0,
+            period: String::new(),
+            max_file_size: config.max_file_size,
+            rotation: config.rotation,
+            max_files: config.max_files,
+        }
+    }
+
+    fn period_of(rotation: LogRotation, time: DateTime<Utc>) -> String {
+        match rotation {
+            LogRotation::Never => String::new(),
+            LogRotation::Hourly => time.format("%Y%m%d%H").to_string(),
+            LogRotation::Daily => time.format("%Y%m%d").to_string(),
+        }
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let file = File::options().append(true).create(true).open(&self.path)?;
+        let metadata = file.metadata()?;
+        let modified: DateTime<Utc> = metadata
+            .modified()
+            .map(DateTime::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        self.size = metadata.len();
+        self.period = Self::period_of(self.rotation, modified);
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    fn needs_rotation(&self, next_len: u64) -> bool {
+        let too_large =
+            self.max_file_size > 0 && self.size > 0 && self.size + next_len > self.max_file_size;
+        let new_period = Self::period_of(self.rotation, Utc::now()) != self.period;
+
+        too_large || new_period
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S%.3f");
+        let rotated = PathBuf::from(format!("{}.{}", self.path.display(), stamp));
+
+        fs::rename(&self.path, rotated)?;
+        self.remove_old_files();
+
+        Ok(())
+    }
+
+    /// Deletes the oldest rotated files so only `max_files` are kept.
+    fn remove_old_files(&self) {
+        if self.max_files == 0 {
+            return;
+        }
+
+        let Some(file_name) = self.path.file_name().and_then(|name| name.to_str()) else {
+            return;
+        };
+        let prefix = format!("{}.", file_name);
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        let mut rotated: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix))
+            })
+            .collect();
+
+        if rotated.len() <= self.max_files {
+            return;
+        }
+
+        rotated.sort();
+
+        for path in &rotated[..rotated.len() - self.max_files] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+
+        if self.file.is_none() {
+            self.open()?;
+        }
+
+        if self.needs_rotation(len) {
+            self.rotate()?;
+            self.open()?;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+            file.write_all(b"\n")?;
+            self.size += len;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
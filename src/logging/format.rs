@@ -0,0 +1,74 @@
+use chrono::{SecondsFormat, Utc};
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Record,
+};
+use serde_json::{Map, Number};
+
+/// Collects the key values of a record such as `uid`, `addr`, `token` and `server`.
+struct FieldCollector(Vec<(String, serde_json::Value)>);
+
+impl<'kvs> VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(v) = value.to_i64() {
+            serde_json::Value::Number(v.into())
+        } else if let Some(v) = value.to_u64() {
+            serde_json::Value::Number(v.into())
+        } else if let Some(v) = value.to_f64().and_then(Number::from_f64) {
+            serde_json::Value::Number(v)
+        } else if let Some(v) = value.to_bool() {
+            serde_json::Value::Bool(v)
+        } else {
+            serde_json::Value::String(value.to_string())
+        };
+
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+fn fields(record: &Record) -> Vec<(String, serde_json::Value)> {
+    let mut collector = FieldCollector(Vec::new());
+    let _ = record.key_values().visit(&mut collector);
+    collector.0
+}
+
+/// One JSON object per line, with the record fields next to the message.
+pub fn format_json(record: &Record) -> String {
+    let mut map = Map::new();
+
+    map.insert(
+        "timestamp".into(),
+        Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .into(),
+    );
+    map.insert("level".into(), record.level().as_str().into());
+    map.insert("target".into(), record.target().into());
+    map.insert("message".into(), record.args().to_string().into());
+
+    for (key, value) in fields(record) {
+        map.insert(key, value);
+    }
+
+    serde_json::Value::Object(map).to_string()
+}
+
+pub fn format_text(record: &Record) -> String {
+    let mut line = format!(
+        "{} {} {} - {}",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        record.level(),
+        record.target(),
+        record.args()
+    );
+
+    for (key, value) in fields(record) {
+        match value {
+            serde_json::Value::String(value) => line.push_str(&format!(" {}={}", key, value)),
+            value => line.push_str(&format!(" {}={}", key, value)),
+        }
+    }
+
+    line
+}
@@ -0,0 +1,131 @@
+use super::{format_json, format_text, LoggingConfig, RotatingFile};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::sync::{Mutex, RwLock};
+
+struct LoggerState {
+    console_level: LevelFilter,
+    console_json: bool,
+    file_level: LevelFilter,
+    file_json: bool,
+    /// Sorted longest first so the most specific module wins.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LoggerState {
+    fn new(config: &LoggingConfig) -> Self {
+        let mut modules: Vec<(String, LevelFilter)> = config
+            .modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.parse_enum()))
+            .collect();
+
+        modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+
+        Self {
+            console_level: config.console_level.parse_enum(),
+            console_json: config.console_json,
+            file_level: config.file_level.parse_enum(),
+            file_json: config.file_json,
+            modules,
+        }
+    }
+
+    fn module_level(&self, target: &str) -> Option<LevelFilter> {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.console_level.max(self.file_level), Ord::max)
+    }
+}
+
+/// Sends records to the console and to a rotating log file, each with its own level and format.
+pub struct ServerLogger {
+    state: RwLock<Option<LoggerState>>,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+// creates a static global logger type for setting the logger
+pub static LOGGER: ServerLogger = ServerLogger {
+    state: RwLock::new(None),
+    file: Mutex::new(None),
+};
+
+impl Log for ServerLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+
+        match state.as_ref() {
+            Some(state) => {
+                let level = state
+                    .module_level(metadata.target())
+                    .unwrap_or(state.console_level.max(state.file_level));
+
+                metadata.level() <= level
+            }
+            None => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        let Some(state) = state.as_ref() else {
+            return;
+        };
+
+        let module_level = state.module_level(record.target());
+
+        if record.level() <= module_level.unwrap_or(state.console_level) {
+            if state.console_json {
+                println!("{}", format_json(record));
+            } else {
+                println!("{}", format_text(record));
+            }
+        }
+
+        if record.level() <= module_level.unwrap_or(state.file_level) {
+            let line = if state.file_json {
+                format_json(record)
+            } else {
+                format_text(record)
+            };
+
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+            if let Some(file) = file.as_mut() {
+                // Nothing sensible can be done here if the log file can not be written to.
+                let _ = file.write_line(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            file.flush();
+        }
+    }
+}
+
+/// Applies the logging settings. Safe to call again to change levels or the file at runtime.
+pub fn configure_logging(config: &LoggingConfig) {
+    let state = LoggerState::new(config);
+
+    log::set_max_level(state.max_level());
+    *LOGGER.state.write().unwrap_or_else(|e| e.into_inner()) = Some(state);
+    *LOGGER.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(RotatingFile::new(config));
+}
+
+pub fn init_logging(config: &LoggingConfig) -> Result<(), SetLoggerError> {
+    configure_logging(config);
+    log::set_logger(&LOGGER)
+}
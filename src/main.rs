@@ -1,80 +1,39 @@
-#![allow(dead_code, clippy::let_and_return)]
-#![recursion_limit = "256"]
-#![feature(let_chains, error_generic_member_access)]
-#![feature(async_closure)]
-
-mod containers;
-mod gameloop;
-mod gametypes;
-mod items;
-mod players;
-mod socket;
-mod sql;
-mod time_ext;
-
 #[allow(unused_imports)]
 use backtrace::Backtrace;
-use containers::Storage;
-use gameloop::*;
-use gametypes::*;
-use log::{error, info, Level, Metadata, Record};
-use std::{env, fs::File, io::Write, panic};
-
-use crate::containers::read_config;
-
-// used to get string input when we add a command console to control the game.
-// until then we will just not use this.
-fn read_line() -> String {
-    let mut rv = String::new();
-    std::io::stdin().read_line(&mut rv).unwrap();
-    rv.replace("\r\n", "").replace('\n', "")
-}
-
-// creates a static global logger type for setting the logger
-static MY_LOGGER: MyLogger = MyLogger(Level::Debug);
+use log::{error, info, warn};
+use std::{env, panic};
+
+use ascending_login_server::{
+    certs,
+    cli::{self, Cli, CliCommand},
+    console,
+    containers::{load_config, Profile, CONFIG_PATH},
+    logging, LoginServer,
+};
+use clap::Parser;
 
-struct MyLogger(pub Level);
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
 
-impl log::Log for MyLogger {
-    // checks if it can log these types of events.
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.0
+    match cli.command.unwrap_or(CliCommand::Serve) {
+        CliCommand::Serve => serve().await,
+        command => std::process::exit(cli::run_cli(command, cli.json).await),
     }
-
-    // This logs to a panic file. This is so we can see
-    // Errors and such if a program crashes in full render mode.
-    fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            let msg = format!("{} - {}\n", record.level(), record.args());
-            println!("{}", &msg);
-
-            let mut file = match File::options()
-                .append(true)
-                .create(true)
-                .open("ServerLog.txt")
-            {
-                Ok(v) => v,
-                Err(_) => return,
-            };
-
-            let _ = file.write(msg.as_bytes());
-        } else if self.0 == Level::Info {
-            let msg = format!("{} - {}\n", record.level(), record.args());
-            println!("{}", &msg);
-        }
-    }
-    fn flush(&self) {}
 }
 
-#[tokio::main]
-async fn main() {
-    let config = read_config("settings.toml");
+async fn serve() {
+    let config = match load_config(CONFIG_PATH) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(cli::EXIT_CONFIG);
+        }
+    };
 
     console_subscriber::init();
 
-    log::set_logger(&MY_LOGGER).unwrap();
-    // Set the Max level we accept logging to the file for.
-    log::set_max_level(config.level_filter.parse_enum());
+    logging::init_logging(&config.logging).unwrap();
 
     if config.enable_backtrace {
         env::set_var("RUST_BACKTRACE", "1");
@@ -92,12 +51,35 @@ async fn main() {
             :::::::::::::::::::::\n",
             panic_info, bt
         );
+        log::logger().flush();
     }));
 
     info!("Starting up");
+
+    if config.profile == Profile::Dev {
+        warn!("Running with the Dev profile, missing certificates are created.");
+
+        if let Err(e) = certs::ensure_dev_certs(&config) {
+            error!("Could not create development certificates: {}", e);
+            log::logger().flush();
+            std::process::exit(1);
+        }
+    }
+
     info!("Initializing Storage");
-    let mut storage = Storage::new(config).await.unwrap();
+    let server = match LoginServer::builder().config(config).build().await {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Could not start the server: {}", e);
+            log::logger().flush();
+            std::process::exit(1);
+        }
+    };
+    let handle = server.handle();
+
+    console::spawn_console(handle.console().clone());
+    console::spawn_shutdown_signals(handle.console().clone());
+    console::spawn_reload_signal(handle.console().clone());
 
-    info!("Game Server is Running.");
-    game_loop(&mut storage).await;
+    server.run().await;
 }
@@ -0,0 +1,223 @@
+use crate::{containers::Storage, gametypes::*};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use log::{error, info};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Address and port the Prometheus scrape endpoint listens on.
+    pub listen: String,
+}
+
+/// Every metric the login server exports. The metric types are reference counted
+/// so clones share the same values.
+#[derive(Clone)]
+pub struct Metrics {
+    pub registry: Registry,
+    pub registrations: IntCounter,
+    pub logins: IntCounterVec,
+    pub verifications: IntCounterVec,
+    pub kills: IntCounter,
+    pub bans: IntCounter,
+    pub clients: IntGauge,
+    pub game_servers: IntGauge,
+    pub free_tokens: IntGauge,
+    pub dropped_connections: IntCounterVec,
+    pub players_on: IntGaugeVec,
+    pub largest_send_queue: IntGaugeVec,
+    pub handler_latency: HistogramVec,
+    pub argon2_time: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("login".into()), None)?;
+
+        let registrations =
+            IntCounter::new("registrations_total", "Accounts created through the client")?;
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by outcome"),
+            &["outcome"],
+        )?;
+        let verifications = IntCounterVec::new(
+            Opts::new(
+                "verifications_total",
+                "Login tokens checked for game servers",
+            ),
+            &["result"],
+        )?;
+        let kills = IntCounter::new(
+            "kills_total",
+            "Players a game server was told to disconnect",
+        )?;
+        let bans = IntCounter::new("bans_total", "Accounts banned")?;
+        let clients = IntGauge::new("clients", "Connected clients")?;
+        let game_servers = IntGauge::new("game_servers", "Connected game servers")?;
+        let free_tokens = IntGauge::new("free_tokens", "Connection tokens left to give out")?;
+        let dropped_connections = IntCounterVec::new(
+            Opts::new(
+                "dropped_connections_total",
+                "Connections refused or closed by a connection limit",
+            ),
+            &["reason"],
+        )?;
+        let players_on = IntGaugeVec::new(
+            Opts::new("players_on", "Players reported online by each game server"),
+            &["server"],
+        )?;
+        let largest_send_queue = IntGaugeVec::new(
+            Opts::new(
+                "largest_send_queue_bytes",
+                "Bytes waiting to be sent to the most backed up connection of each kind",
+            ),
+            &["connection"],
+        )?;
+        let handler_latency = HistogramVec::new(
+            HistogramOpts::new("handler_seconds", "Time spent handling a packet"),
+            &["packet"],
+        )?;
+        let argon2_time = HistogramVec::new(
+            HistogramOpts::new(
+                "argon2_seconds",
+                "Time spent hashing or verifying passwords",
+            )
+            .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["operation"],
+        )?;
+
+        registry.register(Box::new(registrations.clone()))?;
+        registry.register(Box::new(logins.clone()))?;
+        registry.register(Box::new(verifications.clone()))?;
+        registry.register(Box::new(kills.clone()))?;
+        registry.register(Box::new(bans.clone()))?;
+        registry.register(Box::new(clients.clone()))?;
+        registry.register(Box::new(game_servers.clone()))?;
+        registry.register(Box::new(free_tokens.clone()))?;
+        registry.register(Box::new(dropped_connections.clone()))?;
+        registry.register(Box::new(players_on.clone()))?;
+        registry.register(Box::new(largest_send_queue.clone()))?;
+        registry.register(Box::new(handler_latency.clone()))?;
+        registry.register(Box::new(argon2_time.clone()))?;
+
+        Ok(Self {
+            registry,
+            registrations,
+            logins,
+            verifications,
+            kills,
+            bans,
+            clients,
+            game_servers,
+            free_tokens,
+            dropped_connections,
+            players_on,
+            largest_send_queue,
+            handler_latency,
+            argon2_time,
+        })
+    }
+
+    #[inline]
+    pub fn login(&self, outcome: &str) {
+        self.logins.with_label_values(&[outcome]).inc();
+    }
+
+    #[inline]
+    pub fn dropped_connection(&self, reason: &str) {
+        self.dropped_connections.with_label_values(&[reason]).inc();
+    }
+
+    #[inline]
+    pub fn argon2_timer(&self, operation: &str) -> prometheus::HistogramTimer {
+        self.argon2_time
+            .with_label_values(&[operation])
+            .start_timer()
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// Refreshes the gauges from the current connection state. Called from the game loop.
+pub async fn update_gauges(storage: &Storage) {
+    let metrics = &storage.metrics;
+
+    {
+        let server = storage.server.read().await;
+        metrics.clients.set(server.clients.len() as i64);
+        metrics.game_servers.set(server.game_servers.len() as i64);
+        metrics.free_tokens.set(server.tokens.len() as i64);
+
+        let mut largest = 0;
+
+        for client in server.clients.values() {
+            largest = largest.max(client.lock().await.sends.bytes());
+        }
+
+        metrics
+            .largest_send_queue
+            .with_label_values(&["client"])
+            .set(largest as i64);
+
+        largest = 0;
+
+        for game_server in server.game_servers.values() {
+            largest = largest.max(game_server.lock().await.sends.bytes());
+        }
+
+        metrics
+            .largest_send_queue
+            .with_label_values(&["game_server"])
+            .set(largest as i64);
+    }
+
+    metrics.players_on.reset();
+
+    for info in storage.servers.values() {
+        metrics
+            .players_on
+            .with_label_values(&[&info.name])
+            .set(info.players_on as i64);
+    }
+}
+
+async fn get_metrics(State(metrics): State<Metrics>) -> Response {
+    match metrics.encode() {
+        Ok(body) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response(),
+        Err(e) => {
+            error!("Failed to encode metrics: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Serves `/metrics` in the Prometheus text format until the listener fails.
+pub async fn serve_metrics(config: MetricsConfig, metrics: Metrics) -> Result<()> {
+    let addr: SocketAddr = config.listen.parse()?;
+    let listener = TcpListener::bind(addr).await?;
+    let app = Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(metrics);
+
+    info!("Metrics listening on {}", addr);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
@@ -9,3 +9,6 @@ pub const fn is_name_acceptable(n: char) -> bool {
 pub const fn is_password_acceptable(n: char) -> bool {
     matches!(n, '!' | '$' | '&' | '_' | '%' | '@' | '?' | '~' | '0'..='9' | 'A'..='Z' | 'a'..='z')
 }
+
+pub const EMAIL_PATTERN: &str =
+    r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@([a-z0-9]+([\-\.]{1}[a-z0-9]+)*\.[a-z]{2,6})";
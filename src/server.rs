@@ -0,0 +1,9 @@
+mod builder;
+mod clock;
+mod login_server;
+mod mailer;
+
+pub use builder::*;
+pub use clock::*;
+pub use login_server::*;
+pub use mailer::*;
@@ -0,0 +1,68 @@
+use super::{Clock, LoginServer, Mailer, NoMailer, SystemClock};
+use crate::{
+    containers::{load_config, Config, Storage, CONFIG_PATH},
+    gametypes::*,
+    store::{open_account_store, AccountStore},
+};
+use std::sync::Arc;
+
+/// Sets up a `LoginServer`. Anything left unset falls back to what the binary uses:
+/// `settings.toml`, the store `account_store` names, no mailer and the system clock.
+#[derive(Default)]
+pub struct LoginServerBuilder {
+    config: Option<Config>,
+    store: Option<Arc<dyn AccountStore>>,
+    mailer: Option<Arc<dyn Mailer>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl LoginServerBuilder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Keeps accounts in `store` instead of opening the one `account_store` names.
+    pub fn store(mut self, store: Arc<dyn AccountStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Opens the listeners, certificates and account store. Nothing is served until
+    /// `LoginServer::run`.
+    pub async fn build(self) -> Result<LoginServer> {
+        let config = match self.config {
+            Some(config) => {
+                let errors = config.validate();
+
+                if !errors.is_empty() {
+                    return Err(AscendingError::InvalidConfig(errors));
+                }
+
+                config
+            }
+            None => load_config(CONFIG_PATH)?,
+        };
+
+        let accounts = match self.store {
+            Some(store) => store,
+            None => open_account_store(&config).await?,
+        };
+        let mailer = self.mailer.unwrap_or_else(|| Arc::new(NoMailer));
+        let clock = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+
+        let storage = Storage::new(config, accounts, mailer, clock).await?;
+
+        Ok(LoginServer::new(storage))
+    }
+}
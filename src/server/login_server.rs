@@ -0,0 +1,115 @@
+use super::LoginServerBuilder;
+use crate::{
+    api,
+    console::{self, Command, ConsoleRequest, ConsoleSender},
+    containers::{Config, Storage},
+    gameloop::game_loop,
+    metrics,
+};
+use log::{error, info};
+
+/// A login server with its listeners open, ready to `run`. The binary is one of these
+/// with the stdin console and signal handlers added, game servers and tests can run
+/// their own in process.
+pub struct LoginServer {
+    storage: Storage,
+}
+
+impl LoginServer {
+    pub fn builder() -> LoginServerBuilder {
+        LoginServerBuilder::default()
+    }
+
+    pub(crate) fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.storage.config
+    }
+
+    /// A handle that stays usable while `run` has the server.
+    pub fn handle(&self) -> LoginServerHandle {
+        LoginServerHandle {
+            console: self.storage.console_sender.clone(),
+        }
+    }
+
+    /// Serves clients and game servers, plus the metrics endpoint and admin API when
+    /// configured, until shut down from a `LoginServerHandle` or the console.
+    pub async fn run(mut self) {
+        let storage = &mut self.storage;
+
+        if storage.config.tls.watch_certs {
+            let mut files = storage.cert_resolver.paths().to_vec();
+            files.extend(storage.config.tls.crl_file.as_deref());
+
+            if let Err(e) = console::spawn_cert_watcher(&files, storage.console_sender.clone()) {
+                error!(
+                    "Could not watch the certificate files, use `reloadcerts` instead: {}",
+                    e
+                );
+            }
+        }
+
+        let mut tasks = Vec::new();
+
+        if let Some(metrics_config) = storage.config.metrics.clone() {
+            let metrics = storage.metrics.clone();
+
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = metrics::serve_metrics(metrics_config, metrics).await {
+                    error!("Metrics endpoint stopped: {}", e);
+                }
+            }));
+        }
+
+        if let Some(api_config) = storage.config.admin_api.clone() {
+            let state = api::AdminState {
+                accounts: storage.accounts.clone(),
+                mailer: storage.mailer.clone(),
+                console: storage.console_sender.clone(),
+            };
+
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = api::serve_admin_api(api_config, state).await {
+                    error!("Admin API stopped: {}", e);
+                }
+            }));
+        }
+
+        info!("Game Server is Running.");
+        game_loop(storage).await;
+
+        // Free their ports for a server started after this one in the same process.
+        for task in tasks {
+            task.abort();
+        }
+
+        info!("Game Server has stopped.");
+    }
+}
+
+/// Controls a running `LoginServer`.
+#[derive(Clone, Debug)]
+pub struct LoginServerHandle {
+    console: ConsoleSender,
+}
+
+impl LoginServerHandle {
+    /// Where console commands are queued, as the stdin console and signal handlers do.
+    pub fn console(&self) -> &ConsoleSender {
+        &self.console
+    }
+
+    /// Asks the server to drain its connections and stop, which ends `run`. Returns
+    /// false when it has already stopped.
+    pub fn shutdown(&self) -> bool {
+        self.console
+            .send(ConsoleRequest {
+                command: Command::Shutdown,
+                reply: None,
+            })
+            .is_ok()
+    }
+}
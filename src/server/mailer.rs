@@ -0,0 +1,19 @@
+use crate::{gametypes::*, sql::AccountInfo};
+use async_trait::async_trait;
+
+/// Delivers password reset codes to players when an admin forces a reset.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send_password_reset(&self, account: &AccountInfo, code: &str) -> Result<()>;
+}
+
+/// Sends nothing. The admin API still returns the code so it can be handed out by hand.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMailer;
+
+#[async_trait]
+impl Mailer for NoMailer {
+    async fn send_password_reset(&self, _account: &AccountInfo, _code: &str) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,17 +1,31 @@
 mod buffer;
 mod client;
+mod codec;
+mod deadline;
 mod game_server;
+mod listener;
 mod packet_ids;
+mod packets;
+mod proxy;
+mod send_queue;
 mod sends;
 mod server;
+mod websocket;
 
 pub use buffer::*;
 #[allow(unused_imports)]
 pub use bytey::{ByteBuffer, ByteBufferError, ByteBufferRead, ByteBufferWrite};
 pub use client::*;
+pub use codec::*;
+pub use deadline::*;
 pub use game_server::*;
+pub use listener::*;
 #[allow(unused_imports)]
 pub use mmap_bytey::{MByteBuffer, MByteBufferError, MByteBufferRead, MByteBufferWrite};
 pub use packet_ids::*;
+pub use packets::*;
+pub use proxy::*;
+pub use send_queue::*;
 pub use sends::*;
 pub use server::*;
+pub use websocket::*;
@@ -29,6 +29,7 @@ impl MByteBufferExt for MByteBuffer {
 
     fn new_packet_with_count(packet_id: u16) -> Result<MByteBuffer> {
         let mut buffer = MByteBuffer::new()?;
+        buffer.write(0u64)?;
         buffer.write(packet_id)?;
         buffer.write(0u64)?;
         Ok(buffer)
@@ -1,23 +1,38 @@
-use crate::{client_handle_data, containers::Storage, gametypes::*, socket::*};
+use crate::{certs::fingerprint, client_handle_data, containers::Storage, gametypes::*, socket::*};
 use log::{error, trace, warn};
 use mio::{net::TcpStream, Interest};
-use mmap_bytey::BUFFER_SIZE;
-use std::{
-    collections::VecDeque,
-    io::{self, Read, Write},
-};
+use std::io::{self, Read, Write};
+
+/// How packets are framed inside the TLS stream.
+#[derive(Debug)]
+pub enum Transport {
+    /// Length prefixed packets, used by the game client.
+    Tls,
+    /// One packet per binary WebSocket message, used by browser clients.
+    WebSocket(Box<WebSocket>),
+}
 
 #[derive(Debug)]
 pub struct Client {
     pub stream: TcpStream,
     pub token: mio::Token,
     pub state: SocketState,
-    pub sends: VecDeque<MByteBuffer>,
+    pub sends: SendQueue,
     pub poll_state: SocketPollState,
     pub buffer: ByteBuffer,
     pub addr: String,
     // used for sending encrypted Data.
     pub tls: rustls::ServerConnection,
+    pub transport: Transport,
+    /// Set while the PROXY header of a trusted load balancer is still being read.
+    pub proxy: Option<ProxyReader>,
+    pub times: ConnectionTimes,
+    /// Sent in `Hello`, picks the catalog for messages to this client.
+    pub locale: String,
+    /// Agreed in `Hello`, applies to frames both ways.
+    pub compression: Compression,
+    /// Set once `Hello` matched this server's protocol. Other packets are refused until then.
+    pub hello_accepted: bool,
 }
 
 impl Client {
@@ -26,35 +41,92 @@ impl Client {
         stream: TcpStream,
         token: mio::Token,
         addr: String,
-        tls: rustls::ServerConnection,
+        mut tls: rustls::ServerConnection,
+        transport: Transport,
+        proxy: Option<ProxyReader>,
     ) -> Result<Client> {
+        // `tls_write` only moves a write budget into TLS once the last one was sent.
+        tls.set_buffer_limit(None);
+
         Ok(Client {
             stream,
             token,
             state: SocketState::Open,
-            sends: VecDeque::with_capacity(32),
+            sends: SendQueue::with_capacity(32),
             poll_state: SocketPollState::Read,
             buffer: ByteBuffer::with_capacity(16_000)?,
             tls,
+            transport,
+            proxy,
             addr,
+            times: ConnectionTimes::default(),
+            locale: String::new(),
+            compression: Compression::None,
+            hello_accepted: false,
         })
     }
 
-    pub async fn process(&mut self, event: &mio::event::Event, storage: &Storage) -> Result<()> {
+    /// SHA-256 fingerprint of the certificate the client authenticated with, if any.
+    /// Lets accounts be bound to the installations they log in from.
+    pub fn cert_fingerprint(&self) -> Option<String> {
+        self.tls
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(fingerprint)
+    }
+
+    /// The kind of listener the client connected to, which sets its packet limit.
+    pub fn listener_kind(&self) -> ListenerKind {
+        match self.transport {
+            Transport::Tls => ListenerKind::Clients,
+            Transport::WebSocket(_) => ListenerKind::WebSockets,
+        }
+    }
+
+    /// The PROXY header, TLS handshake and WebSocket upgrade are all done.
+    pub fn is_ready(&self) -> bool {
+        self.proxy.is_none()
+            && !self.tls.is_handshaking()
+            && match &self.transport {
+                Transport::Tls => true,
+                Transport::WebSocket(websocket) => websocket.is_open(),
+            }
+    }
+
+    pub async fn process(
+        &mut self,
+        event: &mio::event::Event,
+        storage: &mut Storage,
+    ) -> Result<()> {
         //We set it as None so we can fully control when to enable it again based on conditions.
         self.poll_state.set(SocketPollState::Read);
 
         // Check if the Event has some readable Data from the Poll State.
         if event.is_readable() {
-            self.tls_read().await?;
+            let codec = storage
+                .config
+                .connections
+                .codec(self.listener_kind())
+                .with_compression(self.compression);
+            self.tls_read(storage, codec).await?;
+
+            if self.times.ready.is_none() && self.is_ready() {
+                self.times.set_ready();
+            }
+
+            if self.buffer.length() > self.buffer.cursor() {
+                storage.client_ids.insert(self.token);
+            }
         }
 
         // Check if the Event has some writable Data from the Poll State.
         if event.is_writable() {
-            self.tls_write().await;
+            self.tls_write(storage.config.connections.write_budget)
+                .await;
         }
 
-        if !self.sends.is_empty() {
+        // Handshake messages and WebSocket replies are queued inside rustls.
+        if !self.sends.is_empty() || self.tls.wants_write() {
             self.poll_state.add(SocketPollState::Write);
         }
 
@@ -87,6 +159,8 @@ impl Client {
             _ => {
                 //We dont care about errors here as they only occur when a socket is already disconnected by the client.
                 self.deregister(&*storage.poll.read().await)?;
+                // Best effort flush of TLS alerts and WebSocket close frames.
+                let _ = self.tls.write_tls(&mut self.stream);
                 let _ = self.stream.shutdown(std::net::Shutdown::Both);
                 self.state = SocketState::Closed;
 
@@ -95,13 +169,25 @@ impl Client {
         }
     }
 
-    pub async fn tls_read(&mut self) -> Result<()> {
+    pub async fn tls_read(&mut self, storage: &Storage, codec: FrameCodec) -> Result<()> {
+        // Data that followed the PROXY header, it is the start of the TLS handshake.
+        let Some(early) = self.read_proxy_header(storage).await else {
+            return Ok(());
+        };
+        let mut early = early.as_slice();
+
         let pos = self.buffer.cursor();
 
         self.buffer.move_cursor_to_end();
 
         loop {
-            match self.tls.read_tls(&mut self.stream) {
+            let read = if early.is_empty() {
+                self.tls.read_tls(&mut self.stream)
+            } else {
+                self.tls.read_tls(&mut early)
+            };
+
+            match read {
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
                     break;
                 }
@@ -138,7 +224,7 @@ impl Client {
                     return Ok(());
                 }
 
-                if let Err(e) = self.buffer.write_slice(&buf) {
+                if let Err(e) = self.receive(&buf) {
                     trace!("TLS read error: {}", e);
                     self.state = SocketState::Closing;
                     return Ok(());
@@ -153,6 +239,12 @@ impl Client {
             break;
         }
 
+        if let Err(e) = codec.check(&self.buffer.as_slice()[pos..]) {
+            trace!("Bad or malformed packet from IP: {}, {}", self.addr, e);
+            self.state = SocketState::Closing;
+            return Ok(());
+        }
+
         // reset it back to the original pos so we can Read from it again.
         self.buffer.move_cursor(pos)?;
 
@@ -163,64 +255,155 @@ impl Client {
         Ok(())
     }
 
-    pub async fn tls_write(&mut self) {
-        // lets only send 25 packets per socket each loop.
-        loop {
-            let mut packet = match self.sends.pop_front() {
-                Some(packet) => packet,
-                None => {
-                    if self.sends.capacity() > 100 {
-                        warn!(
-                            "Socket TLS write: tls_sends Buffer Strink to 100, Current Capacity {}, Current len {}.",
-                            self.sends.capacity(),
-                            self.sends.len()
-                        );
-                        self.sends.shrink_to(100);
+    /// Reads the PROXY header when the client came through a trusted load balancer and
+    /// replaces `addr` with the address it reports, which is then held to `max_per_ip`.
+    /// Returns the bytes read past the header, or `None` while the header is incomplete
+    /// or the socket is closing.
+    async fn read_proxy_header(&mut self, storage: &Storage) -> Option<Vec<u8>> {
+        let Some(proxy) = &mut self.proxy else {
+            return Some(Vec::new());
+        };
+
+        match proxy.read(&mut self.stream) {
+            Ok(ProxyStatus::Pending) => None,
+            Ok(ProxyStatus::Done { source, rest }) => {
+                self.proxy = None;
+
+                if let Some(source) = source {
+                    trace!("PROXY header from {} for {}", self.addr, source);
+                    self.addr = source.to_string();
+
+                    let max_per_ip = storage.config.connections.max_per_ip;
+
+                    if !storage.server.write().await.count_proxied(
+                        self.token,
+                        source.ip(),
+                        max_per_ip,
+                    ) {
+                        trace!("PROXY source {} is at its connection limit.", source);
+                        storage.metrics.dropped_connection("max_per_ip");
+                        self.state = SocketState::Closing;
+                        return None;
                     }
-                    break;
                 }
-            };
 
-            match self.tls.writer().write_all(packet.as_slice()) {
-                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
-                    self.sends.push_front(packet);
-                    break;
+                Some(rest)
+            }
+            Ok(ProxyStatus::Invalid) => {
+                warn!(addr = self.addr; "Invalid PROXY header from {}", self.addr);
+                self.state = SocketState::Closing;
+                None
+            }
+            Err(e) => {
+                trace!("PROXY header read error: {}", e);
+                self.state = SocketState::Closing;
+                None
+            }
+        }
+    }
+
+    /// Moves decrypted data into `buffer` as length prefixed packets.
+    fn receive(&mut self, data: &[u8]) -> Result<()> {
+        match &mut self.transport {
+            Transport::Tls => {
+                self.buffer.write_slice(data)?;
+            }
+            Transport::WebSocket(websocket) => {
+                let read = websocket.read(data);
+
+                for message in read.messages {
+                    self.buffer.write(message.len() as u64)?;
+                    self.buffer.write_slice(&message)?;
                 }
-                Err(e) => {
-                    trace!("tls write, error in write_all: {}", e);
+
+                if !read.reply.is_empty() {
+                    self.tls.writer().write_all(&read.reply)?;
+                }
+
+                if read.closed {
                     self.state = SocketState::Closing;
-                    return;
                 }
-                Ok(_) => {}
             }
         }
 
-        loop {
-            if self.tls.wants_write() {
-                match self.tls.write_tls(&mut self.stream) {
-                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+        Ok(())
+    }
+
+    /// Moves up to `budget` bytes of queued packets into TLS and writes out what TLS has
+    /// ready. Nothing more is queued into TLS while the socket has not taken the last
+    /// batch, so a slow reader's packets wait in `sends` where they are counted.
+    pub async fn tls_write(&mut self, budget: usize) {
+        if !self.flush_tls() {
+            return;
+        }
+
+        if !self.tls.wants_write() {
+            let mut moved = 0;
+
+            while budget == 0 || moved < budget {
+                // Nothing is sent before the upgrade has been answered.
+                if let Transport::WebSocket(websocket) = &self.transport {
+                    if !websocket.is_open() {
                         break;
                     }
-                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {
-                        continue;
-                    }
-                    Err(e) => {
-                        trace!("tls write, error in write_tls: {}", e);
-                        self.state = SocketState::Closing;
-                        return;
-                    }
-                    Ok(_) => {}
+                }
+
+                let Some(mut packet) = self.sends.pop_front() else {
+                    break;
                 };
-            } else {
-                break;
+
+                let data = packet.as_slice();
+                moved += data.len();
+
+                let result = match &self.transport {
+                    Transport::Tls => self.tls.writer().write_all(data),
+                    // Coalesced buffers hold several packets, each is sent in its own frame
+                    // with the length prefix replaced by the frame header.
+                    Transport::WebSocket(_) => split_packets(data).try_for_each(|payload| {
+                        let mut writer = self.tls.writer();
+
+                        writer
+                            .write_all(&frame_header(OPCODE_BINARY, payload.len()))
+                            .and_then(|_| writer.write_all(payload))
+                    }),
+                };
+
+                if let Err(e) = result {
+                    trace!("tls write, error in write_all: {}", e);
+                    self.state = SocketState::Closing;
+                    return;
+                }
+            }
+
+            if !self.flush_tls() {
+                return;
             }
         }
 
-        if !self.sends.is_empty() {
+        if !self.sends.is_empty() || self.tls.wants_write() {
             self.poll_state.add(SocketPollState::Write);
         }
     }
 
+    /// Writes what TLS has ready until the socket would block. Returns false when the
+    /// connection failed and is closing.
+    fn flush_tls(&mut self) -> bool {
+        while self.tls.wants_write() {
+            match self.tls.write_tls(&mut self.stream) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    trace!("tls write, error in write_tls: {}", e);
+                    self.state = SocketState::Closing;
+                    return false;
+                }
+                Ok(_) => {}
+            }
+        }
+
+        true
+    }
+
     #[inline]
     pub fn event_set(&mut self) -> Option<Interest> {
         match self.poll_state {
@@ -249,10 +432,22 @@ impl Client {
         Ok(())
     }
 
-    #[inline]
-    pub fn send(&mut self, poll: &mio::Poll, buf: MByteBuffer) -> Result<()> {
-        self.sends.push_back(buf);
-        self.add_write_state(poll)
+    /// Queues a packet, closing the connection instead when its send queue is full.
+    pub async fn send(&mut self, storage: &Storage, buf: MByteBuffer) -> Result<()> {
+        let buf = compress_frame(buf, self.compression, &storage.config.compression)?;
+
+        if !self.sends.push(buf, &storage.config.connections) {
+            warn!(
+                addr = self.addr, token = self.token.0;
+                "Closing client {} with {} packets and {} bytes waiting to be sent",
+                self.addr, self.sends.len(), self.sends.bytes()
+            );
+            storage.metrics.dropped_connection("send_queue_full");
+            self.sends.clear();
+            return self.set_to_closing(storage).await;
+        }
+
+        self.add_write_state(&*storage.poll.read().await)
     }
 
     #[inline]
@@ -266,27 +461,6 @@ impl Client {
     }
 }
 
-pub async fn get_length(storage: &Storage, client: &mut Client) -> Result<Option<u64>> {
-    if client.buffer.length() - client.buffer.cursor() >= 8 {
-        let length = client.buffer.read::<u64>()?;
-
-        if !(1..=8192).contains(&length) {
-            trace!("Player was disconnected on get_length LENGTH: {:?}", length);
-            client.set_to_closing(storage).await?;
-            return Ok(None);
-        }
-
-        Ok(Some(length))
-    } else {
-        client.poll_state.add(SocketPollState::Read);
-        client.reregister(&*storage.poll.read().await)?;
-
-        Ok(None)
-    }
-}
-
-pub const MAX_PROCESSED_PACKETS: i32 = 25;
-
 pub async fn process_client_packets(storage: &mut Storage) -> Result<()> {
     let mut packet = MByteBuffer::new()?;
     let client_ids = storage.client_ids.clone();
@@ -296,85 +470,42 @@ pub async fn process_client_packets(storage: &mut Storage) -> Result<()> {
 
         if let Some(client) = client {
             let mut client = client.lock().await;
-            let mut count = 0;
-
-            loop {
-                packet.move_cursor_to_start();
-                let length = match get_length(storage, &mut client).await? {
-                    Some(n) => n,
-                    None => {
+            let codec = storage
+                .config
+                .connections
+                .codec(client.listener_kind())
+                .with_compression(client.compression);
+
+            for _ in 0..MAX_PROCESSED_PACKETS {
+                match codec.read_frame(&mut client.buffer, &mut packet) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        client.poll_state.add(SocketPollState::Read);
+                        client.reregister(&*storage.poll.read().await)?;
                         socket_update(storage, &mut client, false).await?;
-                        break;
-                    }
-                };
-
-                if length == 0 {
-                    trace!(
-                        "Length was Zero. Bad or malformed packet from IP: {}",
-                        client.addr
-                    );
-
-                    socket_update(storage, &mut client, true).await?;
-                    continue 'user_loop;
-                }
-
-                if length > BUFFER_SIZE as u64 {
-                    trace!(
-                        "Length was {} greater than the max packet size of {}. Bad or malformed packet from IP: {}",
-                        length,
-                        client.addr,
-                        BUFFER_SIZE
-                    );
-
-                    socket_update(storage, &mut client, true).await?;
-                    continue 'user_loop;
-                }
-
-                if length <= (client.buffer.length() - client.buffer.cursor()) as u64 {
-                    let mut errored = false;
-
-                    if let Ok(bytes) = client.buffer.read_slice(length as usize) {
-                        if packet.write_slice(bytes).is_err() {
-                            errored = true;
-                        }
-
-                        packet.move_cursor_to_start();
-                    } else {
-                        errored = true;
-                    }
-
-                    if errored {
-                        warn!(
-                            "IP: {} was disconnected due to error on packet length.",
-                            client.addr
-                        );
-                        socket_update(storage, &mut client, true).await?;
                         continue 'user_loop;
                     }
-
-                    if client_handle_data(storage, &mut packet, &mut client)
-                        .await
-                        .is_err()
-                    {
-                        warn!(
-                            "IP: {} was disconnected due to invalid packets",
-                            client.addr
-                        );
+                    Err(e) => {
+                        trace!("Bad or malformed packet from IP: {}, {}", client.addr, e);
                         socket_update(storage, &mut client, true).await?;
                         continue 'user_loop;
                     }
-
-                    count += 1
-                } else {
-                    let cursor = client.buffer.cursor() - 8;
-                    client.buffer.move_cursor(cursor)?;
-                    socket_update(storage, &mut client, false).await?;
-                    break;
                 }
 
-                if count == MAX_PROCESSED_PACKETS {
-                    break;
+                if client_handle_data(storage, &mut packet, &mut client)
+                    .await
+                    .is_err()
+                {
+                    warn!(
+                        addr = client.addr, token = client.token.0;
+                        "IP: {} was disconnected due to invalid packets",
+                        client.addr
+                    );
+                    socket_update(storage, &mut client, true).await?;
+                    continue 'user_loop;
                 }
+
+                client.times.packet_handled();
             }
         }
     }
@@ -0,0 +1,363 @@
+use crate::{containers::CompressionConfig, gametypes::*};
+use bytey::ByteBuffer;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use mmap_bytey::MByteBuffer;
+use std::io::{Read, Write};
+
+/// Size of the little endian u64 in front of every frame.
+pub const LENGTH_PREFIX: usize = 8;
+
+/// Set in the length prefix of a frame whose body is compressed. The rest of the prefix
+/// is the compressed length.
+pub const COMPRESSED_FLAG: u64 = 1 << 63;
+
+/// Packets handled from one connection each loop before moving on to the next.
+pub const MAX_PROCESSED_PACKETS: usize = 25;
+
+/// A frame found by `FrameCodec::decode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub body: &'a [u8],
+    pub compressed: bool,
+}
+
+/// Splits a byte stream into frames, a u64 length then that many bytes holding the packet
+/// ID and fields. Lengths outside `1..=max_length` are refused as soon as their prefix
+/// has arrived, before the rest of the frame is buffered. Compressed frames are only
+/// accepted once `compression` has been agreed and may not inflate past `max_length`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameCodec {
+    pub max_length: usize,
+    pub compression: Compression,
+}
+
+impl FrameCodec {
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            max_length,
+            compression: Compression::None,
+        }
+    }
+
+    pub fn with_compression(self, compression: Compression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    fn length(&self, prefix: [u8; LENGTH_PREFIX]) -> Result<(usize, bool)> {
+        let prefix = u64::from_le_bytes(prefix);
+        let compressed = prefix & COMPRESSED_FLAG != 0;
+        let length = prefix & !COMPRESSED_FLAG;
+
+        if length == 0 || length > self.max_length as u64 {
+            return Err(AscendingError::InvalidFrameLength {
+                length,
+                max: self.max_length,
+            });
+        }
+
+        if compressed && self.compression == Compression::None {
+            return Err(AscendingError::InvalidCompressedFrame {
+                max: self.max_length,
+            });
+        }
+
+        Ok((length as usize, compressed))
+    }
+
+    /// The first frame in `data`, or `None` until all of it has arrived.
+    pub fn decode<'a>(&self, data: &'a [u8]) -> Result<Option<Frame<'a>>> {
+        let Some((prefix, rest)) = data.split_first_chunk::<LENGTH_PREFIX>() else {
+            return Ok(None);
+        };
+
+        let (length, compressed) = self.length(*prefix)?;
+
+        Ok(rest.get(..length).map(|body| Frame { body, compressed }))
+    }
+
+    /// Checks the length of every frame started in `data`. Called as data arrives so a
+    /// peer announcing an oversized frame is dropped before its body is read.
+    pub fn check(&self, data: &[u8]) -> Result<()> {
+        let mut rest = data;
+
+        while let Some((prefix, body)) = rest.split_first_chunk::<LENGTH_PREFIX>() {
+            let (length, _) = self.length(*prefix)?;
+
+            match body.get(length..) {
+                Some(next) => rest = next,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inflates a compressed body, refusing one that would grow past `max_length`.
+    pub fn decompress(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let invalid = || AscendingError::InvalidCompressedFrame {
+            max: self.max_length,
+        };
+        let mut data = Vec::new();
+
+        match self.compression {
+            Compression::None => return Err(invalid()),
+            Compression::Deflate => {
+                DeflateDecoder::new(body)
+                    .take(self.max_length as u64 + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|_| invalid())?;
+            }
+        }
+
+        if data.is_empty() || data.len() > self.max_length {
+            return Err(invalid());
+        }
+
+        Ok(data)
+    }
+
+    /// Copies the body of the next whole frame in `buffer`, from its cursor, into `packet`
+    /// and moves past it. Returns false and leaves the cursor until the frame is complete.
+    pub fn read_frame(&self, buffer: &mut ByteBuffer, packet: &mut MByteBuffer) -> Result<bool> {
+        let cursor = buffer.cursor();
+
+        let length = match self.decode(&buffer.as_slice()[cursor..])? {
+            Some(frame) => {
+                packet.move_cursor_to_start();
+
+                if frame.compressed {
+                    packet.write_slice(&self.decompress(frame.body)?)?;
+                } else {
+                    packet.write_slice(frame.body)?;
+                }
+
+                packet.move_cursor_to_start();
+                frame.body.len()
+            }
+            None => return Ok(false),
+        };
+
+        buffer.move_cursor(cursor + LENGTH_PREFIX + length)?;
+
+        // Start over once everything received has been handled so the buffer stays small.
+        if buffer.cursor() == buffer.length() {
+            buffer.truncate(0)?;
+            buffer.move_cursor_to_start();
+        }
+
+        Ok(true)
+    }
+}
+
+/// Compresses the body of an encoded packet when it is over the threshold and shrinks,
+/// otherwise returns it unchanged.
+pub fn compress_frame(
+    mut buf: MByteBuffer,
+    compression: Compression,
+    config: &CompressionConfig,
+) -> Result<MByteBuffer> {
+    let body = buf.as_slice().get(LENGTH_PREFIX..).unwrap_or_default();
+
+    if body.len() <= config.threshold {
+        return Ok(buf);
+    }
+
+    let compressed = match compression {
+        Compression::None => return Ok(buf),
+        Compression::Deflate => {
+            let mut encoder =
+                DeflateEncoder::new(Vec::new(), flate2::Compression::new(config.level));
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+    };
+
+    if compressed.len() >= body.len() {
+        return Ok(buf);
+    }
+
+    let mut frame = MByteBuffer::new()?;
+
+    frame.write(compressed.len() as u64 | COMPRESSED_FLAG)?;
+    frame.write_slice(&compressed)?;
+
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut data = (body.len() as u64).to_le_bytes().to_vec();
+        data.extend_from_slice(body);
+        data
+    }
+
+    /// Decodes every whole frame in `data` and returns them with the bytes left over.
+    fn decode_all<'a>(codec: &FrameCodec, mut data: &'a [u8]) -> (Vec<&'a [u8]>, &'a [u8]) {
+        let mut frames = Vec::new();
+
+        while let Some(frame) = codec.decode(data).unwrap() {
+            data = &data[LENGTH_PREFIX + frame.body.len()..];
+            frames.push(frame.body);
+        }
+
+        (frames, data)
+    }
+
+    #[test]
+    fn decodes_a_whole_frame() {
+        let codec = FrameCodec::new(16);
+        let data = frame(&[1, 2, 3]);
+
+        let frame = codec.decode(&data).unwrap().unwrap();
+
+        assert_eq!(frame.body, [1, 2, 3]);
+        assert!(!frame.compressed);
+    }
+
+    #[test]
+    fn waits_for_truncated_frames() {
+        let codec = FrameCodec::new(16);
+        let data = frame(&[1, 2, 3, 4]);
+
+        for end in 0..data.len() {
+            assert_eq!(codec.decode(&data[..end]).unwrap(), None, "{} bytes", end);
+            codec.check(&data[..end]).unwrap();
+        }
+    }
+
+    #[test]
+    fn joins_frames_split_across_reads() {
+        let codec = FrameCodec::new(16);
+        let data = [frame(&[1, 2, 3]), frame(&[4, 5])].concat();
+
+        for split in 0..=data.len() {
+            let mut received = data[..split].to_vec();
+            let (frames, rest) = decode_all(&codec, &received);
+            let handled = received.len() - rest.len();
+            let first = frames.len();
+
+            received.drain(..handled);
+            received.extend_from_slice(&data[split..]);
+
+            let (frames, rest) = decode_all(&codec, &received);
+
+            assert_eq!(first + frames.len(), 2, "split at {}", split);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn separates_merged_frames() {
+        let codec = FrameCodec::new(16);
+        let mut data = [frame(&[1]), frame(&[2, 3]), frame(&[4, 5, 6])].concat();
+        data.extend_from_slice(&5u64.to_le_bytes()[..3]);
+
+        let (frames, rest) = decode_all(&codec, &data);
+
+        assert_eq!(frames, [&[1][..], &[2, 3][..], &[4, 5, 6][..]]);
+        assert_eq!(rest.len(), 3);
+    }
+
+    #[test]
+    fn accepts_the_largest_frame() {
+        let codec = FrameCodec::new(4);
+
+        assert!(codec.decode(&frame(&[0; 4])).unwrap().is_some());
+    }
+
+    #[test]
+    fn rejects_oversized_frames_from_the_prefix() {
+        let codec = FrameCodec::new(4);
+        let prefix = 5u64.to_le_bytes();
+
+        assert!(matches!(
+            codec.decode(&prefix),
+            Err(AscendingError::InvalidFrameLength { length: 5, max: 4 })
+        ));
+        assert!(codec.check(&prefix).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_frames_after_merged_ones() {
+        let codec = FrameCodec::new(4);
+        let data = [frame(&[1, 2]), u64::MAX.to_le_bytes().to_vec()].concat();
+
+        assert!(codec.decode(&data).unwrap().is_some());
+        assert!(matches!(
+            codec.check(&data),
+            Err(AscendingError::InvalidFrameLength { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_frames() {
+        let codec = FrameCodec::new(4);
+
+        assert!(matches!(
+            codec.decode(&0u64.to_le_bytes()),
+            Err(AscendingError::InvalidFrameLength { length: 0, .. })
+        ));
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn compressed_frame(body: &[u8]) -> Vec<u8> {
+        let mut data = (body.len() as u64 | COMPRESSED_FLAG).to_le_bytes().to_vec();
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn inflates_compressed_frames() {
+        let codec = FrameCodec::new(64).with_compression(Compression::Deflate);
+        let packet = [7; 64];
+        let data = compressed_frame(&deflate(&packet));
+
+        let frame = codec.decode(&data).unwrap().unwrap();
+
+        assert!(frame.compressed);
+        assert_eq!(codec.decompress(frame.body).unwrap(), packet);
+    }
+
+    #[test]
+    fn rejects_compression_that_was_not_agreed() {
+        let codec = FrameCodec::new(64);
+        let data = compressed_frame(&deflate(&[7; 16]));
+
+        assert!(matches!(
+            codec.check(&data),
+            Err(AscendingError::InvalidCompressedFrame { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_frames_that_inflate_past_the_limit() {
+        let codec = FrameCodec::new(4096).with_compression(Compression::Deflate);
+        let bomb = deflate(&[0; 1 << 20]);
+        let data = compressed_frame(&bomb);
+
+        let frame = codec.decode(&data).unwrap().unwrap();
+
+        assert!(matches!(
+            codec.decompress(frame.body),
+            Err(AscendingError::InvalidCompressedFrame { max: 4096 })
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_compressed_frames() {
+        let codec = FrameCodec::new(64).with_compression(Compression::Deflate);
+
+        assert!(codec.decompress(&[0xFF; 16]).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    containers::{ConnectionConfig, Storage},
+    gametypes::*,
+    socket::SocketState,
+};
+use log::info;
+use std::time::{Duration, Instant};
+
+/// The timeout a connection ran out of.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Deadline {
+    Handshake,
+    FirstPacket,
+    Idle,
+}
+
+impl Deadline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Deadline::Handshake => "handshake_timeout",
+            Deadline::FirstPacket => "first_packet_timeout",
+            Deadline::Idle => "idle_timeout",
+        }
+    }
+}
+
+/// When a connection reached each stage, checked against `ConnectionConfig`.
+#[derive(Copy, Clone, Debug)]
+pub struct ConnectionTimes {
+    pub connected: Instant,
+    /// The handshake finished and packets may be sent.
+    pub ready: Option<Instant>,
+    /// A complete packet was last handled.
+    pub last_packet: Option<Instant>,
+}
+
+impl Default for ConnectionTimes {
+    fn default() -> Self {
+        Self {
+            connected: Instant::now(),
+            ready: None,
+            last_packet: None,
+        }
+    }
+}
+
+impl ConnectionTimes {
+    #[inline]
+    pub fn set_ready(&mut self) {
+        self.ready.get_or_insert_with(Instant::now);
+    }
+
+    #[inline]
+    pub fn packet_handled(&mut self) {
+        self.last_packet = Some(Instant::now());
+    }
+
+    /// The deadline that has passed at `now`, if any. `idle` is false for connections
+    /// that may stay quiet, such as game servers.
+    pub fn expired(&self, limits: &ConnectionConfig, now: Instant, idle: bool) -> Option<Deadline> {
+        let passed = |since: Instant, seconds: u64| {
+            seconds > 0 && now.saturating_duration_since(since) >= Duration::from_secs(seconds)
+        };
+
+        match (self.ready, self.last_packet) {
+            (None, _) if passed(self.connected, limits.handshake_timeout) => {
+                Some(Deadline::Handshake)
+            }
+            (Some(ready), None) if passed(ready, limits.first_packet_timeout) => {
+                Some(Deadline::FirstPacket)
+            }
+            (Some(_), Some(last)) if idle && passed(last, limits.idle_timeout) => {
+                Some(Deadline::Idle)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Closes the clients and game servers that ran past a deadline and returns their tokens
+/// to the bag. Called from the game loop.
+pub async fn close_expired_connections(storage: &mut Storage) -> Result<()> {
+    let limits = storage.config.connections.clone();
+    let now = Instant::now();
+    let mut expired = Vec::new();
+
+    let (clients, game_servers): (Vec<_>, Vec<_>) = {
+        let server = storage.server.read().await;
+        (
+            server.clients.values().cloned().collect(),
+            server.game_servers.values().cloned().collect(),
+        )
+    };
+
+    for client in clients {
+        let mut client = client.lock().await;
+
+        if client.state != SocketState::Open {
+            continue;
+        }
+
+        if let Some(deadline) = client.times.expired(&limits, now, true) {
+            info!(
+                addr = client.addr, token = client.token.0;
+                "Closing client {} after its {}", client.addr, deadline.as_str()
+            );
+            storage.metrics.dropped_connection(deadline.as_str());
+            storage.client_ids.swap_remove(&client.token);
+            client.close_socket(storage).await?;
+            expired.push(client.token);
+        }
+    }
+
+    for game_server in game_servers {
+        let mut game_server = game_server.lock().await;
+
+        if game_server.state != SocketState::Open {
+            continue;
+        }
+
+        if let Some(deadline) = game_server.times.expired(&limits, now, false) {
+            info!(
+                addr = game_server.addr, token = game_server.token.0;
+                "Closing game server {} after its {}", game_server.addr, deadline.as_str()
+            );
+            storage.metrics.dropped_connection(deadline.as_str());
+            storage.servers_ids.swap_remove(&game_server.token);
+            game_server.close_socket(storage).await?;
+            storage.remove_game_server_info(game_server.token);
+            expired.push(game_server.token);
+        }
+    }
+
+    let mut server = storage.server.write().await;
+
+    for token in expired {
+        server.remove(token);
+    }
+
+    Ok(())
+}
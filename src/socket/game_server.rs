@@ -1,11 +1,7 @@
 use crate::{containers::Storage, game_server_handle_data, gametypes::*, socket::*};
 use log::{trace, warn};
 use mio::{net::TcpStream, Interest};
-use mmap_bytey::BUFFER_SIZE;
-use std::{
-    collections::VecDeque,
-    io::{self, Read, Write},
-};
+use std::io::{self, Read, Write};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SocketState {
@@ -75,24 +71,47 @@ pub struct GameServer {
     pub stream: TcpStream,
     pub token: mio::Token,
     pub state: SocketState,
-    pub sends: VecDeque<MByteBuffer>,
+    pub sends: SendQueue,
     pub poll_state: SocketPollState,
     pub buffer: ByteBuffer,
     pub addr: String,
+    /// Set while the PROXY header of a trusted load balancer is still being read.
+    pub proxy: Option<ProxyReader>,
+    pub times: ConnectionTimes,
+    /// Agreed in `Hello`, applies to frames both ways.
+    pub compression: Compression,
+    /// Set once `Hello` matched this server's protocol. Other packets are refused until then.
+    pub hello_accepted: bool,
 }
 
 impl GameServer {
     #[inline]
-    pub fn new(stream: TcpStream, token: mio::Token, addr: String) -> Result<GameServer> {
-        Ok(GameServer {
+    pub fn new(
+        stream: TcpStream,
+        token: mio::Token,
+        addr: String,
+        proxy: Option<ProxyReader>,
+    ) -> Result<GameServer> {
+        let mut game_server = GameServer {
             stream,
             token,
             state: SocketState::Open,
-            sends: VecDeque::with_capacity(32),
+            sends: SendQueue::with_capacity(32),
             poll_state: SocketPollState::Read,
             buffer: ByteBuffer::with_capacity(16_000)?,
             addr,
-        })
+            proxy,
+            times: ConnectionTimes::default(),
+            compression: Compression::None,
+            hello_accepted: false,
+        };
+
+        // Game servers have no handshake beyond a PROXY header.
+        if game_server.proxy.is_none() {
+            game_server.times.set_ready();
+        }
+
+        Ok(game_server)
     }
 
     pub async fn process(
@@ -106,11 +125,15 @@ impl GameServer {
         // Check if the Event has some readable Data from the Poll State.
         if event.is_readable() {
             self.read(storage).await?;
+
+            if self.proxy.is_none() {
+                self.times.set_ready();
+            }
         }
 
         // Check if the Event has some writable Data from the Poll State.
         if event.is_writable() {
-            self.write().await;
+            self.write(storage.config.connections.write_budget).await;
         }
 
         if !self.sends.is_empty() {
@@ -162,6 +185,46 @@ impl GameServer {
 
         let mut buf: [u8; 4096] = [0; 4096];
         let mut closing = false;
+        let codec = storage
+            .config
+            .connections
+            .codec(ListenerKind::GameServers)
+            .with_compression(self.compression);
+
+        if let Some(proxy) = &mut self.proxy {
+            match proxy.read(&mut self.stream) {
+                Ok(ProxyStatus::Pending) => {
+                    self.buffer.move_cursor(pos)?;
+                    return Ok(());
+                }
+                Ok(ProxyStatus::Done { source, rest }) => {
+                    if let Some(source) = source {
+                        trace!("PROXY header from {} for {}", self.addr, source);
+                        self.addr = source.to_string();
+                    }
+
+                    self.proxy = None;
+
+                    if let Err(e) = self.buffer.write_slice(&rest) {
+                        trace!("buffer.write_slice, error in socket read: {}", e);
+                        closing = true;
+                    }
+                }
+                Ok(ProxyStatus::Invalid) => {
+                    warn!(addr = self.addr; "Invalid PROXY header from {}", self.addr);
+                    closing = true;
+                }
+                Err(e) => {
+                    trace!("PROXY header read error: {}", e);
+                    closing = true;
+                }
+            }
+
+            if closing {
+                self.state = SocketState::Closing;
+                return Ok(());
+            }
+        }
 
         loop {
             match self.stream.read(&mut buf) {
@@ -176,6 +239,9 @@ impl GameServer {
                     if let Err(e) = self.buffer.write_slice(&buf[0..n]) {
                         trace!("buffer.write_slice, error in socket read: {}", e);
                         closing = true;
+                    } else if let Err(e) = codec.check(&self.buffer.as_slice()[pos..]) {
+                        trace!("Bad or malformed packet from IP: {}, {}", self.addr, e);
+                        closing = true;
                     }
                 }
             }
@@ -201,36 +267,30 @@ impl GameServer {
         Ok(())
     }
 
-    pub async fn write(&mut self) {
-        let mut count: usize = 0;
-
-        //info!("Player sends count: {}", self.sends.len());
-        // lets only send 25 packets per socket each loop.
-        while count < 25 {
-            let mut packet = match self.sends.pop_front() {
-                Some(packet) => packet,
-                None => {
-                    if self.sends.capacity() > 100 && self.sends.len() < 50 {
-                        warn!(
-                            "Socket write: sends Buffer Strink to 100, Current Capacity {}, Current len {}.",
-                            self.sends.capacity(),
-                            self.sends.len()
-                        );
-                        self.sends.shrink_to(100);
-                    }
-                    return;
-                }
+    /// Writes up to `budget` bytes of queued packets, keeping the place in a packet the
+    /// socket only took part of.
+    pub async fn write(&mut self, budget: usize) {
+        let mut written = 0;
+
+        while budget == 0 || written < budget {
+            let Some(data) = self.sends.unwritten() else {
+                break;
             };
 
-            match self.stream.write_all(packet.as_slice()) {
-                Ok(()) => count += 1,
-                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
-                    //Operation would block so we insert it back in to try again later.
-                    self.sends.push_front(packet);
-                    break;
+            match self.stream.write(data) {
+                Ok(0) => {
+                    trace!("stream.write wrote nothing in socket write");
+                    self.state = SocketState::Closing;
+                    return;
                 }
+                Ok(count) => {
+                    self.sends.advance(count);
+                    written += count;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
                 Err(e) => {
-                    trace!("stream.write_all error in socket write: {}", e);
+                    trace!("stream.write error in socket write: {}", e);
                     self.state = SocketState::Closing;
                     return;
                 }
@@ -270,10 +330,22 @@ impl GameServer {
         Ok(())
     }
 
-    #[inline]
-    pub fn send(&mut self, poll: &mio::Poll, buf: MByteBuffer) -> Result<()> {
-        self.sends.push_back(buf);
-        self.add_write_state(poll)
+    /// Queues a packet, closing the connection instead when its send queue is full.
+    pub async fn send(&mut self, storage: &Storage, buf: MByteBuffer) -> Result<()> {
+        let buf = compress_frame(buf, self.compression, &storage.config.compression)?;
+
+        if !self.sends.push(buf, &storage.config.connections) {
+            warn!(
+                addr = self.addr, token = self.token.0;
+                "Closing game server {} with {} packets and {} bytes waiting to be sent",
+                self.addr, self.sends.len(), self.sends.bytes()
+            );
+            storage.metrics.dropped_connection("send_queue_full");
+            self.sends.clear();
+            return self.set_to_closing(storage).await;
+        }
+
+        self.add_write_state(&*storage.poll.read().await)
     }
 
     #[inline]
@@ -294,121 +366,58 @@ pub async fn send_to_client(
     buf: MByteBuffer,
 ) -> Result<()> {
     if let Some(client) = storage.server.read().await.clients.get(&token) {
-        client.lock().await.send(&*storage.poll.read().await, buf)
+        client.lock().await.send(storage, buf).await
     } else {
         Ok(())
     }
 }
 
-pub async fn get_length(storage: &Storage, game_server: &mut GameServer) -> Result<Option<u64>> {
-    if game_server.buffer.length() - game_server.buffer.cursor() >= 8 {
-        let length = game_server.buffer.read::<u64>()?;
-
-        if !(1..=8192).contains(&length) {
-            trace!("Player was disconnected on get_length LENGTH: {:?}", length);
-            game_server.set_to_closing(storage).await?;
-            return Ok(None);
-        }
-
-        Ok(Some(length))
-    } else {
-        game_server.poll_state.add(SocketPollState::Read);
-        game_server.reregister(&*storage.poll.read().await)?;
-
-        Ok(None)
-    }
-}
-
-pub const MAX_PROCESSED_PACKETS: i32 = 25;
-
 pub async fn process_packets(storage: &mut Storage) -> Result<()> {
     let mut packet = MByteBuffer::new()?;
     let server_ids = storage.servers_ids.clone();
+    let limits = storage.config.connections.codec(ListenerKind::GameServers);
 
     'user_loop: for token in &server_ids {
         let game_server = storage.server.read().await.game_servers.get(token).cloned();
 
         if let Some(game_server) = game_server {
             let mut game_server = game_server.lock().await;
-            let mut count = 0;
-
-            loop {
-                packet.move_cursor_to_start();
-                let length = match get_length(storage, &mut game_server).await? {
-                    Some(n) => n,
-                    None => {
+            let codec = limits.with_compression(game_server.compression);
+
+            for _ in 0..MAX_PROCESSED_PACKETS {
+                match codec.read_frame(&mut game_server.buffer, &mut packet) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        game_server.poll_state.add(SocketPollState::Read);
+                        game_server.reregister(&*storage.poll.read().await)?;
                         socket_update(storage, &mut game_server, false).await?;
-                        break;
-                    }
-                };
-
-                if length == 0 {
-                    trace!(
-                        "Length was Zero. Bad or malformed packet from IP: {}",
-                        game_server.addr
-                    );
-
-                    socket_update(storage, &mut game_server, true).await?;
-                    continue 'user_loop;
-                }
-
-                if length > BUFFER_SIZE as u64 {
-                    trace!(
-                        "Length was {} greater than the max packet size of {}. Bad or malformed packet from IP: {}",
-                        length,
-                        game_server.addr,
-                        BUFFER_SIZE
-                    );
-
-                    socket_update(storage, &mut game_server, true).await?;
-                    continue 'user_loop;
-                }
-
-                if length <= (game_server.buffer.length() - game_server.buffer.cursor()) as u64 {
-                    let mut errored = false;
-
-                    if let Ok(bytes) = game_server.buffer.read_slice(length as usize) {
-                        if packet.write_slice(bytes).is_err() {
-                            errored = true;
-                        }
-
-                        packet.move_cursor_to_start();
-                    } else {
-                        errored = true;
-                    }
-
-                    if errored {
-                        warn!(
-                            "IP: {} was disconnected due to error on packet length.",
-                            game_server.addr
-                        );
-                        socket_update(storage, &mut game_server, true).await?;
                         continue 'user_loop;
                     }
-
-                    if game_server_handle_data(storage, &mut packet, &mut game_server)
-                        .await
-                        .is_err()
-                    {
-                        warn!(
-                            "IP: {} was disconnected due to invalid packets",
-                            game_server.addr
+                    Err(e) => {
+                        trace!(
+                            "Bad or malformed packet from IP: {}, {}",
+                            game_server.addr,
+                            e
                         );
                         socket_update(storage, &mut game_server, true).await?;
                         continue 'user_loop;
                     }
-
-                    count += 1
-                } else {
-                    let cursor = game_server.buffer.cursor() - 8;
-                    game_server.buffer.move_cursor(cursor)?;
-                    socket_update(storage, &mut game_server, false).await?;
-                    break;
                 }
 
-                if count == MAX_PROCESSED_PACKETS {
-                    break;
+                if game_server_handle_data(storage, &mut packet, &mut game_server)
+                    .await
+                    .is_err()
+                {
+                    warn!(
+                        addr = game_server.addr, token = game_server.token.0;
+                        "IP: {} was disconnected due to invalid packets",
+                        game_server.addr
+                    );
+                    socket_update(storage, &mut game_server, true).await?;
+                    continue 'user_loop;
                 }
+
+                game_server.times.packet_handled();
             }
         }
     }
@@ -0,0 +1,71 @@
+use crate::gametypes::*;
+use mio::net::TcpListener;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+const BACKLOG: i32 = 1024;
+
+/// Who connects to a listener, which decides how its connections are handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ListenerKind {
+    Clients,
+    WebSockets,
+    GameServers,
+}
+
+impl fmt::Display for ListenerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ListenerKind::Clients => "clients",
+            ListenerKind::WebSockets => "browser clients",
+            ListenerKind::GameServers => "game servers",
+        })
+    }
+}
+
+/// One bound address. Its index in `Server::listeners` is its mio token.
+pub struct Listener {
+    pub kind: ListenerKind,
+    pub addr: SocketAddr,
+    pub socket: TcpListener,
+}
+
+impl Listener {
+    /// Binds `addr`. With `only_v6` an IPv6 socket leaves IPv4 to a separate listener on
+    /// the same port, otherwise `[::]` accepts both whatever the system default is.
+    pub fn bind(kind: ListenerKind, addr: SocketAddr, only_v6: bool) -> Result<Self> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+        if addr.is_ipv6() {
+            socket.set_only_v6(only_v6)?;
+        }
+
+        // Matches mio, so a restarted server does not wait for old connections to time out.
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(BACKLOG)?;
+
+        Ok(Self {
+            kind,
+            addr,
+            socket: TcpListener::from_std(socket.into()),
+        })
+    }
+}
+
+/// Loopback, private and link local ranges, where the game server port belongs.
+pub fn is_private_address(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // Unique local fc00::/7 and link local fe80::/10.
+            ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+    }
+}
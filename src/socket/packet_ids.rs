@@ -22,6 +22,8 @@ pub enum ServerToClientPackets {
     FltAlert,
     ServerList,
     Login,
+    Shutdown,
+    Hello,
 }
 
 #[derive(
@@ -42,6 +44,8 @@ pub enum ServerToServerPackets {
     OnlineCheck,
     Verification,
     KillClient,
+    Shutdown,
+    Hello,
 }
 
 #[derive(
@@ -64,6 +68,7 @@ pub enum ClientPacket {
     Login,
     PasswordReset,
     RequestServers,
+    Hello,
 }
 
 #[derive(
@@ -85,4 +90,5 @@ pub enum GameServerPacket {
     Verification,
     UpdateInfo,
     UpdateCount,
+    Hello,
 }
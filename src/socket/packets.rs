@@ -0,0 +1,181 @@
+pub mod from_client;
+pub mod from_game_server;
+pub mod to_client;
+pub mod to_game_server;
+
+mod schema;
+
+pub use schema::*;
+
+use crate::{gametypes::*, socket::MByteBufferExt};
+use mmap_bytey::{MByteBuffer, MByteBufferRead, MByteBufferWrite};
+use std::fmt::Debug;
+
+/// A packet body and the ID written in front of it. Each struct is named after its ID
+/// and its fields are read and written in declaration order.
+pub trait Packet: Sized {
+    type Id: MByteBufferWrite + Copy + Debug;
+
+    const ID: Self::Id;
+
+    /// Rejects strings longer than the protocol allows.
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the body of a packet whose ID has already been read.
+pub fn decode<P: Packet + MByteBufferRead>(data: &mut MByteBuffer) -> Result<P> {
+    let packet = data.read::<P>()?;
+    packet.check()?;
+    Ok(packet)
+}
+
+/// Writes a length prefixed packet ready to be sent.
+pub fn encode<P: Packet + MByteBufferWrite>(packet: P) -> Result<MByteBuffer> {
+    packet.check()?;
+
+    let mut buf = MByteBuffer::new_packet()?;
+
+    buf.write(P::ID)?;
+    buf.write(packet)?;
+    buf.finish()?;
+
+    Ok(buf)
+}
+
+pub fn check_length(field: &'static str, value: &str, max: usize) -> Result<()> {
+    if value.len() > max {
+        return Err(AscendingError::FieldTooLong { field, max });
+    }
+
+    Ok(())
+}
+
+/// Defines the packet structs of one direction, named after the variants of `$id`, and
+/// implements `Packet` for them. A string field may end with `[max = N]`, its longest
+/// length in bytes. The same definitions make up this direction's part of the schema.
+macro_rules! packets {
+    (
+        $id:ident;
+        $(
+            $(#[$meta:meta])*
+            pub struct $packet:ident {
+                $(
+                    $(#[$field_meta:meta])*
+                    pub $field:ident: $ty:ty $([max = $max:expr])?
+                ),* $(,)?
+            }
+        )*
+    ) => {
+        $(
+            $(#[$meta])*
+            pub struct $packet {
+                $(
+                    $(#[$field_meta])*
+                    pub $field: $ty,
+                )*
+            }
+
+            impl $crate::socket::Packet for $packet {
+                type Id = $id;
+
+                const ID: $id = $id::$packet;
+
+                fn check(&self) -> $crate::gametypes::Result<()> {
+                    $($(
+                        $crate::socket::check_length(
+                            concat!(stringify!($packet), ".", stringify!($field)),
+                            &self.$field,
+                            $max,
+                        )?;
+                    )?)*
+                    Ok(())
+                }
+            }
+        )*
+
+        /// This direction's packets as described in the protocol schema.
+        pub fn schema() -> $crate::socket::DirectionSchema {
+            $crate::socket::DirectionSchema {
+                id_type: stringify!($id),
+                packets: vec![$(
+                    $crate::socket::PacketSchema {
+                        id: $id::$packet as u16,
+                        name: stringify!($packet),
+                        fields: vec![$(
+                            $crate::socket::FieldSchema {
+                                name: stringify!($field),
+                                ty: stringify!($ty),
+                                max_length: None $(.or(Some($max)))?,
+                            },
+                        )*],
+                    },
+                )*],
+            }
+        }
+
+        /// Every ID has a struct, checked when the list above is compiled.
+        #[allow(dead_code)]
+        fn covers_every_id(id: $id) {
+            match id {
+                $($id::$packet => {})*
+            }
+        }
+    };
+}
+
+pub(crate) use packets;
+
+/// Defines a struct carried inside packets, such as the entries of a list, in the form
+/// `packets!` takes, along with `schema()`, its fields as described in the schema.
+macro_rules! packet_struct {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                pub $field:ident: $ty:ty $([max = $max:expr])?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            $(
+                $(#[$field_meta])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl $name {
+            /// This struct's fields as described in the protocol schema.
+            pub fn schema() -> Vec<$crate::socket::FieldSchema> {
+                vec![$(
+                    $crate::socket::FieldSchema {
+                        name: stringify!($field),
+                        ty: stringify!($ty),
+                        max_length: None $(.or(Some($max)))?,
+                    },
+                )*]
+            }
+        }
+    };
+}
+
+pub(crate) use packet_struct;
+
+/// Longest account name, email or password accepted on the wire. The handlers apply
+/// their own, shorter rules with a message for the player.
+pub const MAX_CREDENTIAL_LENGTH: usize = 256;
+/// Longest game server name.
+pub const MAX_SERVER_NAME_LENGTH: usize = 64;
+/// Longest login token or reconnect code.
+pub const MAX_TOKEN_LENGTH: usize = 2048;
+/// Longest game server address, an IP or a DNS name.
+pub const MAX_ADDRESS_LENGTH: usize = 255;
+/// Longest message shown to a player.
+pub const MAX_MESSAGE_LENGTH: usize = 1024;
+/// Longest locale name, such as `zh-Hant-TW`.
+pub const MAX_LOCALE_LENGTH: usize = 35;
+/// Length of a hex encoded protocol hash.
+pub const PROTOCOL_HASH_LENGTH: usize = 64;
@@ -0,0 +1,55 @@
+use super::*;
+use crate::socket::ClientPacket;
+use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
+
+packets! {
+    ClientPacket;
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct OnlineCheck {}
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Register {
+        pub username: String [max = MAX_CREDENTIAL_LENGTH],
+        pub password: String [max = MAX_CREDENTIAL_LENGTH],
+        pub email: String [max = MAX_CREDENTIAL_LENGTH],
+        pub sprite_id: u8,
+        pub app_major: u16,
+        pub app_minor: u16,
+        pub app_revision: u16,
+        pub server_name: String [max = MAX_SERVER_NAME_LENGTH],
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Login {
+        pub email: String [max = MAX_CREDENTIAL_LENGTH],
+        pub password: String [max = MAX_CREDENTIAL_LENGTH],
+        pub app_major: u16,
+        pub app_minor: u16,
+        pub app_revision: u16,
+        pub reconnect_code: String [max = MAX_TOKEN_LENGTH],
+        pub server_name: String [max = MAX_SERVER_NAME_LENGTH],
+    }
+
+    /// Completes a reset forced by an admin, with the code they or the mailer handed out.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct PasswordReset {
+        pub email: String [max = MAX_CREDENTIAL_LENGTH],
+        pub reset_code: String [max = MAX_TOKEN_LENGTH],
+        pub password: String [max = MAX_CREDENTIAL_LENGTH],
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct RequestServers {}
+
+    /// Sent first so a client built against another protocol is told before it logs in.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Hello {
+        pub protocol_version: u16,
+        pub protocol_hash: String [max = PROTOCOL_HASH_LENGTH],
+        /// Language for messages, such as `de` or `pt-BR`. Empty uses the default locale.
+        pub locale: String [max = MAX_LOCALE_LENGTH],
+        /// Compression the client can read and would like used.
+        pub compression: Compression,
+    }
+}
@@ -0,0 +1,41 @@
+use super::*;
+use crate::socket::GameServerPacket;
+use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
+
+packets! {
+    GameServerPacket;
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct OnlineCheck {}
+
+    /// A login token a client presented to the game server.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Verification {
+        pub token: String [max = MAX_TOKEN_LENGTH],
+    }
+
+    /// Adds the game server to the server list, or renames it.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct UpdateInfo {
+        pub name: String [max = MAX_SERVER_NAME_LENGTH],
+        pub ip: String [max = MAX_ADDRESS_LENGTH],
+        pub port: u16,
+        pub players_on: u64,
+        pub max_players: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct UpdateCount {
+        pub players_on: u64,
+        pub max_players: u64,
+    }
+
+    /// Sent first so a game server built against another protocol is refused.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Hello {
+        pub protocol_version: u16,
+        pub protocol_hash: String [max = PROTOCOL_HASH_LENGTH],
+        /// Compression the game server can read and would like used.
+        pub compression: Compression,
+    }
+}
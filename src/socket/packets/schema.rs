@@ -0,0 +1,159 @@
+use super::*;
+use crate::containers::GameServerInfo;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, sync::OnceLock};
+
+/// Raised by hand for changes the packet definitions do not show, such as how a type is
+/// encoded. Field, type, limit and ID changes already change the hash.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// How each kind of value is written.
+const ENCODING: [(&str, &str); 8] = [
+    (
+        "packet",
+        "u64 length of everything after it, the u16 packet ID, then the fields in order",
+    ),
+    (
+        "compression",
+        "once agreed in Hello, a packet over the threshold may have the top bit of its \
+         length set, the rest being the length of its ID and fields compressed as a whole",
+    ),
+    ("integers", "little endian, of the size in their name"),
+    ("bool", "one byte, 0 or 1"),
+    ("String", "u64 byte length then that many bytes of UTF-8"),
+    ("Vec<T>", "u64 count then that many T"),
+    ("enums", "the u16 index of the variant, as listed"),
+    ("structs", "their fields in order"),
+];
+
+/// Variants of the enums used in packet fields, in index order.
+fn enums() -> BTreeMap<&'static str, Vec<String>> {
+    fn variants<T: std::fmt::Debug>(all: &[T]) -> Vec<String> {
+        all.iter().map(|variant| format!("{:?}", variant)).collect()
+    }
+
+    BTreeMap::from([
+        ("FtlType", variants(&FtlType::ALL)),
+        ("Compression", variants(&Compression::ALL)),
+        ("ErrorCode", variants(&ErrorCode::ALL)),
+    ])
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub ty: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PacketSchema {
+    pub id: u16,
+    pub name: &'static str,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DirectionSchema {
+    pub id_type: &'static str,
+    pub packets: Vec<PacketSchema>,
+}
+
+/// Every packet ID, field, type and limit, for clients and tools that speak the protocol.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProtocolSchema {
+    pub version: u16,
+    /// SHA-256 of the rest of the schema, exchanged in the `Hello` packets.
+    pub hash: String,
+    pub encoding: BTreeMap<&'static str, &'static str>,
+    pub enums: BTreeMap<&'static str, Vec<String>>,
+    pub structs: BTreeMap<&'static str, Vec<FieldSchema>>,
+    pub from_client: DirectionSchema,
+    pub from_game_server: DirectionSchema,
+    pub to_client: DirectionSchema,
+    pub to_game_server: DirectionSchema,
+}
+
+impl ProtocolSchema {
+    pub fn new() -> Self {
+        let mut schema = Self {
+            version: PROTOCOL_VERSION,
+            hash: String::new(),
+            encoding: ENCODING.into_iter().collect(),
+            enums: enums(),
+            structs: BTreeMap::from([("GameServerInfo", GameServerInfo::schema())]),
+            from_client: from_client::schema(),
+            from_game_server: from_game_server::schema(),
+            to_client: to_client::schema(),
+            to_game_server: to_game_server::schema(),
+        };
+
+        let json = serde_json::to_vec(&schema).expect("the protocol schema should serialize");
+
+        schema.hash = Sha256::digest(json)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        schema
+    }
+}
+
+impl Default for ProtocolSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The hash of this build's protocol, computed once.
+pub fn protocol_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+
+    HASH.get_or_init(|| ProtocolSchema::new().hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The index each variant is encoded as.
+    fn encoded_index<T: MByteBufferWrite>(variant: T) -> u16 {
+        let mut buf = MByteBuffer::new().unwrap();
+        buf.write(variant).unwrap();
+        buf.move_cursor_to_start();
+        buf.read::<u16>().unwrap()
+    }
+
+    #[test]
+    fn lists_enum_variants_in_encoding_order() {
+        let schema = ProtocolSchema::new();
+
+        for (i, variant) in FtlType::ALL.into_iter().enumerate() {
+            assert_eq!(encoded_index(variant) as usize, i);
+        }
+
+        for (i, variant) in Compression::ALL.into_iter().enumerate() {
+            assert_eq!(encoded_index(variant) as usize, i);
+        }
+
+        for (i, variant) in ErrorCode::ALL.into_iter().enumerate() {
+            assert_eq!(encoded_index(variant) as usize, i);
+        }
+
+        assert_eq!(schema.enums["ErrorCode"][1], "Outdated");
+        assert_eq!(schema.enums["Compression"], ["None", "Deflate"]);
+    }
+
+    #[test]
+    fn describes_structs_inside_packets() {
+        let schema = ProtocolSchema::new();
+        let fields = &schema.structs["GameServerInfo"];
+
+        assert_eq!(fields[0].name, "name");
+        assert_eq!(fields[0].ty, "String");
+        assert_eq!(fields[0].max_length, Some(MAX_SERVER_NAME_LENGTH));
+        assert_eq!(fields.len(), 5);
+    }
+}
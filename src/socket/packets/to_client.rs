@@ -0,0 +1,85 @@
+use super::*;
+use crate::{
+    containers::GameServerInfo,
+    gametypes::*,
+    socket::{MByteBufferExt, ServerToClientPackets},
+};
+use mmap_bytey::{MByteBuffer, MByteBufferRead, MByteBufferWrite};
+
+/// Game servers sent in each `ServerList` packet.
+pub const SERVERS_PER_PACKET: usize = 5;
+
+packets! {
+    ServerToClientPackets;
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct OnlineCheck {}
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct AlertMsg {
+        pub message: String [max = MAX_MESSAGE_LENGTH],
+        /// The client should disconnect once the message is shown.
+        pub close_socket: bool,
+        /// Why the request was refused, `None` for other messages.
+        pub code: ErrorCode,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct FltAlert {
+        pub ftl_type: FtlType,
+        pub message: String [max = MAX_MESSAGE_LENGTH],
+    }
+
+    /// Sent in pages of `SERVERS_PER_PACKET` by `encode_pages` rather than `encode`.
+    #[derive(Clone, Debug)]
+    pub struct ServerList {
+        pub servers: Vec<GameServerInfo>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Login {
+        pub token: String [max = MAX_TOKEN_LENGTH],
+        pub relogin_code: String [max = MAX_TOKEN_LENGTH],
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Shutdown {
+        pub message: String [max = MAX_MESSAGE_LENGTH],
+    }
+
+    /// The answer to `Hello`, with this server's protocol.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Hello {
+        pub protocol_version: u16,
+        pub protocol_hash: String [max = PROTOCOL_HASH_LENGTH],
+        pub accepted: bool,
+        /// Compression used for frames in both directions from now on.
+        pub compression: Compression,
+    }
+}
+
+impl ServerList {
+    pub fn encode_pages(&self) -> Result<Vec<MByteBuffer>> {
+        let mut pages = Vec::new();
+
+        // An empty list is still sent so the client knows there is nothing to show.
+        for servers in self
+            .servers
+            .chunks(SERVERS_PER_PACKET)
+            .chain(self.servers.is_empty().then_some(&[][..]))
+        {
+            let mut buf = MByteBuffer::new_packet_with_count(Self::ID as u16)?;
+
+            for server in servers {
+                check_length("ServerList.name", &server.name, MAX_SERVER_NAME_LENGTH)?;
+                check_length("ServerList.ip", &server.ip, MAX_ADDRESS_LENGTH)?;
+                buf.write(server.clone())?;
+            }
+
+            buf.finish_with_count(servers.len() as u64)?;
+            pages.push(buf);
+        }
+
+        Ok(pages)
+    }
+}
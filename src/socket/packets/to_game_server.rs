@@ -0,0 +1,36 @@
+use super::*;
+use crate::socket::ServerToServerPackets;
+use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
+
+packets! {
+    ServerToServerPackets;
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct OnlineCheck {}
+
+    /// Whether a client's login token was issued for this game server. `uid` is 0 when not.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Verification {
+        pub uid: i64,
+        pub verified: bool,
+    }
+
+    /// Disconnects a player who logged in again elsewhere or was banned.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct KillClient {
+        pub uid: i64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Shutdown {}
+
+    /// The answer to `Hello`, with this server's protocol.
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub struct Hello {
+        pub protocol_version: u16,
+        pub protocol_hash: String [max = PROTOCOL_HASH_LENGTH],
+        pub accepted: bool,
+        /// Compression used for frames in both directions from now on.
+        pub compression: Compression,
+    }
+}
@@ -0,0 +1,543 @@
+use crate::containers::ProxyProtocolConfig;
+use std::{
+    io::{self, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Longest v1 header, `PROXY UNKNOWN` with two IPv6 addresses and ports.
+const V1_MAX_LENGTH: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LENGTH: usize = 16;
+
+/// An IP network such as `10.0.0.0/8`. A plain address matches only itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+
+        let network: IpAddr = address
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not an IP address or CIDR", value))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("'{}' has an invalid prefix length", value))?,
+            None => max,
+        };
+
+        Ok(Self { network, prefix })
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Dual stack sockets report IPv4 peers as IPv4 mapped IPv6 addresses.
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Which listeners read a PROXY header, and from which peers.
+#[derive(Clone, Debug)]
+pub struct ProxyProtocol {
+    trusted_proxies: Vec<Cidr>,
+    clients: bool,
+    game_servers: bool,
+}
+
+impl ProxyProtocol {
+    pub fn new(config: &ProxyProtocolConfig) -> std::result::Result<Self, String> {
+        Ok(Self {
+            trusted_proxies: config
+                .trusted_proxies
+                .iter()
+                .map(|proxy| proxy.parse())
+                .collect::<std::result::Result<_, _>>()?,
+            clients: config.clients,
+            game_servers: config.game_servers,
+        })
+    }
+
+    /// A header reader for a new connection, if `peer` is a trusted proxy on a listener
+    /// that expects one. Everyone else is taken at their socket address.
+    pub fn reader(&self, peer: SocketAddr, game_server: bool) -> Option<ProxyReader> {
+        let enabled = if game_server {
+            self.game_servers
+        } else {
+            self.clients
+        };
+
+        (enabled && self.is_trusted(peer.ip())).then(ProxyReader::default)
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|proxy| proxy.contains(ip))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProxyStatus {
+    /// More data is needed.
+    Pending,
+    /// The header was read. `source` is the original client, `None` for health checks
+    /// and unknown protocols. `rest` is data that followed the header.
+    Done {
+        source: Option<SocketAddr>,
+        rest: Vec<u8>,
+    },
+    Invalid,
+}
+
+/// Collects the PROXY protocol v1 or v2 header a load balancer sends before anything else.
+#[derive(Debug, Default)]
+pub struct ProxyReader {
+    data: Vec<u8>,
+}
+
+impl ProxyReader {
+    /// Reads what is available from `stream`. Fails on errors and end of stream.
+    pub fn read(&mut self, stream: &mut impl Read) -> io::Result<ProxyStatus> {
+        let mut buf = [0u8; 512];
+
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.data.extend_from_slice(&buf[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(ProxyStatus::Pending)
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+
+            match parse_proxy_header(&self.data) {
+                ProxyStatus::Pending => continue,
+                status => return Ok(status),
+            }
+        }
+    }
+}
+
+pub fn parse_proxy_header(data: &[u8]) -> ProxyStatus {
+    if starts_with(data, V1_PREFIX) {
+        parse_v1(data)
+    } else if starts_with(data, V2_SIGNATURE) {
+        parse_v2(data)
+    } else {
+        ProxyStatus::Invalid
+    }
+}
+
+/// True when `data` begins with `prefix`, or is the start of it.
+fn starts_with(data: &[u8], prefix: &[u8]) -> bool {
+    let length = data.len().min(prefix.len());
+    data[..length] == prefix[..length]
+}
+
+fn parse_v1(data: &[u8]) -> ProxyStatus {
+    let Some(end) = data.windows(2).position(|window| window == b"\r\n") else {
+        return if data.len() < V1_MAX_LENGTH {
+            ProxyStatus::Pending
+        } else {
+            ProxyStatus::Invalid
+        };
+    };
+
+    if end + 2 > V1_MAX_LENGTH {
+        return ProxyStatus::Invalid;
+    }
+
+    let Ok(line) = std::str::from_utf8(&data[..end]) else {
+        return ProxyStatus::Invalid;
+    };
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    let rest = data[end + 2..].to_vec();
+
+    let source = match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => None,
+        ["PROXY", protocol @ ("TCP4" | "TCP6"), source, _destination, port, _] => {
+            let ip = match (*protocol, source.parse::<IpAddr>()) {
+                (_, Err(_)) => return ProxyStatus::Invalid,
+                ("TCP4", Ok(ip)) if ip.is_ipv4() => ip,
+                ("TCP6", Ok(ip)) if ip.is_ipv6() => ip,
+                _ => return ProxyStatus::Invalid,
+            };
+
+            match port.parse::<u16>() {
+                Ok(port) => Some(SocketAddr::new(ip, port)),
+                Err(_) => return ProxyStatus::Invalid,
+            }
+        }
+        _ => return ProxyStatus::Invalid,
+    };
+
+    ProxyStatus::Done { source, rest }
+}
+
+fn parse_v2(data: &[u8]) -> ProxyStatus {
+    if data.len() < V2_HEADER_LENGTH {
+        return ProxyStatus::Pending;
+    }
+
+    let version = data[12] >> 4;
+    let command = data[12] & 0x0F;
+    let family = data[13];
+    let length = u16::from_be_bytes([data[14], data[15]]) as usize;
+
+    if version != 2 || command > 1 {
+        return ProxyStatus::Invalid;
+    }
+
+    let Some(addresses) = data.get(V2_HEADER_LENGTH..V2_HEADER_LENGTH + length) else {
+        return ProxyStatus::Pending;
+    };
+
+    let rest = data[V2_HEADER_LENGTH + length..].to_vec();
+
+    // LOCAL is used by the proxy's own health checks.
+    if command == 0 {
+        return ProxyStatus::Done { source: None, rest };
+    }
+
+    let source = match family {
+        // TCP over IPv4.
+        0x11 if addresses.len() >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Some(SocketAddr::new(IpAddr::V4(ip), port))
+        }
+        // TCP over IPv6.
+        0x21 if addresses.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+        }
+        0x11 | 0x21 => return ProxyStatus::Invalid,
+        // UDP and unix sockets keep the proxy's address.
+        _ => None,
+    };
+
+    ProxyStatus::Done { source, rest }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn done(source: Option<&str>, rest: &[u8]) -> ProxyStatus {
+        ProxyStatus::Done {
+            source: source.map(|source| source.parse().unwrap()),
+            rest: rest.to_vec(),
+        }
+    }
+
+    /// A v2 header with `command`, `family` and the address block `addresses`.
+    fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    fn v2_addresses(source: IpAddr, destination: IpAddr, ports: [u16; 2]) -> Vec<u8> {
+        let mut addresses = Vec::new();
+
+        for address in [source, destination] {
+            match address {
+                IpAddr::V4(address) => addresses.extend_from_slice(&address.octets()),
+                IpAddr::V6(address) => addresses.extend_from_slice(&address.octets()),
+            }
+        }
+
+        for port in ports {
+            addresses.extend_from_slice(&port.to_be_bytes());
+        }
+
+        addresses
+    }
+
+    #[test]
+    fn cidrs_contain_their_networks() {
+        let network: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains(ip("10.255.0.1")));
+        assert!(!network.contains(ip("11.0.0.1")));
+        assert!(!network.contains(ip("::1")));
+
+        let single: Cidr = "192.168.1.5".parse().unwrap();
+        assert!(single.contains(ip("192.168.1.5")));
+        assert!(!single.contains(ip("192.168.1.6")));
+
+        let all: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(ip("203.0.113.9")));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(ip("fd12:3456::1")));
+        assert!(!v6.contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn cidrs_match_ipv4_mapped_addresses() {
+        let network: Cidr = "10.0.0.0/8".parse().unwrap();
+
+        assert!(network.contains(ip("::ffff:10.1.2.3")));
+        assert!(!network.contains(ip("::ffff:11.1.2.3")));
+    }
+
+    #[test]
+    fn refuses_invalid_cidrs() {
+        for value in ["10.0.0.0/33", "::1/129", "10.0.0.0/x", "proxy", ""] {
+            assert!(value.parse::<Cidr>().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn only_trusted_proxies_on_enabled_listeners_send_headers() {
+        let proxy_protocol = ProxyProtocol::new(&ProxyProtocolConfig {
+            trusted_proxies: vec!["10.0.0.0/8".into()],
+            clients: true,
+            game_servers: false,
+        })
+        .unwrap();
+        let proxy = "10.0.0.2:4000".parse().unwrap();
+
+        assert!(proxy_protocol.reader(proxy, false).is_some());
+        assert!(proxy_protocol.reader(proxy, true).is_none());
+        assert!(proxy_protocol
+            .reader("203.0.113.9:4000".parse().unwrap(), false)
+            .is_none());
+    }
+
+    #[test]
+    fn parses_v1_headers() {
+        assert_eq!(
+            parse_proxy_header(b"PROXY TCP4 203.0.113.9 10.0.0.1 51000 7010\r\nhello"),
+            done(Some("203.0.113.9:51000"), b"hello")
+        );
+        assert_eq!(
+            parse_proxy_header(b"PROXY TCP6 2001:db8::9 2001:db8::1 51000 7010\r\n"),
+            done(Some("[2001:db8::9]:51000"), b"")
+        );
+        assert_eq!(
+            parse_proxy_header(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\nrest"),
+            done(None, b"rest")
+        );
+        assert_eq!(parse_proxy_header(b"PROXY UNKNOWN\r\n"), done(None, b""));
+    }
+
+    #[test]
+    fn waits_for_truncated_v1_headers() {
+        let header = b"PROXY TCP4 203.0.113.9 10.0.0.1 51000 7010\r\n";
+
+        for length in [1, 5, 20, header.len() - 1] {
+            assert_eq!(parse_proxy_header(&header[..length]), ProxyStatus::Pending);
+        }
+    }
+
+    #[test]
+    fn refuses_invalid_v1_headers() {
+        let long = format!("PROXY UNKNOWN {}\r\n", "a".repeat(V1_MAX_LENGTH));
+
+        for header in [
+            &b"GET / HTTP/1.1\r\n"[..],
+            b"PROXY TCP4 2001:db8::9 10.0.0.1 51000 7010\r\n",
+            b"PROXY TCP6 203.0.113.9 2001:db8::1 51000 7010\r\n",
+            b"PROXY TCP4 203.0.113.9 10.0.0.1 port 7010\r\n",
+            b"PROXY TCP4 203.0.113.9 10.0.0.1 51000\r\n",
+            b"PROXY UDP4 203.0.113.9 10.0.0.1 51000 7010\r\n",
+            long.as_bytes(),
+            &[b'P', b'R', b'O', b'X', b'Y', b' ', 0xFF, b'\r', b'\n'],
+        ] {
+            assert_eq!(
+                parse_proxy_header(header),
+                ProxyStatus::Invalid,
+                "{}",
+                String::from_utf8_lossy(header)
+            );
+        }
+
+        // Without an end of line the header is refused once it can not fit any more.
+        assert_eq!(
+            parse_proxy_header(&long.as_bytes()[..V1_MAX_LENGTH]),
+            ProxyStatus::Invalid
+        );
+    }
+
+    #[test]
+    fn parses_v2_headers() {
+        let mut header = v2(
+            1,
+            0x11,
+            &v2_addresses(ip("203.0.113.9"), ip("10.0.0.1"), [51000, 7010]),
+        );
+        header.extend_from_slice(b"hello");
+        assert_eq!(
+            parse_proxy_header(&header),
+            done(Some("203.0.113.9:51000"), b"hello")
+        );
+
+        let header = v2(
+            1,
+            0x21,
+            &v2_addresses(ip("2001:db8::9"), ip("2001:db8::1"), [51000, 7010]),
+        );
+        assert_eq!(
+            parse_proxy_header(&header),
+            done(Some("[2001:db8::9]:51000"), b"")
+        );
+    }
+
+    #[test]
+    fn keeps_ipv4_mapped_v2_sources_for_the_server_to_canonicalize() {
+        let header = v2(
+            1,
+            0x21,
+            &v2_addresses(ip("::ffff:203.0.113.9"), ip("::1"), [51000, 7010]),
+        );
+
+        let ProxyStatus::Done {
+            source: Some(source),
+            ..
+        } = parse_proxy_header(&header)
+        else {
+            panic!("header should parse");
+        };
+
+        assert_eq!(source.ip().to_canonical(), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn v2_local_and_unknown_families_have_no_source() {
+        let addresses = v2_addresses(ip("203.0.113.9"), ip("10.0.0.1"), [51000, 7010]);
+
+        assert_eq!(
+            parse_proxy_header(&v2(0, 0x11, &addresses)),
+            done(None, b"")
+        );
+        assert_eq!(parse_proxy_header(&v2(0, 0x00, &[])), done(None, b""));
+        // UDP over IPv4.
+        assert_eq!(
+            parse_proxy_header(&v2(1, 0x12, &addresses)),
+            done(None, b"")
+        );
+    }
+
+    #[test]
+    fn waits_for_truncated_v2_headers() {
+        let header = v2(
+            1,
+            0x11,
+            &v2_addresses(ip("203.0.113.9"), ip("10.0.0.1"), [51000, 7010]),
+        );
+
+        for length in [
+            1,
+            12,
+            V2_HEADER_LENGTH - 1,
+            V2_HEADER_LENGTH,
+            header.len() - 1,
+        ] {
+            assert_eq!(parse_proxy_header(&header[..length]), ProxyStatus::Pending);
+        }
+    }
+
+    #[test]
+    fn refuses_invalid_v2_headers() {
+        let addresses = v2_addresses(ip("203.0.113.9"), ip("10.0.0.1"), [51000, 7010]);
+
+        let mut version_one = v2(1, 0x11, &addresses);
+        version_one[12] = 0x11;
+        assert_eq!(parse_proxy_header(&version_one), ProxyStatus::Invalid);
+
+        assert_eq!(
+            parse_proxy_header(&v2(2, 0x11, &addresses)),
+            ProxyStatus::Invalid
+        );
+        // Address blocks too short for their family.
+        assert_eq!(
+            parse_proxy_header(&v2(1, 0x11, &addresses[..8])),
+            ProxyStatus::Invalid
+        );
+        assert_eq!(
+            parse_proxy_header(&v2(1, 0x21, &addresses)),
+            ProxyStatus::Invalid
+        );
+    }
+
+    /// Hands out `chunks` one read at a time, then would block.
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn reads_headers_split_across_reads() {
+        let mut reader = ProxyReader::default();
+        let mut stream = Chunks(vec![b"PROXY TCP4 203.0.113.9".to_vec()]);
+
+        assert_eq!(reader.read(&mut stream).unwrap(), ProxyStatus::Pending);
+
+        let mut stream = Chunks(vec![
+            b" 10.0.0.1 51000 ".to_vec(),
+            b"7010\r\nclient hello".to_vec(),
+        ]);
+
+        assert_eq!(
+            reader.read(&mut stream).unwrap(),
+            done(Some("203.0.113.9:51000"), b"client hello")
+        );
+    }
+
+    #[test]
+    fn fails_when_the_stream_ends_before_the_header() {
+        let mut reader = ProxyReader::default();
+
+        assert_eq!(
+            reader.read(&mut &b"PROXY TCP4"[..]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
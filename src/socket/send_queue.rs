@@ -0,0 +1,299 @@
+use crate::containers::{ConnectionConfig, QueueOverflow};
+use mmap_bytey::{MByteBuffer, BUFFER_SIZE};
+use std::collections::VecDeque;
+
+/// Packets waiting to be written to one connection, bounded by `ConnectionConfig`.
+#[derive(Debug)]
+pub struct SendQueue {
+    packets: VecDeque<MByteBuffer>,
+    /// Bytes not yet written, counting only the rest of a partly written front packet.
+    bytes: usize,
+    /// Bytes of the front packet already written.
+    written: usize,
+}
+
+impl SendQueue {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            packets: VecDeque::with_capacity(capacity),
+            bytes: 0,
+            written: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Queues `buf`, or returns false when that would pass the limits and the connection
+    /// should be closed.
+    pub fn push(&mut self, mut buf: MByteBuffer, limits: &ConnectionConfig) -> bool {
+        let size = buf.length();
+
+        if limits.max_queued_bytes > 0 && self.bytes + size > limits.max_queued_bytes {
+            return false;
+        }
+
+        if limits.max_queued_packets > 0 && self.packets.len() >= limits.max_queued_packets {
+            if limits.overflow == QueueOverflow::Disconnect {
+                return false;
+            }
+
+            // A packet being written may not change under the writer.
+            let started = self.packets.len() == 1 && self.written > 0;
+
+            let Some(back) = self.packets.back_mut() else {
+                return false;
+            };
+
+            // Encoded packets leave their cursor at the start, so append at the end.
+            if started
+                || back.length() + size > BUFFER_SIZE
+                || back.move_cursor(back.length()).is_err()
+                || back.write_slice(buf.as_slice()).is_err()
+            {
+                return false;
+            }
+
+            self.bytes += size;
+            return true;
+        }
+
+        self.packets.push_back(buf);
+        self.bytes += size;
+        true
+    }
+
+    /// Takes the next packet to write whole.
+    pub fn pop_front(&mut self) -> Option<MByteBuffer> {
+        let packet = self.packets.pop_front()?;
+
+        self.bytes -= packet.length() - self.written;
+        self.written = 0;
+        self.shrink();
+        Some(packet)
+    }
+
+    /// The part of the front packet that has not been written yet.
+    pub fn unwritten(&mut self) -> Option<&[u8]> {
+        let written = self.written;
+
+        self.packets
+            .front_mut()
+            .map(|packet| packet.as_slice().get(written..).unwrap_or_default())
+    }
+
+    /// Marks `count` bytes of the front packet written, removing it once all are.
+    pub fn advance(&mut self, count: usize) {
+        let Some(front) = self.packets.front() else {
+            return;
+        };
+
+        self.written += count;
+        self.bytes -= count;
+
+        if self.written >= front.length() {
+            self.packets.pop_front();
+            self.written = 0;
+            self.shrink();
+        }
+    }
+
+    /// Drops everything queued, for connections that are being closed.
+    pub fn clear(&mut self) {
+        self.packets.clear();
+        self.bytes = 0;
+        self.written = 0;
+        self.shrink();
+    }
+
+    /// Gives back the memory of a burst once it has been sent.
+    fn shrink(&mut self) {
+        if self.packets.is_empty() && self.packets.capacity() > 100 {
+            self.packets.shrink_to(100);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{MByteBufferExt, LENGTH_PREFIX};
+
+    /// A frame of `size` bytes built the way `encode` builds them, which leaves the cursor
+    /// at the start. The body is filled with `fill`.
+    fn packet(size: usize, fill: u8) -> MByteBuffer {
+        let mut buf = MByteBuffer::new_packet().unwrap();
+        buf.write_slice(&vec![fill; size - LENGTH_PREFIX]).unwrap();
+        buf.finish().unwrap();
+        buf
+    }
+
+    fn limits(packets: usize, bytes: usize, overflow: QueueOverflow) -> ConnectionConfig {
+        ConnectionConfig {
+            max_queued_packets: packets,
+            max_queued_bytes: bytes,
+            overflow,
+            ..ConnectionConfig::default()
+        }
+    }
+
+    #[test]
+    fn refuses_packets_past_the_byte_limit() {
+        let limits = limits(0, 100, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(60, 1), &limits));
+        assert!(queue.push(packet(40, 2), &limits));
+        assert!(!queue.push(packet(10, 3), &limits));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.bytes(), 100);
+    }
+
+    #[test]
+    fn disconnects_past_the_packet_limit() {
+        let limits = limits(2, 0, QueueOverflow::Disconnect);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        assert!(queue.push(packet(10, 2), &limits));
+        assert!(!queue.push(packet(10, 3), &limits));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.bytes(), 20);
+    }
+
+    #[test]
+    fn coalesces_past_the_packet_limit() {
+        let limits = limits(2, 0, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        assert!(queue.push(packet(10, 2), &limits));
+        assert!(queue.push(packet(12, 3), &limits));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.bytes(), 32);
+
+        let mut first = queue.pop_front().unwrap();
+        assert_eq!(first.as_slice(), packet(10, 1).as_slice());
+
+        let mut joined = packet(10, 2).as_slice().to_vec();
+        joined.extend_from_slice(packet(12, 3).as_slice());
+        assert_eq!(queue.pop_front().unwrap().as_slice(), joined.as_slice());
+        assert_eq!(queue.bytes(), 0);
+    }
+
+    #[test]
+    fn coalescing_still_keeps_to_the_byte_limit() {
+        let limits = limits(1, 30, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(20, 1), &limits));
+        assert!(!queue.push(packet(11, 2), &limits));
+        assert_eq!(queue.bytes(), 20);
+    }
+
+    #[test]
+    fn does_not_coalesce_past_the_buffer_size() {
+        let limits = limits(1, 0, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(BUFFER_SIZE - 10, 1), &limits));
+        assert!(!queue.push(packet(11, 2), &limits));
+        assert!(queue.push(packet(10, 3), &limits));
+        assert_eq!(queue.bytes(), BUFFER_SIZE);
+    }
+
+    #[test]
+    fn does_not_coalesce_into_a_partly_written_packet() {
+        let limits = limits(1, 0, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        queue.advance(4);
+        assert!(!queue.push(packet(10, 2), &limits));
+        assert_eq!(queue.unwritten().unwrap().len(), 6);
+        assert_eq!(queue.bytes(), 6);
+    }
+
+    #[test]
+    fn coalesces_behind_a_partly_written_packet() {
+        let limits = limits(2, 0, QueueOverflow::Coalesce);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        assert!(queue.push(packet(10, 2), &limits));
+        queue.advance(4);
+        assert!(queue.push(packet(12, 3), &limits));
+        assert_eq!(queue.unwritten().unwrap(), &packet(10, 1).as_slice()[4..]);
+        assert_eq!(queue.bytes(), 28);
+    }
+
+    #[test]
+    fn counts_bytes_across_partial_writes() {
+        let limits = limits(0, 0, QueueOverflow::Disconnect);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        assert!(queue.push(packet(20, 2), &limits));
+
+        queue.advance(3);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.bytes(), 27);
+        assert_eq!(queue.unwritten().unwrap().len(), 7);
+
+        queue.advance(7);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.bytes(), 20);
+        assert_eq!(queue.unwritten().unwrap().len(), 20);
+
+        queue.advance(20);
+        assert!(queue.is_empty());
+        assert_eq!(queue.bytes(), 0);
+        assert!(queue.unwritten().is_none());
+    }
+
+    #[test]
+    fn pop_front_only_takes_the_unwritten_bytes_off() {
+        let limits = limits(0, 0, QueueOverflow::Disconnect);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        assert!(queue.push(packet(20, 2), &limits));
+        queue.advance(4);
+
+        assert_eq!(queue.pop_front().unwrap().length(), 10);
+        assert_eq!(queue.bytes(), 20);
+        assert_eq!(queue.unwritten().unwrap().len(), 20);
+
+        queue.advance(5);
+        assert_eq!(queue.pop_front().unwrap().length(), 20);
+        assert_eq!(queue.bytes(), 0);
+        assert!(queue.pop_front().is_none());
+    }
+
+    #[test]
+    fn clear_drops_everything() {
+        let limits = limits(0, 0, QueueOverflow::Disconnect);
+        let mut queue = SendQueue::with_capacity(4);
+
+        assert!(queue.push(packet(10, 1), &limits));
+        queue.advance(4);
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.bytes(), 0);
+
+        assert!(queue.push(packet(10, 2), &limits));
+        assert_eq!(queue.unwritten().unwrap().len(), 10);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{containers::Storage, gametypes::*, socket::*};
+use crate::{containers::Storage, gametypes::*, locales::Message, socket::*};
 
 #[inline]
 pub async fn send_infomsg(
@@ -7,14 +7,46 @@ pub async fn send_infomsg(
     message: String,
     close_socket: bool,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
+    send_alert(storage, client, ErrorCode::None, message, close_socket).await
+}
+
+/// Refuses a request with the code's message in the client's locale and tells the
+/// client to disconnect.
+#[inline]
+pub async fn send_error(storage: &mut Storage, client: &mut Client, code: ErrorCode) -> Result<()> {
+    send_error_with(storage, client, code, &[]).await
+}
+
+/// `send_error` for messages with placeholders, such as `{server}`.
+#[inline]
+pub async fn send_error_with(
+    storage: &mut Storage,
+    client: &mut Client,
+    code: ErrorCode,
+    args: &[(&str, &str)],
+) -> Result<()> {
+    let message = storage
+        .locales
+        .text(&client.locale, Message::Error(code), args);
+
+    send_alert(storage, client, code, message, true).await
+}
 
-    buf.write(ServerToClientPackets::AlertMsg)?;
-    buf.write(message)?;
-    buf.write(close_socket)?;
-    buf.finish()?;
+#[inline]
+pub async fn send_alert(
+    storage: &mut Storage,
+    client: &mut Client,
+    code: ErrorCode,
+    message: String,
+    close_socket: bool,
+) -> Result<()> {
+    let buf = encode(to_client::AlertMsg {
+        message,
+        close_socket,
+        code,
+    })?;
 
-    client.send(&*storage.poll.read().await, buf)
+    client.send(storage, buf).await
 }
 
 #[inline]
@@ -24,14 +56,12 @@ pub async fn send_fltalert(
     message: String,
     ftltype: FtlType,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
+    let buf = encode(to_client::FltAlert {
+        ftl_type: ftltype,
+        message,
+    })?;
 
-    buf.write(ServerToClientPackets::FltAlert)?;
-    buf.write(ftltype)?;
-    buf.write(message)?;
-    buf.finish()?;
-
-    client.send(&*storage.poll.read().await, buf)
+    client.send(storage, buf).await
 }
 
 #[inline]
@@ -41,41 +71,19 @@ pub async fn send_verification(
     id: i64,
     verified: bool,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
-
-    buf.write(ServerToServerPackets::Verification)?;
-    buf.write(id)?;
-    buf.write(verified)?;
-    buf.finish()?;
+    let buf = encode(to_game_server::Verification { uid: id, verified })?;
 
-    game_server.send(&*storage.poll.read().await, buf)
+    game_server.send(storage, buf).await
 }
 
 #[inline]
 pub async fn send_server_list(storage: &mut Storage, client: &mut Client) -> Result<()> {
-    let per_packet = 5;
-
-    for i in 0..(storage.servers.len() / per_packet) + 1 {
-        let mut buf = MByteBuffer::new_packet_with_count(ServerToClientPackets::ServerList as u16)?;
-        let mut count = 0;
-
-        for id in i * per_packet..i * per_packet + per_packet {
-            if let Some((_, server_info)) = storage.servers.get_index(id) {
-                count += 1;
-                buf.write(&server_info.name)?;
-                buf.write(&server_info.ip)?;
-                buf.write(server_info.port)?;
-                buf.write(server_info.players_on)?;
-                buf.write(server_info.max_players)?;
-            } else {
-                // we reached the end.
-                buf.finish_with_count(count)?;
-                return client.send(&*storage.poll.read().await, buf);
-            }
-        }
-
-        buf.finish_with_count(count)?;
-        client.send(&*storage.poll.read().await, buf)?;
+    let list = to_client::ServerList {
+        servers: storage.servers.values().cloned().collect(),
+    };
+
+    for buf in list.encode_pages()? {
+        client.send(storage, buf).await?;
     }
 
     Ok(())
@@ -88,26 +96,53 @@ pub async fn send_login(
     token: String,
     relogin_code: &str,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
+    let buf = encode(to_client::Login {
+        token,
+        relogin_code: relogin_code.to_string(),
+    })?;
 
-    buf.write(ServerToClientPackets::Login)?;
-    buf.write(token)?;
-    buf.write(relogin_code)?;
-    buf.finish()?;
+    client.send(storage, buf).await
+}
 
-    client.send(&*storage.poll.read().await, buf)
+pub async fn send_hello(
+    storage: &mut Storage,
+    client: &mut Client,
+    accepted: bool,
+    compression: Compression,
+) -> Result<()> {
+    let buf = encode(to_client::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        protocol_hash: protocol_hash().to_string(),
+        accepted,
+        compression,
+    })?;
+
+    client.send(storage, buf).await
 }
 
-pub async fn send_game_server_online_check(
+pub async fn send_game_server_hello(
     storage: &mut Storage,
     game_server: &mut GameServer,
+    accepted: bool,
+    compression: Compression,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
+    let buf = encode(to_game_server::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        protocol_hash: protocol_hash().to_string(),
+        accepted,
+        compression,
+    })?;
+
+    game_server.send(storage, buf).await
+}
 
-    buf.write(ServerToServerPackets::OnlineCheck)?;
-    buf.finish()?;
+pub async fn send_game_server_online_check(
+    storage: &mut Storage,
+    game_server: &mut GameServer,
+) -> Result<()> {
+    let buf = encode(to_game_server::OnlineCheck {})?;
 
-    game_server.send(&*storage.poll.read().await, buf)
+    game_server.send(storage, buf).await
 }
 
 pub async fn send_kill_client(
@@ -115,11 +150,28 @@ pub async fn send_kill_client(
     game_server: &mut GameServer,
     uid: i64,
 ) -> Result<()> {
-    let mut buf = MByteBuffer::new_packet()?;
+    let buf = encode(to_game_server::KillClient { uid })?;
+
+    storage.metrics.kills.inc();
+
+    game_server.send(storage, buf).await
+}
 
-    buf.write(ServerToServerPackets::KillClient)?;
-    buf.write(uid)?;
-    buf.finish()?;
+pub async fn send_client_shutdown(
+    storage: &mut Storage,
+    client: &mut Client,
+    message: String,
+) -> Result<()> {
+    let buf = encode(to_client::Shutdown { message })?;
+
+    client.send(storage, buf).await
+}
+
+pub async fn send_game_server_shutdown(
+    storage: &mut Storage,
+    game_server: &mut GameServer,
+) -> Result<()> {
+    let buf = encode(to_game_server::Shutdown {})?;
 
-    game_server.send(&*storage.poll.read().await, buf)
+    game_server.send(storage, buf).await
 }
@@ -1,77 +1,128 @@
 use crate::{
-    containers::{HashMap, Storage},
-    gametypes::Result,
-    socket::{Client, SocketState},
+    containers::{Config, HashMap, Storage, WebSocketConfig},
+    gametypes::*,
+    socket::{
+        is_private_address, Client, Listener, ListenerKind, ProxyProtocol, SocketState, Transport,
+        WebSocket,
+    },
 };
-use log::{trace, warn};
-use mio::{net::TcpListener, Events, Poll};
-use std::{collections::VecDeque, io, net::SocketAddr, sync::Arc, time::Duration};
+use log::{info, trace, warn};
+use mio::{Events, Poll};
+use std::{collections::VecDeque, io, net::IpAddr, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 
 use super::GameServer;
 
-pub const CLIENT_SERVER: mio::Token = mio::Token(0);
-pub const GAME_SERVER: mio::Token = mio::Token(1);
-
 pub struct Server {
-    pub client_listener: TcpListener,
-    pub server_listener: TcpListener,
+    /// Every bound address, registered with its index as the token.
+    pub listeners: Vec<Listener>,
+    /// Origins WebSocket upgrades must come from, None when any may connect.
+    pub websocket_origins: Option<Arc<[String]>>,
     pub clients: HashMap<mio::Token, Arc<Mutex<Client>>>,
     pub game_servers: HashMap<mio::Token, Arc<Mutex<GameServer>>>,
     pub tokens: VecDeque<mio::Token>,
+    pub max_connections: usize,
     pub tls_config: Arc<rustls::ServerConfig>,
+    /// `tls_config` with the ALPN browsers use, so they connect whatever the game client offers.
+    pub websocket_tls_config: Arc<rustls::ServerConfig>,
+    pub proxy_protocol: Option<ProxyProtocol>,
+    /// Open client connections by address, for `connections.max_per_ip`.
+    pub connections_per_ip: HashMap<IpAddr, usize>,
+    pub connection_ips: HashMap<mio::Token, IpAddr>,
 }
 
 impl Server {
     #[inline]
-    pub fn new(
-        poll: &mut Poll,
-        addr: &str,
-        clients_port: u16,
-        servers_port: u16,
-        max: usize,
-        cfg: Arc<rustls::ServerConfig>,
-    ) -> Result<Server> {
-        assert_ne!(
-            clients_port, servers_port,
-            "Server Socket and Client Socket can not have the same Port."
-        );
+    pub fn new(poll: &mut Poll, config: &Config, cfg: Arc<rustls::ServerConfig>) -> Result<Server> {
+        let websocket = config.websocket.as_ref();
+        let max = config.maxconnections;
+
+        let proxy_protocol = config
+            .proxy_protocol
+            .as_ref()
+            .map(ProxyProtocol::new)
+            .transpose()
+            .map_err(|e| AscendingError::InvalidConfig(vec![e]))?;
+
+        let addresses = config
+            .listen_addresses()
+            .map_err(AscendingError::InvalidConfig)?;
+
+        /* Bind every address, the listener's position is its token. */
+        let mut listeners = Vec::with_capacity(addresses.len());
+
+        for &(kind, addr) in &addresses {
+            // An IPv6 wildcard would also take the IPv4 port another listener wants.
+            let only_v6 = addresses
+                .iter()
+                .any(|(_, other)| other.is_ipv4() && other.port() == addr.port());
+            let mut listener = Listener::bind(kind, addr, only_v6)?;
+
+            poll.registry().register(
+                &mut listener.socket,
+                mio::Token(listeners.len()),
+                mio::Interest::READABLE,
+            )?;
+
+            if kind == ListenerKind::GameServers && !is_private_address(addr.ip()) {
+                warn!(
+                    "The game server listener {} is not on a private address, keep it behind a firewall.",
+                    addr
+                );
+            }
+
+            info!("Listening for {} on {}", kind, addr);
+            listeners.push(listener);
+        }
 
         /* Create a bag of unique tokens. */
         let mut tokens = VecDeque::with_capacity(max);
 
-        for i in 2..max {
+        for i in listeners.len()..max {
             tokens.push_back(mio::Token(i));
         }
 
-        /* Set up the Clients TCP TLS listener. */
-        let client_addr = SocketAddr::new(addr.parse()?, clients_port);
-        let mut client_listener = TcpListener::bind(client_addr)?;
+        let mut server = Server {
+            listeners,
+            websocket_origins: websocket.and_then(WebSocketConfig::origins),
+            clients: HashMap::default(),
+            game_servers: HashMap::default(),
+            tokens,
+            max_connections: max,
+            tls_config: cfg.clone(),
+            websocket_tls_config: cfg.clone(),
+            proxy_protocol,
+            connections_per_ip: HashMap::default(),
+            connection_ips: HashMap::default(),
+        };
 
-        poll.registry()
-            .register(&mut client_listener, CLIENT_SERVER, mio::Interest::READABLE)?;
+        server.set_tls_config(cfg);
+        Ok(server)
+    }
 
-        /* Set up the Game Servers TCP listener. */
-        let game_server_addr = SocketAddr::new(addr.parse()?, servers_port);
-        let mut server_listener = TcpListener::bind(game_server_addr)?;
+    /// Replaces the TLS settings used for new client connections on both listeners.
+    pub fn set_tls_config(&mut self, cfg: Arc<rustls::ServerConfig>) {
+        let mut websocket = (*cfg).clone();
+        websocket.alpn_protocols = vec![b"http/1.1".to_vec()];
 
-        poll.registry()
-            .register(&mut server_listener, GAME_SERVER, mio::Interest::READABLE)?;
+        self.websocket_tls_config = Arc::new(websocket);
+        self.tls_config = cfg;
+    }
 
-        Ok(Server {
-            client_listener,
-            server_listener,
-            clients: HashMap::default(),
-            game_servers: HashMap::default(),
-            tokens,
-            tls_config: cfg,
-        })
+    /// The listener registered with `token`, if it is not a connection's.
+    pub fn listener_kind(&self, token: mio::Token) -> Option<ListenerKind> {
+        self.listeners.get(token.0).map(|listener| listener.kind)
     }
 
-    pub async fn accept_client(&mut self, storage: &mut Storage) -> Result<()> {
+    /// Accepts every pending connection on the listener registered with `token`.
+    pub async fn accept(&mut self, storage: &mut Storage, token: mio::Token) -> Result<()> {
+        let Some(kind) = self.listener_kind(token) else {
+            return Ok(());
+        };
+
         /* Wait for a new connection to accept and try to grab a token from the bag. */
         loop {
-            let (stream, addr) = match self.client_listener.accept() {
+            let (stream, addr) = match self.listeners[token.0].socket.accept() {
                 Ok((stream, addr)) => (stream, addr),
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
                 Err(e) => {
@@ -82,62 +133,168 @@ impl Server {
 
             stream.set_nodelay(true)?;
 
-            if let Some(token) = self.tokens.pop_front() {
-                let tls_conn = rustls::ServerConnection::new(Arc::clone(&self.tls_config))?;
-                // Lets make the Client to handle hwo we send packets.
-                let mut client = Client::new(stream, token, addr.to_string(), tls_conn)?;
-                //Register the Poll to the client for recv and Sending
-                client.register(&*storage.poll.read().await)?;
+            // Checked before a token is taken so one address can not use them all up.
+            let ip = addr.ip().to_canonical();
+            let limited = kind != ListenerKind::GameServers
+                && !self
+                    .proxy_protocol
+                    .as_ref()
+                    .is_some_and(|proxy_protocol| proxy_protocol.is_trusted(ip));
+            let max_per_ip = storage.config.connections.max_per_ip;
 
-                // insert client into handled list.
-                self.clients.insert(token, Arc::new(Mutex::new(client)));
-            } else {
-                warn!("listener.accept No tokens left to give out.");
+            if limited
+                && max_per_ip > 0
+                && self
+                    .connections_per_ip
+                    .get(&ip)
+                    .copied()
+                    .unwrap_or_default()
+                    >= max_per_ip
+            {
+                trace!("listener.accept {} is at its connection limit.", ip);
+                storage.metrics.dropped_connection("max_per_ip");
                 drop(stream);
+                continue;
             }
-        }
-        Ok(())
-    }
 
-    pub async fn accept_server(&mut self, storage: &mut Storage) -> Result<()> {
-        /* Wait for a new connection to accept and try to grab a token from the bag. */
-        loop {
-            let (stream, addr) = match self.server_listener.accept() {
-                Ok((stream, addr)) => (stream, addr),
-                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
-                Err(e) => {
-                    trace!("listener.accept error: {}", e);
-                    return Err(e.into());
-                }
+            let Some(connection) = self.tokens.pop_front() else {
+                warn!("listener.accept No tokens left to give out.");
+                storage.metrics.dropped_connection("no_tokens");
+                drop(stream);
+                continue;
             };
 
-            stream.set_nodelay(true)?;
+            if limited {
+                *self.connections_per_ip.entry(ip).or_default() += 1;
+                self.connection_ips.insert(connection, ip);
+            }
 
-            if let Some(token) = self.tokens.pop_front() {
-                // Lets make the game_server to handle how we send packets.
-                let mut game_server = GameServer::new(stream, token, addr.to_string())?;
-                //Register the Poll to the client for recv and Sending
-                game_server.register(&*storage.poll.read().await)?;
+            let proxy = self.proxy_protocol.as_ref().and_then(|proxy_protocol| {
+                proxy_protocol.reader(addr, kind == ListenerKind::GameServers)
+            });
 
-                // insert client into handled list.
-                self.game_servers
-                    .insert(token, Arc::new(Mutex::new(game_server)));
-            } else {
-                warn!("listener.accept No tokens left to give out.");
-                drop(stream);
+            match kind {
+                ListenerKind::Clients | ListenerKind::WebSockets => {
+                    let (tls_config, transport) = if kind == ListenerKind::WebSockets {
+                        (
+                            &self.websocket_tls_config,
+                            Transport::WebSocket(Box::new(WebSocket::new(
+                                self.websocket_origins.clone(),
+                                storage.config.connections.max_websocket_packet,
+                            ))),
+                        )
+                    } else {
+                        (&self.tls_config, Transport::Tls)
+                    };
+
+                    let tls_conn = rustls::ServerConnection::new(Arc::clone(tls_config))?;
+                    // Lets make the Client to handle hwo we send packets.
+                    let mut client = Client::new(
+                        stream,
+                        connection,
+                        addr.to_string(),
+                        tls_conn,
+                        transport,
+                        proxy,
+                    )?;
+                    //Register the Poll to the client for recv and Sending
+                    client.register(&*storage.poll.read().await)?;
+
+                    // insert client into handled list.
+                    self.clients
+                        .insert(connection, Arc::new(Mutex::new(client)));
+                }
+                ListenerKind::GameServers => {
+                    // Lets make the game_server to handle how we send packets.
+                    let mut game_server =
+                        GameServer::new(stream, connection, addr.to_string(), proxy)?;
+                    //Register the Poll to the client for recv and Sending
+                    game_server.register(&*storage.poll.read().await)?;
+
+                    // insert client into handled list.
+                    self.game_servers
+                        .insert(connection, Arc::new(Mutex::new(game_server)));
+                }
             }
         }
+
+        storage.poll.read().await.registry().reregister(
+            &mut self.listeners[token.0].socket,
+            token,
+            mio::Interest::READABLE,
+        )?;
+
         Ok(())
     }
 
+    /// Counts a connection from a trusted proxy against the client it was made for, once
+    /// its PROXY header is read. Returns false when that client is at `max_per_ip`.
+    pub fn count_proxied(&mut self, token: mio::Token, ip: IpAddr, max_per_ip: usize) -> bool {
+        let ip = ip.to_canonical();
+        let count = self
+            .connections_per_ip
+            .get(&ip)
+            .copied()
+            .unwrap_or_default();
+
+        if max_per_ip > 0 && count >= max_per_ip {
+            return false;
+        }
+
+        self.connections_per_ip.insert(ip, count + 1);
+        self.connection_ips.insert(token, ip);
+        true
+    }
+
     #[inline]
     pub fn remove(&mut self, token: mio::Token) {
+        if let Some(ip) = self.connection_ips.remove(&token) {
+            if let Some(count) = self.connections_per_ip.get_mut(&ip) {
+                *count -= 1;
+
+                if *count == 0 {
+                    self.connections_per_ip.remove(&ip);
+                }
+            }
+        }
+
         /* If the token is valid, let's remove the connection and add the token back to the bag. */
-        if self.clients.contains_key(&token) {
-            self.clients.remove(&token);
+        if (self.clients.remove(&token).is_some() || self.game_servers.remove(&token).is_some())
+            && token.0 < self.max_connections
+        {
             self.tokens.push_front(token);
         }
     }
+
+    /// Grows or shrinks the token bag. Connections already using a token above the new
+    /// limit stay open, their token is just not handed out again once they close.
+    pub fn set_max_connections(&mut self, max: usize) {
+        if max < self.max_connections {
+            self.tokens.retain(|token| token.0 < max);
+        } else {
+            for i in self.max_connections.max(self.listeners.len())..max {
+                let token = mio::Token(i);
+
+                if !self.clients.contains_key(&token)
+                    && !self.game_servers.contains_key(&token)
+                    && !self.tokens.contains(&token)
+                {
+                    self.tokens.push_back(token);
+                }
+            }
+        }
+
+        self.max_connections = max;
+    }
+
+    /// Stops the listeners from accepting new connections. Used when shutting down.
+    pub fn stop_listening(&mut self, poll: &mio::Poll) -> Result<()> {
+        for listener in &mut self.listeners {
+            poll.registry().deregister(&mut listener.socket)?;
+        }
+
+        Ok(())
+    }
 }
 
 pub async fn poll_events(storage: &mut Storage) -> Result<()> {
@@ -150,41 +307,47 @@ pub async fn poll_events(storage: &mut Storage) -> Result<()> {
         .poll(&mut events, Some(Duration::from_millis(0)))?;
 
     for event in events.iter() {
-        match event.token() {
-            CLIENT_SERVER => {
-                let server = storage.server.clone();
-                server.write().await.accept_client(storage).await?;
-                storage.poll.read().await.registry().reregister(
-                    &mut server.write().await.client_listener,
-                    CLIENT_SERVER,
-                    mio::Interest::READABLE,
-                )?;
-            }
-            GAME_SERVER => {
-                let server = storage.server.clone();
-                server.write().await.accept_server(storage).await?;
-                storage.poll.read().await.registry().reregister(
-                    &mut server.write().await.server_listener,
-                    GAME_SERVER,
-                    mio::Interest::READABLE,
-                )?;
-            }
-            token => {
-                let mut server = storage.server.write().await;
-                let state = if let Some(a) = server.clients.get(&token) {
-                    let mut client = a.lock().await;
-                    client.process(event, storage).await?;
-                    client.state
-                } else {
-                    trace!("a token no longer exists within clients.");
-                    SocketState::Closed
-                };
-
-                if state == SocketState::Closed {
-                    server.remove(token);
-                };
-            }
+        let token = event.token();
+
+        if storage.server.read().await.listener_kind(token).is_some() {
+            let server = storage.server.clone();
+            server.write().await.accept(storage, token).await?;
+            continue;
         }
+
+        let client = storage.server.read().await.clients.get(&token).cloned();
+
+        let state = if let Some(client) = client {
+            let mut client = client.lock().await;
+            client.process(event, storage).await?;
+            client.state
+        } else {
+            let game_server = storage
+                .server
+                .read()
+                .await
+                .game_servers
+                .get(&token)
+                .cloned();
+
+            if let Some(game_server) = game_server {
+                let mut game_server = game_server.lock().await;
+                game_server.process(event, storage).await?;
+
+                if game_server.state == SocketState::Closed {
+                    storage.remove_game_server_info(token);
+                }
+
+                game_server.state
+            } else {
+                trace!("a token no longer exists within clients or game servers.");
+                SocketState::Closed
+            }
+        };
+
+        if state == SocketState::Closed {
+            storage.server.write().await.remove(token);
+        };
     }
 
     Ok(())
@@ -0,0 +1,642 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+
+/// Largest upgrade request accepted before the connection is dropped.
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum WebSocketState {
+    Handshake,
+    Open,
+    Closed,
+}
+
+/// What a chunk of decrypted data from the client turned into.
+#[derive(Debug, Default)]
+pub struct WebSocketRead {
+    /// Complete binary messages, each one packet without its length prefix.
+    pub messages: Vec<Vec<u8>>,
+    /// Bytes to send back: the handshake response, pongs and close frames.
+    pub reply: Vec<u8>,
+    /// The connection should be closed once `reply` is sent.
+    pub closed: bool,
+}
+
+/// Server side of RFC 6455 running on the decrypted TLS stream of a browser client.
+/// Only binary messages are accepted, each carrying one packet.
+#[derive(Debug)]
+pub struct WebSocket {
+    state: WebSocketState,
+    /// None lets any origin connect, see `WebSocketConfig::allow_any_origin`.
+    allowed_origins: Option<Arc<[String]>>,
+    /// Largest message accepted, `max_websocket_packet` as each message is one packet.
+    max_message_size: usize,
+    incoming: Vec<u8>,
+    fragments: Vec<u8>,
+    fragmented: bool,
+}
+
+impl WebSocket {
+    pub fn new(allowed_origins: Option<Arc<[String]>>, max_message_size: usize) -> Self {
+        Self {
+            state: WebSocketState::Handshake,
+            allowed_origins,
+            max_message_size,
+            incoming: Vec::new(),
+            fragments: Vec::new(),
+            fragmented: false,
+        }
+    }
+
+    /// Packets may only be sent once the upgrade has been answered.
+    pub fn is_open(&self) -> bool {
+        self.state == WebSocketState::Open
+    }
+
+    pub fn read(&mut self, data: &[u8]) -> WebSocketRead {
+        let mut read = WebSocketRead::default();
+
+        if self.state != WebSocketState::Closed {
+            self.incoming.extend_from_slice(data);
+        }
+
+        if self.state == WebSocketState::Handshake {
+            self.handshake(&mut read);
+        }
+
+        while self.state == WebSocketState::Open && self.next_frame(&mut read) {}
+
+        read.closed = self.state == WebSocketState::Closed;
+        read
+    }
+
+    fn handshake(&mut self, read: &mut WebSocketRead) {
+        let Some(end) = self
+            .incoming
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+        else {
+            if self.incoming.len() > MAX_HANDSHAKE_SIZE {
+                self.reject(read, "431 Request Header Fields Too Large");
+            }
+
+            return;
+        };
+
+        let Some(upgrade) = parse_upgrade(&self.incoming[..end + 4]) else {
+            return self.reject(read, "400 Bad Request");
+        };
+
+        // Browsers always send Origin, so this keeps other sites from connecting on a
+        // player's behalf.
+        let allowed = match &self.allowed_origins {
+            Some(origins) => upgrade.origin.is_some_and(|origin| {
+                origins
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(&origin))
+            }),
+            None => true,
+        };
+
+        if !allowed {
+            return self.reject(read, "403 Forbidden");
+        }
+
+        read.reply.extend_from_slice(
+            format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&upgrade.key)
+            )
+            .as_bytes(),
+        );
+
+        self.incoming.drain(..end + 4);
+        self.state = WebSocketState::Open;
+    }
+
+    fn reject(&mut self, read: &mut WebSocketRead, status: &str) {
+        read.reply.extend_from_slice(
+            format!(
+                "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                status
+            )
+            .as_bytes(),
+        );
+        self.state = WebSocketState::Closed;
+    }
+
+    /// Handles one frame. Returns false when more data is needed or the socket closed.
+    fn next_frame(&mut self, read: &mut WebSocketRead) -> bool {
+        if self.incoming.len() < 2 {
+            return false;
+        }
+
+        let fin = self.incoming[0] & 0x80 != 0;
+        let reserved = self.incoming[0] & 0x70;
+        let opcode = self.incoming[0] & 0x0F;
+        let masked = self.incoming[1] & 0x80 != 0;
+
+        let (length, mut offset) = match self.incoming[1] & 0x7F {
+            126 => match self.incoming.get(2..4) {
+                Some(bytes) => (u16::from_be_bytes([bytes[0], bytes[1]]) as u64, 4),
+                None => return false,
+            },
+            127 => match self.incoming.get(2..10) {
+                Some(bytes) => (u64::from_be_bytes(bytes.try_into().unwrap_or_default()), 10),
+                None => return false,
+            },
+            length => (length as u64, 2),
+        };
+
+        // Clients must mask every frame and no extensions are negotiated.
+        if !masked || reserved != 0 {
+            return self.fail(read, CLOSE_PROTOCOL_ERROR);
+        }
+
+        if opcode >= OPCODE_CLOSE && (!fin || length > 125) {
+            return self.fail(read, CLOSE_PROTOCOL_ERROR);
+        }
+
+        if length > self.max_message_size as u64
+            || (opcode == OPCODE_CONTINUATION
+                && self.fragments.len() + length as usize > self.max_message_size)
+        {
+            return self.fail(read, CLOSE_TOO_BIG);
+        }
+
+        let length = length as usize;
+
+        if self.incoming.len() < offset + 4 + length {
+            return false;
+        }
+
+        let mask = [
+            self.incoming[offset],
+            self.incoming[offset + 1],
+            self.incoming[offset + 2],
+            self.incoming[offset + 3],
+        ];
+        offset += 4;
+
+        let payload: Vec<u8> = self.incoming[offset..offset + length]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4])
+            .collect();
+
+        self.incoming.drain(..offset + length);
+
+        match opcode {
+            OPCODE_BINARY if !self.fragmented => {
+                if fin {
+                    read.messages.push(payload);
+                } else {
+                    self.fragments = payload;
+                    self.fragmented = true;
+                }
+            }
+            OPCODE_CONTINUATION if self.fragmented => {
+                self.fragments.extend_from_slice(&payload);
+
+                if fin {
+                    read.messages.push(std::mem::take(&mut self.fragments));
+                    self.fragmented = false;
+                }
+            }
+            OPCODE_TEXT if !self.fragmented => return self.fail(read, CLOSE_UNSUPPORTED_DATA),
+            OPCODE_CLOSE => {
+                // Echo the status code back as the close handshake asks.
+                read.reply
+                    .extend(frame(OPCODE_CLOSE, payload.get(..2).unwrap_or_default()));
+                self.state = WebSocketState::Closed;
+                return false;
+            }
+            OPCODE_PING => read.reply.extend(frame(OPCODE_PONG, &payload)),
+            OPCODE_PONG => {}
+            _ => return self.fail(read, CLOSE_PROTOCOL_ERROR),
+        }
+
+        true
+    }
+
+    fn fail(&mut self, read: &mut WebSocketRead, code: u16) -> bool {
+        read.reply.extend(frame(OPCODE_CLOSE, &code.to_be_bytes()));
+        self.state = WebSocketState::Closed;
+        false
+    }
+}
+
+/// Header of an unmasked, final server frame carrying `length` bytes.
+pub fn frame_header(opcode: u8, length: usize) -> Vec<u8> {
+    let mut header = vec![0x80 | opcode];
+
+    match length {
+        0..=125 => header.push(length as u8),
+        126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        _ => {
+            header.push(127);
+            header.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+
+    header
+}
+
+/// The bodies of the length prefixed packets in `data`, each sent as its own message.
+pub fn split_packets(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (prefix, rest) = data.split_first_chunk::<8>()?;
+        let length = (u64::from_le_bytes(*prefix) as usize).min(rest.len());
+        let (payload, rest) = rest.split_at(length);
+
+        data = rest;
+        Some(payload)
+    })
+}
+
+pub fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = frame_header(opcode, payload.len());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+struct UpgradeRequest {
+    key: String,
+    origin: Option<String>,
+}
+
+/// Checks that `data` is a WebSocket version 13 upgrade request.
+fn parse_upgrade(data: &[u8]) -> Option<UpgradeRequest> {
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut request = httparse::Request::new(&mut headers);
+
+    if !matches!(request.parse(data), Ok(httparse::Status::Complete(_)))
+        || request.method != Some("GET")
+    {
+        return None;
+    }
+
+    let header = |name: &str| {
+        request
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .and_then(|header| std::str::from_utf8(header.value).ok())
+            .map(str::trim)
+    };
+
+    let upgrade = header("Upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let connection = header("Connection").is_some_and(|value| {
+        value
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    });
+
+    if !upgrade || !connection || header("Sec-WebSocket-Version") != Some("13") {
+        return None;
+    }
+
+    let key = header("Sec-WebSocket-Key")?;
+
+    // The key is a random 16 byte nonce in base64.
+    if STANDARD.decode(key).map_or(true, |nonce| nonce.len() != 16) {
+        return None;
+    }
+
+    Some(UpgradeRequest {
+        key: key.to_string(),
+        origin: header("Origin").map(str::to_string),
+    })
+}
+
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(ACCEPT_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+    const MAX: usize = 16;
+
+    fn request(method: &str, headers: &[(&str, &str)]) -> Vec<u8> {
+        let mut request = format!("{} /ws HTTP/1.1\r\nHost: localhost\r\n", method);
+
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        request.push_str("\r\n");
+        request.into_bytes()
+    }
+
+    /// A valid upgrade request with `changed` headers replaced or added.
+    fn upgrade(changed: &[(&str, &str)]) -> Vec<u8> {
+        let mut headers = vec![
+            ("Upgrade", "websocket"),
+            ("Connection", "keep-alive, Upgrade"),
+            ("Sec-WebSocket-Version", "13"),
+            ("Sec-WebSocket-Key", KEY),
+            ("Origin", "https://play.example.com"),
+        ];
+
+        for &(name, value) in changed {
+            headers.retain(|(header, _)| *header != name);
+
+            if !value.is_empty() {
+                headers.push((name, value));
+            }
+        }
+
+        request("GET", &headers)
+    }
+
+    fn upgrade_without_origin() -> Vec<u8> {
+        upgrade(&[("Origin", "")])
+    }
+
+    /// A frame as a browser sends it, masked.
+    fn client_frame(opcode: u8, fin: bool, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = frame_header(opcode, payload.len());
+
+        if !fin {
+            frame[0] &= 0x7F;
+        }
+
+        frame[1] |= 0x80;
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    fn open_socket() -> WebSocket {
+        let mut socket = WebSocket::new(None, MAX);
+        let read = socket.read(&upgrade(&[]));
+
+        assert!(read.reply.starts_with(b"HTTP/1.1 101"));
+        assert!(socket.is_open());
+        socket
+    }
+
+    fn closed_with(read: &WebSocketRead, code: u16) {
+        assert!(read.closed);
+        assert_eq!(read.reply, frame(OPCODE_CLOSE, &code.to_be_bytes()));
+    }
+
+    #[test]
+    fn parses_upgrade_requests() {
+        let upgrade = parse_upgrade(&upgrade(&[])).unwrap();
+
+        assert_eq!(upgrade.key, KEY);
+        assert_eq!(upgrade.origin.as_deref(), Some("https://play.example.com"));
+        // The example from RFC 6455.
+        assert_eq!(accept_key(KEY), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert!(parse_upgrade(&upgrade_without_origin())
+            .unwrap()
+            .origin
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_bad_upgrade_requests() {
+        let bad = [
+            request("POST", &[("Upgrade", "websocket")]),
+            upgrade(&[("Sec-WebSocket-Version", "8")]),
+            upgrade(&[("Sec-WebSocket-Version", "")]),
+            upgrade(&[("Upgrade", "h2c")]),
+            upgrade(&[("Connection", "keep-alive")]),
+            upgrade(&[("Sec-WebSocket-Key", "")]),
+            upgrade(&[("Sec-WebSocket-Key", "c2hvcnQ=")]),
+            upgrade(&[("Sec-WebSocket-Key", "not base64!")]),
+        ];
+
+        for request in bad {
+            assert!(
+                parse_upgrade(&request).is_none(),
+                "{}",
+                String::from_utf8_lossy(&request)
+            );
+
+            let mut socket = WebSocket::new(None, MAX);
+            let read = socket.read(&request);
+
+            assert!(read.reply.starts_with(b"HTTP/1.1 400"));
+            assert!(read.closed);
+        }
+    }
+
+    #[test]
+    fn checks_the_origin_when_origins_are_listed() {
+        let allowed: Arc<[String]> = Arc::from(["https://PLAY.example.com".to_string()]);
+
+        for (request, status) in [
+            (upgrade(&[]), "101"),
+            (upgrade(&[("Origin", "https://evil.example.com")]), "403"),
+            (upgrade_without_origin(), "403"),
+        ] {
+            let mut socket = WebSocket::new(Some(allowed.clone()), MAX);
+            let read = socket.read(&request);
+
+            assert!(read
+                .reply
+                .starts_with(format!("HTTP/1.1 {}", status).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn accepts_any_origin_when_allowed() {
+        for request in [
+            upgrade(&[("Origin", "https://evil.example.com")]),
+            upgrade_without_origin(),
+        ] {
+            let mut socket = WebSocket::new(None, MAX);
+            let read = socket.read(&request);
+
+            assert!(read.reply.starts_with(b"HTTP/1.1 101"));
+        }
+    }
+
+    #[test]
+    fn waits_for_the_whole_upgrade_request_and_frames() {
+        let mut socket = WebSocket::new(None, MAX);
+        let request = upgrade(&[]);
+        let (start, end) = request.split_at(20);
+
+        assert!(socket.read(start).reply.is_empty());
+
+        let mut rest = end.to_vec();
+        rest.extend(client_frame(OPCODE_BINARY, true, b"packet"));
+        let (end, frame) = rest.split_at(end.len() + 3);
+
+        let read = socket.read(end);
+        assert!(read.reply.starts_with(b"HTTP/1.1 101"));
+        assert!(read.messages.is_empty());
+
+        assert_eq!(socket.read(frame).messages, [b"packet".to_vec()]);
+    }
+
+    #[test]
+    fn rejects_unmasked_and_reserved_frames() {
+        let mut socket = open_socket();
+        closed_with(
+            &socket.read(&frame(OPCODE_BINARY, b"unmasked")),
+            CLOSE_PROTOCOL_ERROR,
+        );
+
+        let mut reserved = client_frame(OPCODE_BINARY, true, b"rsv1");
+        reserved[0] |= 0x40;
+        let mut socket = open_socket();
+        closed_with(&socket.read(&reserved), CLOSE_PROTOCOL_ERROR);
+
+        let mut socket = open_socket();
+        closed_with(
+            &socket.read(&client_frame(OPCODE_TEXT, true, b"text")),
+            CLOSE_UNSUPPORTED_DATA,
+        );
+    }
+
+    #[test]
+    fn joins_fragmented_messages() {
+        let mut socket = open_socket();
+        let mut data = client_frame(OPCODE_BINARY, false, b"pac");
+        data.extend(client_frame(OPCODE_PING, true, b"ping"));
+        data.extend(client_frame(OPCODE_CONTINUATION, false, b"k"));
+        data.extend(client_frame(OPCODE_CONTINUATION, true, b"et"));
+        data.extend(client_frame(OPCODE_BINARY, true, b"next"));
+
+        let read = socket.read(&data);
+
+        assert_eq!(read.messages, [b"packet".to_vec(), b"next".to_vec()]);
+        assert_eq!(read.reply, frame(OPCODE_PONG, b"ping"));
+        assert!(!read.closed);
+    }
+
+    #[test]
+    fn rejects_continuations_out_of_order() {
+        let mut socket = open_socket();
+        closed_with(
+            &socket.read(&client_frame(OPCODE_CONTINUATION, true, b"lost")),
+            CLOSE_PROTOCOL_ERROR,
+        );
+
+        let mut socket = open_socket();
+        let mut data = client_frame(OPCODE_BINARY, false, b"first");
+        data.extend(client_frame(OPCODE_BINARY, true, b"second"));
+        closed_with(&socket.read(&data), CLOSE_PROTOCOL_ERROR);
+    }
+
+    #[test]
+    fn rejects_long_or_fragmented_control_frames() {
+        let mut socket = open_socket();
+        closed_with(
+            &socket.read(&client_frame(OPCODE_PING, true, &[0; 126])),
+            CLOSE_PROTOCOL_ERROR,
+        );
+
+        let mut socket = open_socket();
+        closed_with(
+            &socket.read(&client_frame(OPCODE_PING, false, b"ping")),
+            CLOSE_PROTOCOL_ERROR,
+        );
+
+        let mut socket = WebSocket::new(None, 200);
+        socket.read(&upgrade(&[]));
+        let read = socket.read(&client_frame(OPCODE_PING, true, &[7; 125]));
+        assert_eq!(read.reply, frame(OPCODE_PONG, &[7; 125]));
+    }
+
+    #[test]
+    fn rejects_messages_over_the_limit() {
+        let mut socket = open_socket();
+        assert_eq!(
+            socket
+                .read(&client_frame(OPCODE_BINARY, true, &[1; MAX]))
+                .messages,
+            [vec![1; MAX]]
+        );
+        closed_with(
+            &socket.read(&client_frame(OPCODE_BINARY, true, &[1; MAX + 1])),
+            CLOSE_TOO_BIG,
+        );
+
+        // Refused from the header alone, before the payload arrives.
+        let mut socket = open_socket();
+        let huge = client_frame(OPCODE_BINARY, true, &[0; 70_000]);
+        closed_with(&socket.read(&huge[..20]), CLOSE_TOO_BIG);
+
+        let mut socket = open_socket();
+        let mut data = client_frame(OPCODE_BINARY, false, &[1; 10]);
+        data.extend(client_frame(OPCODE_CONTINUATION, true, &[1; 7]));
+        closed_with(&socket.read(&data), CLOSE_TOO_BIG);
+    }
+
+    #[test]
+    fn echoes_close_frames() {
+        let mut socket = open_socket();
+        let read = socket.read(&client_frame(OPCODE_CLOSE, true, &1000u16.to_be_bytes()));
+
+        closed_with(&read, 1000);
+        assert!(socket
+            .read(&client_frame(OPCODE_BINARY, true, b"late"))
+            .messages
+            .is_empty());
+    }
+
+    #[test]
+    fn encodes_frame_lengths() {
+        assert_eq!(frame_header(OPCODE_BINARY, 125), [0x82, 125]);
+        assert_eq!(frame_header(OPCODE_BINARY, 126), [0x82, 126, 0, 126]);
+        assert_eq!(frame_header(OPCODE_BINARY, 0xFFFF), [0x82, 126, 0xFF, 0xFF]);
+        assert_eq!(
+            frame_header(OPCODE_CLOSE, 65536),
+            [0x88, 127, 0, 0, 0, 0, 0, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn splits_length_prefixed_packets() {
+        let mut data = Vec::new();
+
+        for packet in [&b"first"[..], b"", b"third"] {
+            data.extend_from_slice(&(packet.len() as u64).to_le_bytes());
+            data.extend_from_slice(packet);
+        }
+
+        assert_eq!(
+            split_packets(&data).collect::<Vec<_>>(),
+            [&b"first"[..], b"", b"third"]
+        );
+
+        // A short last packet is cut off at the end of the data.
+        data.extend_from_slice(&10u64.to_le_bytes());
+        data.extend_from_slice(b"cut");
+        assert_eq!(split_packets(&data).last(), Some(&b"cut"[..]));
+        assert_eq!(split_packets(&data[..4]).count(), 0);
+    }
+}
@@ -12,7 +12,7 @@ mod storagestruct;
 pub use equipmentstruct::PGEquipItem;
 pub use invstruct::PGInvItem;
 #[allow(unused_imports)]
-pub use logstruct::PGLog;
+pub use logstruct::{LogEntry, PGLog};
 pub use playerstruct::*;
 pub use queries::*;
 #[allow(unused_imports)]
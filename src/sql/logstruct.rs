@@ -1,14 +1,16 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::FromRow;
 
 use crate::gametypes::*;
 
 #[derive(Debug, FromRow)]
 pub struct PGLog {
-    serverid: i16,
-    userid: i64,
-    logtype: LogType,
-    message: String,
-    ipaddress: String,
+    pub serverid: i16,
+    pub userid: i64,
+    pub logtype: LogType,
+    pub message: String,
+    pub ipaddress: String,
 }
 
 impl PGLog {
@@ -28,3 +30,11 @@ impl PGLog {
         }
     }
 }
+
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct LogEntry {
+    pub logtype: LogType,
+    pub message: String,
+    pub ipaddress: String,
+    pub created_on: DateTime<Utc>,
+}
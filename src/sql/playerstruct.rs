@@ -1,3 +1,6 @@
+use crate::gametypes::*;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::FromRow;
 
 #[derive(Debug, PartialEq, Eq, FromRow)]
@@ -7,4 +10,34 @@ pub struct PlayerWithPassword {
     pub current_server: Option<String>,
     pub reconnect_code: Option<String>,
     pub password: String,
+    pub access: UserAccess,
+    pub banned: bool,
+    pub ban_expires: Option<DateTime<Utc>>,
+    pub pass_reset_code: Option<String>,
+}
+
+impl PlayerWithPassword {
+    /// Whether the account is banned at `now`. A ban without an expiry date is permanent.
+    pub fn is_banned(&self, now: DateTime<Utc>) -> bool {
+        self.banned && self.ban_expires.is_none_or(|expires| expires > now)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, FromRow)]
+pub struct BannedPlayer {
+    pub uid: i64,
+    pub current_server: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, FromRow)]
+pub struct AccountInfo {
+    pub uid: i64,
+    pub username: String,
+    pub email: String,
+    pub access: UserAccess,
+    pub banned: bool,
+    pub ban_expires: Option<DateTime<Utc>>,
+    pub password_reset: bool,
+    pub current_server: Option<String>,
+    pub created_on: DateTime<Utc>,
 }
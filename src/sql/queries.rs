@@ -6,8 +6,10 @@ use crate::{
     sql::{integers::Shifting, *},
 };
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use chrono::{DateTime, Utc};
 use password_hash::SaltString;
 use sqlx::{FromRow, PgPool};
+use std::collections::HashSet;
 
 #[derive(Debug, PartialEq, Eq, FromRow)]
 pub struct Check {
@@ -19,6 +21,7 @@ pub async fn initiate(conn: &PgPool) -> Result<()> {
     let queries = [
         LOGTYPE_SCHEMA,
         LOGTYPE_SCHEMA_ALTER,
+        LOGTYPE_SCHEMA_ADMIN,
         USERACCESS_SCHEMA,
         USERACCESS_SCHEMA_ALTER,
         MAP_POSITION_SCHEMA,
@@ -29,6 +32,7 @@ pub async fn initiate(conn: &PgPool) -> Result<()> {
         PLAYER_SEQ_SCHEMA_ALTER,
         PLAYER_SCHEMA,
         PLAYER_SCHEMA_ALTER,
+        PLAYER_SCHEMA_BANS,
         EQUIPMENT_SCHEMA,
         EQUIPMENT_SCHEMA_ALTER,
         INVENTORY_SCHEMA,
@@ -37,6 +41,9 @@ pub async fn initiate(conn: &PgPool) -> Result<()> {
         STORAGE_SCHEMA_ALTER,
         LOGS_SCHEMA,
         LOGS_SCHEMA_ALTER,
+        LOGS_SCHEMA_CREATED_ON,
+        REVOKED_CERTS_SCHEMA,
+        REVOKED_CERTS_SCHEMA_ALTER,
     ];
 
     for quere in queries {
@@ -51,15 +58,7 @@ pub async fn find_player(
     email: &str,
     password: &str,
 ) -> Result<Option<PlayerWithPassword>> {
-    let userdata: Option<PlayerWithPassword> = sqlx::query_as(
-        r#"
-        SELECT uid, username, current_server, reconnect_code, password FROM player
-        WHERE email = $1
-    "#,
-    )
-    .bind(email)
-    .fetch_optional(&storage.pgconn)
-    .await?;
+    let userdata = storage.accounts.find_login(email).await?;
 
     if let Some(userdata) = userdata {
         let hash = match PasswordHash::new(&userdata.password[..]) {
@@ -67,10 +66,13 @@ pub async fn find_player(
             Err(_) => return Err(AscendingError::IncorrectPassword),
         };
 
-        if Argon2::default()
+        let timer = storage.metrics.argon2_timer("verify");
+        let verified = Argon2::default()
             .verify_password(password.as_bytes(), &hash)
-            .is_ok()
-        {
+            .is_ok();
+        timer.observe_duration();
+
+        if verified {
             Ok(Some(userdata))
         } else {
             Err(AscendingError::IncorrectPassword)
@@ -80,12 +82,26 @@ pub async fn find_player(
     }
 }
 
-pub async fn check_existance(storage: &mut Storage, username: &str, email: &str) -> Result<i64> {
+pub async fn find_login(conn: &PgPool, email: &str) -> Result<Option<PlayerWithPassword>> {
+    let userdata: Option<PlayerWithPassword> = sqlx::query_as(
+        r#"
+        SELECT uid, username, current_server, reconnect_code, password, access, banned, ban_expires, pass_reset_code FROM player
+        WHERE email = $1
+    "#,
+    )
+    .bind(email)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(userdata)
+}
+
+pub async fn check_existance(conn: &PgPool, username: &str, email: &str) -> Result<i64> {
     let check: Check =
         sqlx::query_as(r#"SELECT EXISTS(SELECT 1 FROM player WHERE username=$1) as username_check, EXISTS(SELECT 1 FROM player WHERE email=$2) as email_check"#)
             .bind(username)
             .bind(email)
-            .fetch_one(&storage.pgconn)
+            .fetch_one(conn)
             .await?;
 
     if check.username_check {
@@ -97,14 +113,11 @@ pub async fn check_existance(storage: &mut Storage, username: &str, email: &str)
     Ok(0)
 }
 
-pub async fn new_player(
-    storage: &mut Storage,
-    client: &mut Client,
-    player: &Player,
-    password: String,
-) -> Result<i64> {
+/// Hashes a password the way it is stored in the `player` table.
+pub fn hash_password(password: &str) -> String {
     let argon = Argon2::default();
-    let hashed_password = if let Ok(salt) = SaltString::encode_b64(SALT) {
+
+    if let Ok(salt) = SaltString::encode_b64(SALT) {
         if let Ok(hash) = argon.hash_password(password.as_bytes(), &salt) {
             hash.to_string()
         } else {
@@ -112,15 +125,39 @@ pub async fn new_player(
         }
     } else {
         String::from("FailedPasswordHash")
-    };
+    }
+}
 
+pub async fn new_player(
+    storage: &mut Storage,
+    client: &mut Client,
+    player: &Player,
+    password: String,
+) -> Result<i64> {
+    let timer = storage.metrics.argon2_timer("hash");
+    let hashed_password = hash_password(&password);
+    timer.observe_duration();
+
+    storage
+        .accounts
+        .insert_player(&client.addr, player, hashed_password)
+        .await
+}
+
+/// Inserts a new account along with its empty inventory, storage and equipment.
+pub async fn insert_player(
+    conn: &PgPool,
+    address: &str,
+    player: &Player,
+    hashed_password: String,
+) -> Result<i64> {
     let (uid, ): (i64,) =  sqlx::query_as(r#"
         INSERT INTO public.player(
-            username, address, password, itemtimer, deathtimer, vals, spawn, pos, email, sprite, indeath, level, levelexp, resetcount, pk, data, vital, vital_max, access)
+            username, address, password, item_timer, death_timer, vals, spawn, pos, email, sprite, in_death, level, level_exp, resetcount, pk, data, vital, vital_max, access)
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19) RETURNING uid;
         "#)
             .bind(&player.username)
-            .bind(&client.addr)
+            .bind(address)
             .bind(hashed_password)
             .bind(player.item_timer)
             .bind(player.death_timer)
@@ -138,30 +175,30 @@ pub async fn new_player(
             .bind(player.vital)
             .bind(player.vital_max)
             .bind(player.access)
-            .fetch_one(&storage.pgconn).await?;
+            .fetch_one(conn).await?;
 
     let inv_insert = PGInvItem::into_insert_all(PGInvItem::new(&player.inventory, uid));
 
     for script in inv_insert {
-        sqlx::query(&script).execute(&storage.pgconn).await?;
+        sqlx::query(&script).execute(conn).await?;
     }
 
     let storage_insert = PGStorageItem::into_insert_all(PGStorageItem::new(&player.storage, uid));
 
     for script in storage_insert {
-        sqlx::query(&script).execute(&storage.pgconn).await?;
+        sqlx::query(&script).execute(conn).await?;
     }
 
     let equip_insert = PGEquipItem::into_insert_all(PGEquipItem::new(&player.equipment, uid));
 
     for script in equip_insert {
-        sqlx::query(&script).execute(&storage.pgconn).await?;
+        sqlx::query(&script).execute(conn).await?;
     }
 
     Ok(uid)
 }
 
-pub async fn update_address(storage: &mut Storage, user_id: i64, address: String) -> Result<()> {
+pub async fn update_address(conn: &PgPool, user_id: i64, address: String) -> Result<()> {
     sqlx::query(
         r#"
         UPDATE public.player
@@ -171,34 +208,34 @@ pub async fn update_address(storage: &mut Storage, user_id: i64, address: String
     )
     .bind(user_id)
     .bind(&address)
-    .execute(&storage.pgconn)
+    .execute(conn)
     .await?;
 
     Ok(())
 }
 
 pub async fn update_passreset(
-    storage: &mut Storage,
+    conn: &PgPool,
     user_id: i64,
     resetpassword: Option<String>,
 ) -> Result<()> {
     sqlx::query(
         r#"
                 UPDATE public.player
-                SET passresetcode=$2
+                SET pass_reset_code=$2
                 WHERE uid = $1;
             "#,
     )
     .bind(user_id)
     .bind(resetpassword)
-    .execute(&storage.pgconn)
+    .execute(conn)
     .await?;
 
     Ok(())
 }
 
 pub async fn update_reconnect_code(
-    storage: &mut Storage,
+    conn: &PgPool,
     user_id: i64,
     reconnect_code: Option<String>,
 ) -> Result<()> {
@@ -211,8 +248,265 @@ pub async fn update_reconnect_code(
     )
     .bind(user_id)
     .bind(reconnect_code)
-    .execute(&storage.pgconn)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_current_server(
+    conn: &PgPool,
+    user_id: i64,
+    server_name: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+                UPDATE public.player
+                SET current_server=$2
+                WHERE uid = $1;
+            "#,
+    )
+    .bind(user_id)
+    .bind(server_name)
+    .execute(conn)
     .await?;
 
     Ok(())
 }
+
+pub async fn ban_player(
+    conn: &PgPool,
+    username: &str,
+    expires: Option<DateTime<Utc>>,
+) -> Result<Option<BannedPlayer>> {
+    let banned: Option<BannedPlayer> = sqlx::query_as(
+        r#"
+                UPDATE public.player
+                SET banned=true, ban_expires=$2
+                WHERE username = $1
+                RETURNING uid, current_server;
+            "#,
+    )
+    .bind(username)
+    .bind(expires)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(banned)
+}
+
+pub async fn unban_player(conn: &PgPool, uid: i64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+                UPDATE public.player
+                SET banned=false, ban_expires=NULL
+                WHERE uid = $1;
+            "#,
+    )
+    .bind(uid)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn update_access(conn: &PgPool, uid: i64, access: UserAccess) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+                UPDATE public.player
+                SET access=$2
+                WHERE uid = $1;
+            "#,
+    )
+    .bind(uid)
+    .bind(access)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Sets a reset code and drops the relogin code so the player has to reset
+/// their password before they can log in again.
+pub async fn force_password_reset(conn: &PgPool, uid: i64, code: &str) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+                UPDATE public.player
+                SET pass_reset_code=$2, reconnect_code=NULL
+                WHERE uid = $1;
+            "#,
+    )
+    .bind(uid)
+    .bind(code)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Sets the new password of a forced reset and clears its code.
+pub async fn complete_password_reset(
+    conn: &PgPool,
+    uid: i64,
+    hashed_password: String,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+                UPDATE public.player
+                SET password=$2, pass_reset_code=NULL
+                WHERE uid = $1;
+            "#,
+    )
+    .bind(uid)
+    .bind(hashed_password)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+const ACCOUNT_INFO_SELECT: &str = r#"
+        SELECT uid, username, email, access, banned, ban_expires, pass_reset_code IS NOT NULL AS password_reset, current_server, created_on
+        FROM player
+    "#;
+
+pub async fn find_account(conn: &PgPool, uid: i64) -> Result<Option<AccountInfo>> {
+    let account: Option<AccountInfo> =
+        sqlx::query_as(&format!("{} WHERE uid = $1", ACCOUNT_INFO_SELECT))
+            .bind(uid)
+            .fetch_optional(conn)
+            .await?;
+
+    Ok(account)
+}
+
+pub async fn find_account_by_name(conn: &PgPool, username: &str) -> Result<Option<AccountInfo>> {
+    let account: Option<AccountInfo> =
+        sqlx::query_as(&format!("{} WHERE username = $1", ACCOUNT_INFO_SELECT))
+            .bind(username)
+            .fetch_optional(conn)
+            .await?;
+
+    Ok(account)
+}
+
+/// Accounts that are currently logged in to a game server.
+pub async fn find_online_accounts(conn: &PgPool, limit: i64) -> Result<Vec<AccountInfo>> {
+    let accounts: Vec<AccountInfo> = sqlx::query_as(&format!(
+        "{} WHERE current_server IS NOT NULL ORDER BY uid LIMIT $1",
+        ACCOUNT_INFO_SELECT
+    ))
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(accounts)
+}
+
+/// Matches part of a username or email, returning at most `limit` accounts.
+pub async fn search_accounts(conn: &PgPool, search: &str, limit: i64) -> Result<Vec<AccountInfo>> {
+    let pattern = format!(
+        "%{}%",
+        search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let accounts: Vec<AccountInfo> = sqlx::query_as(&format!(
+        "{} WHERE username ILIKE $1 OR email ILIKE $1 ORDER BY uid LIMIT $2",
+        ACCOUNT_INFO_SELECT
+    ))
+    .bind(pattern)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(accounts)
+}
+
+pub async fn insert_log(conn: &PgPool, log: &PGLog) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO public.logs(serverid, userid, logtype, message, ipaddress)
+            VALUES ($1, $2, $3, $4, $5);
+        "#,
+    )
+    .bind(log.serverid)
+    .bind(log.userid)
+    .bind(log.logtype)
+    .bind(&log.message)
+    .bind(&log.ipaddress)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_logs(
+    conn: &PgPool,
+    uid: i64,
+    logtype: LogType,
+    limit: i64,
+) -> Result<Vec<LogEntry>> {
+    let logs: Vec<LogEntry> = sqlx::query_as(
+        r#"
+        SELECT logtype, message, ipaddress, created_on FROM logs
+        WHERE userid = $1 AND logtype = $2
+        ORDER BY created_on DESC
+        LIMIT $3
+    "#,
+    )
+    .bind(uid)
+    .bind(logtype)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(logs)
+}
+
+pub async fn find_revoked_certs(conn: &PgPool) -> Result<HashSet<String>> {
+    let fingerprints: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT fingerprint FROM public.revoked_certs;
+    "#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(fingerprints
+        .into_iter()
+        .map(|(fingerprint,)| fingerprint)
+        .collect())
+}
+
+pub async fn is_cert_revoked(conn: &PgPool, fingerprint: &str) -> Result<bool> {
+    let (revoked,): (bool,) = sqlx::query_as(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM public.revoked_certs WHERE fingerprint = $1);
+    "#,
+    )
+    .bind(fingerprint)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(revoked)
+}
+
+/// Returns false when the fingerprint was already revoked.
+pub async fn revoke_cert(conn: &PgPool, fingerprint: &str, reason: &str) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO public.revoked_certs(fingerprint, reason)
+            VALUES ($1, $2)
+            ON CONFLICT (fingerprint) DO NOTHING;
+        "#,
+    )
+    .bind(fingerprint)
+    .bind(reason)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
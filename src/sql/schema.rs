@@ -41,6 +41,8 @@ CREATE TABLE IF NOT EXISTS public.player
     reconnect_code text COLLATE pg_catalog.\"default\",
     access \"user_access\" NOT NULL,
     current_server text COLLATE pg_catalog.\"default\",
+    banned boolean NOT NULL DEFAULT false,
+    ban_expires timestamp with time zone,
     created_on timestamp with time zone NOT NULL DEFAULT now(),
     CONSTRAINT player_pkey PRIMARY KEY (uid),
     CONSTRAINT email UNIQUE (email),
@@ -59,6 +61,13 @@ ALTER TABLE IF EXISTS public.player
     OWNER to postgres;
 ";
 
+#[rustfmt::skip]
+pub const PLAYER_SCHEMA_BANS: &str = "
+ALTER TABLE IF EXISTS public.player
+    ADD COLUMN IF NOT EXISTS banned boolean NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS ban_expires timestamp with time zone;
+";
+
 #[rustfmt::skip]
 pub const EQUIPMENT_SCHEMA: &str = "
 CREATE TABLE IF NOT EXISTS public.equipment
@@ -139,7 +148,8 @@ CREATE TABLE IF NOT EXISTS public.logs
     userid bigint NOT NULL,
     logtype \"log_type\" NOT NULL,
     message text COLLATE pg_catalog.\"default\" NOT NULL,
-    ipaddress text COLLATE pg_catalog.\"default\" NOT NULL
+    ipaddress text COLLATE pg_catalog.\"default\" NOT NULL,
+    created_on timestamp with time zone NOT NULL DEFAULT now()
 )
 
 WITH (
@@ -153,3 +163,31 @@ pub const LOGS_SCHEMA_ALTER: &str = "
 ALTER TABLE IF EXISTS public.logs
     OWNER to postgres
 ";
+
+#[rustfmt::skip]
+pub const LOGS_SCHEMA_CREATED_ON: &str = "
+ALTER TABLE IF EXISTS public.logs
+    ADD COLUMN IF NOT EXISTS created_on timestamp with time zone NOT NULL DEFAULT now();
+";
+
+#[rustfmt::skip]
+pub const REVOKED_CERTS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.revoked_certs
+(
+    fingerprint text COLLATE pg_catalog.\"default\" NOT NULL,
+    reason text COLLATE pg_catalog.\"default\" NOT NULL,
+    revoked_on timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT revoked_certs_pkey PRIMARY KEY (fingerprint)
+)
+
+WITH (
+    FILLFACTOR = 70
+)
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const REVOKED_CERTS_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.revoked_certs
+    OWNER to postgres;
+";
@@ -2,7 +2,7 @@
 pub const LOGTYPE_SCHEMA: &str = "
 DO $$ BEGIN
     CREATE TYPE public.\"log_type\" AS ENUM
-        ('Login', 'Logout', 'Item', 'Warning', 'Error');
+        ('Login', 'Logout', 'Item', 'Warning', 'Error', 'Admin');
 EXCEPTION
     WHEN duplicate_object THEN null;
 END $$;
@@ -14,6 +14,12 @@ ALTER TYPE public.\"log_type\"
     OWNER TO postgres;
 ";
 
+#[rustfmt::skip]
+pub const LOGTYPE_SCHEMA_ADMIN: &str = "
+ALTER TYPE public.\"log_type\"
+    ADD VALUE IF NOT EXISTS 'Admin';
+";
+
 #[rustfmt::skip]
 pub const USERACCESS_SCHEMA: &str = "
 DO $$ BEGIN
@@ -0,0 +1,98 @@
+mod memory;
+mod postgres;
+
+pub use memory::*;
+pub use postgres::*;
+
+use crate::{
+    containers::{AccountStoreKind, Config},
+    gametypes::*,
+    players::Player,
+    sql::{AccountInfo, BannedPlayer, LogEntry, PGLog, PlayerWithPassword},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::{collections::HashSet, sync::Arc};
+
+/// Accounts, their sessions and logs as the login server, console and admin API use
+/// them. `PgAccountStore` keeps them in the database, `MemoryAccountStore` in memory for
+/// development, tests and LAN games without one.
+#[async_trait]
+pub trait AccountStore: Send + Sync {
+    /// The account with `email` and its password hash, for logging in.
+    async fn find_login(&self, email: &str) -> Result<Option<PlayerWithPassword>>;
+
+    async fn find_account(&self, uid: i64) -> Result<Option<AccountInfo>>;
+
+    async fn find_account_by_name(&self, username: &str) -> Result<Option<AccountInfo>>;
+
+    /// Matches part of a username or email, ignoring case, returning at most `limit`
+    /// accounts by uid.
+    async fn search_accounts(&self, search: &str, limit: i64) -> Result<Vec<AccountInfo>>;
+
+    /// Accounts that are currently logged in to a game server.
+    async fn find_online_accounts(&self, limit: i64) -> Result<Vec<AccountInfo>>;
+
+    /// 1 when `username` is taken, 2 when `email` is, otherwise 0.
+    async fn check_existance(&self, username: &str, email: &str) -> Result<i64>;
+
+    /// Creates an account and returns its uid.
+    async fn insert_player(
+        &self,
+        address: &str,
+        player: &Player,
+        hashed_password: String,
+    ) -> Result<i64>;
+
+    /// Bans the account named `username` until `expires`, or for good when None.
+    async fn ban_player(
+        &self,
+        username: &str,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<Option<BannedPlayer>>;
+
+    /// Returns false when there is no such account, as do the updates below.
+    async fn unban_player(&self, uid: i64) -> Result<bool>;
+
+    async fn update_access(&self, uid: i64, access: UserAccess) -> Result<bool>;
+
+    /// Sets a reset code and drops the relogin code so the player has to reset
+    /// their password before they can log in again.
+    async fn force_password_reset(&self, uid: i64, code: &str) -> Result<bool>;
+
+    /// Sets the password the player chose and clears the reset code.
+    async fn complete_password_reset(&self, uid: i64, hashed_password: String) -> Result<()>;
+
+    /// The code a player sends to log in again while still on a game server.
+    async fn update_reconnect_code(&self, uid: i64, reconnect_code: Option<String>) -> Result<()>;
+
+    /// The game server a player is on, written by game servers as players join and leave.
+    async fn update_current_server(&self, uid: i64, server_name: Option<&str>) -> Result<()>;
+
+    async fn insert_log(&self, log: &PGLog) -> Result<()>;
+
+    /// The newest `limit` logs of `logtype` for an account.
+    async fn find_logs(&self, uid: i64, logtype: LogType, limit: i64) -> Result<Vec<LogEntry>>;
+
+    async fn find_revoked_certs(&self) -> Result<HashSet<String>>;
+
+    async fn is_cert_revoked(&self, fingerprint: &str) -> Result<bool>;
+
+    /// Returns false when the fingerprint was already revoked.
+    async fn revoke_cert(&self, fingerprint: &str, reason: &str) -> Result<bool>;
+
+    /// Waits for queries still running, such as audit log writes, when shutting down.
+    async fn close(&self);
+}
+
+/// Opens the store `account_store` names, creating the database tables if needed.
+pub async fn open_account_store(config: &Config) -> Result<Arc<dyn AccountStore>> {
+    Ok(match config.account_store {
+        AccountStoreKind::Postgres => Arc::new(PgAccountStore::connect(config).await?),
+        AccountStoreKind::Memory => {
+            warn!("Accounts are kept in memory and will be lost when the server stops.");
+            Arc::new(MemoryAccountStore::default())
+        }
+    })
+}
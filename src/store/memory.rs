@@ -0,0 +1,461 @@
+use super::AccountStore;
+use crate::{
+    gametypes::*,
+    players::Player,
+    sql::{AccountInfo, BannedPlayer, LogEntry, PGLog, PlayerWithPassword},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::sync::Mutex;
+
+/// Accounts kept in memory, for development, tests and LAN games without a database.
+/// Nothing survives a restart.
+#[derive(Debug, Default)]
+pub struct MemoryAccountStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    accounts: BTreeMap<i64, MemoryAccount>,
+    last_uid: i64,
+    logs: Vec<(i64, LogEntry)>,
+    /// Revocation reasons by certificate fingerprint.
+    revoked_certs: HashMap<String, String>,
+}
+
+/// The columns of the `player` table the login server uses.
+#[derive(Clone, Debug)]
+struct MemoryAccount {
+    uid: i64,
+    username: String,
+    email: String,
+    password: String,
+    address: String,
+    access: UserAccess,
+    banned: bool,
+    ban_expires: Option<DateTime<Utc>>,
+    pass_reset_code: Option<String>,
+    reconnect_code: Option<String>,
+    current_server: Option<String>,
+    created_on: DateTime<Utc>,
+}
+
+impl MemoryAccount {
+    fn info(&self) -> AccountInfo {
+        AccountInfo {
+            uid: self.uid,
+            username: self.username.clone(),
+            email: self.email.clone(),
+            access: self.access,
+            banned: self.banned,
+            ban_expires: self.ban_expires,
+            password_reset: self.pass_reset_code.is_some(),
+            current_server: self.current_server.clone(),
+            created_on: self.created_on,
+        }
+    }
+}
+
+impl MemoryData {
+    fn find_by(&self, matches: impl Fn(&MemoryAccount) -> bool) -> Option<&MemoryAccount> {
+        self.accounts.values().find(|account| matches(account))
+    }
+
+    fn list_by(&self, limit: i64, matches: impl Fn(&MemoryAccount) -> bool) -> Vec<AccountInfo> {
+        self.accounts
+            .values()
+            .filter(|account| matches(account))
+            .take(limit.max(0) as usize)
+            .map(MemoryAccount::info)
+            .collect()
+    }
+
+    /// Applies `update` to the account with `uid`, returning false when there is none.
+    fn update(&mut self, uid: i64, update: impl FnOnce(&mut MemoryAccount)) -> bool {
+        self.accounts.get_mut(&uid).map(update).is_some()
+    }
+}
+
+#[async_trait]
+impl AccountStore for MemoryAccountStore {
+    async fn find_login(&self, email: &str) -> Result<Option<PlayerWithPassword>> {
+        let data = self.data.lock().await;
+
+        Ok(data
+            .find_by(|account| account.email == email)
+            .map(|account| PlayerWithPassword {
+                uid: account.uid,
+                username: account.username.clone(),
+                current_server: account.current_server.clone(),
+                reconnect_code: account.reconnect_code.clone(),
+                password: account.password.clone(),
+                access: account.access,
+                banned: account.banned,
+                ban_expires: account.ban_expires,
+                pass_reset_code: account.pass_reset_code.clone(),
+            }))
+    }
+
+    async fn find_account(&self, uid: i64) -> Result<Option<AccountInfo>> {
+        let data = self.data.lock().await;
+
+        Ok(data.accounts.get(&uid).map(MemoryAccount::info))
+    }
+
+    async fn find_account_by_name(&self, username: &str) -> Result<Option<AccountInfo>> {
+        let data = self.data.lock().await;
+
+        Ok(data
+            .find_by(|account| account.username == username)
+            .map(MemoryAccount::info))
+    }
+
+    async fn search_accounts(&self, search: &str, limit: i64) -> Result<Vec<AccountInfo>> {
+        let search = search.to_lowercase();
+        let data = self.data.lock().await;
+
+        Ok(data.list_by(limit, |account| {
+            account.username.to_lowercase().contains(&search)
+                || account.email.to_lowercase().contains(&search)
+        }))
+    }
+
+    async fn find_online_accounts(&self, limit: i64) -> Result<Vec<AccountInfo>> {
+        let data = self.data.lock().await;
+
+        Ok(data.list_by(limit, |account| account.current_server.is_some()))
+    }
+
+    async fn check_existance(&self, username: &str, email: &str) -> Result<i64> {
+        let data = self.data.lock().await;
+
+        if data
+            .find_by(|account| account.username == username)
+            .is_some()
+        {
+            return Ok(1);
+        } else if data.find_by(|account| account.email == email).is_some() {
+            return Ok(2);
+        }
+
+        Ok(0)
+    }
+
+    async fn insert_player(
+        &self,
+        address: &str,
+        player: &Player,
+        hashed_password: String,
+    ) -> Result<i64> {
+        let mut data = self.data.lock().await;
+
+        // The table's unique constraints.
+        if data
+            .find_by(|account| account.username == player.username || account.email == player.email)
+            .is_some()
+        {
+            return Err(AscendingError::RegisterFail);
+        }
+
+        data.last_uid += 1;
+
+        let uid = data.last_uid;
+
+        data.accounts.insert(
+            uid,
+            MemoryAccount {
+                uid,
+                username: player.username.clone(),
+                email: player.email.clone(),
+                password: hashed_password,
+                address: address.to_string(),
+                access: player.access,
+                banned: false,
+                ban_expires: None,
+                pass_reset_code: None,
+                reconnect_code: None,
+                current_server: None,
+                created_on: Utc::now(),
+            },
+        );
+
+        Ok(uid)
+    }
+
+    async fn ban_player(
+        &self,
+        username: &str,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<Option<BannedPlayer>> {
+        let mut data = self.data.lock().await;
+
+        Ok(data
+            .accounts
+            .values_mut()
+            .find(|account| account.username == username)
+            .map(|account| {
+                account.banned = true;
+                account.ban_expires = expires;
+
+                BannedPlayer {
+                    uid: account.uid,
+                    current_server: account.current_server.clone(),
+                }
+            }))
+    }
+
+    async fn unban_player(&self, uid: i64) -> Result<bool> {
+        let mut data = self.data.lock().await;
+
+        Ok(data.update(uid, |account| {
+            account.banned = false;
+            account.ban_expires = None;
+        }))
+    }
+
+    async fn update_access(&self, uid: i64, access: UserAccess) -> Result<bool> {
+        let mut data = self.data.lock().await;
+
+        Ok(data.update(uid, |account| account.access = access))
+    }
+
+    async fn force_password_reset(&self, uid: i64, code: &str) -> Result<bool> {
+        let mut data = self.data.lock().await;
+
+        Ok(data.update(uid, |account| {
+            account.pass_reset_code = Some(code.to_string());
+            account.reconnect_code = None;
+        }))
+    }
+
+    async fn complete_password_reset(&self, uid: i64, hashed_password: String) -> Result<()> {
+        let mut data = self.data.lock().await;
+
+        data.update(uid, |account| {
+            account.password = hashed_password;
+            account.pass_reset_code = None;
+        });
+        Ok(())
+    }
+
+    async fn update_reconnect_code(&self, uid: i64, reconnect_code: Option<String>) -> Result<()> {
+        let mut data = self.data.lock().await;
+
+        data.update(uid, |account| account.reconnect_code = reconnect_code);
+        Ok(())
+    }
+
+    async fn update_current_server(&self, uid: i64, server_name: Option<&str>) -> Result<()> {
+        let mut data = self.data.lock().await;
+
+        data.update(uid, |account| {
+            account.current_server = server_name.map(str::to_string)
+        });
+        Ok(())
+    }
+
+    async fn insert_log(&self, log: &PGLog) -> Result<()> {
+        let mut data = self.data.lock().await;
+
+        data.logs.push((
+            log.userid,
+            LogEntry {
+                logtype: log.logtype,
+                message: log.message.clone(),
+                ipaddress: log.ipaddress.clone(),
+                created_on: Utc::now(),
+            },
+        ));
+        Ok(())
+    }
+
+    async fn find_logs(&self, uid: i64, logtype: LogType, limit: i64) -> Result<Vec<LogEntry>> {
+        let data = self.data.lock().await;
+
+        Ok(data
+            .logs
+            .iter()
+            .rev()
+            .filter(|(userid, log)| *userid == uid && log.logtype == logtype)
+            .take(limit.max(0) as usize)
+            .map(|(_, log)| log.clone())
+            .collect())
+    }
+
+    async fn find_revoked_certs(&self) -> Result<HashSet<String>> {
+        let data = self.data.lock().await;
+
+        Ok(data.revoked_certs.keys().cloned().collect())
+    }
+
+    async fn is_cert_revoked(&self, fingerprint: &str) -> Result<bool> {
+        let data = self.data.lock().await;
+
+        Ok(data.revoked_certs.contains_key(fingerprint))
+    }
+
+    async fn revoke_cert(&self, fingerprint: &str, reason: &str) -> Result<bool> {
+        let mut data = self.data.lock().await;
+
+        if data.revoked_certs.contains_key(fingerprint) {
+            return Ok(false);
+        }
+
+        data.revoked_certs
+            .insert(fingerprint.to_string(), reason.to_string());
+        Ok(true)
+    }
+
+    async fn close(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(username: &str, email: &str) -> Player {
+        Player {
+            username: username.into(),
+            email: email.into(),
+            ..Player::default()
+        }
+    }
+
+    async fn store_with(usernames: &[&str]) -> MemoryAccountStore {
+        let store = MemoryAccountStore::default();
+
+        for username in usernames {
+            let email = format!("{}@example.com", username.to_lowercase());
+
+            store
+                .insert_player("127.0.0.1", &player(username, &email), "hash".into())
+                .await
+                .unwrap();
+        }
+
+        store
+    }
+
+    #[tokio::test]
+    async fn creates_and_finds_accounts() {
+        let store = store_with(&["Alice", "Bob"]).await;
+
+        let login = store.find_login("bob@example.com").await.unwrap().unwrap();
+
+        assert_eq!(login.uid, 2);
+        assert_eq!(login.username, "Bob");
+        assert_eq!(login.password, "hash");
+        assert!(store
+            .find_login("carol@example.com")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            store
+                .find_account_by_name("Alice")
+                .await
+                .unwrap()
+                .unwrap()
+                .uid,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_taken_usernames_and_emails() {
+        let store = store_with(&["Alice"]).await;
+
+        assert_eq!(
+            store
+                .check_existance("Alice", "new@example.com")
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            store
+                .check_existance("New", "alice@example.com")
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store
+                .check_existance("New", "new@example.com")
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(store
+            .insert_player("", &player("Alice", "other@example.com"), "hash".into())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn searches_names_and_emails_ignoring_case() {
+        let store = store_with(&["Alice", "Alicia", "Bob"]).await;
+
+        let found = store.search_accounts("ALI", 10).await.unwrap();
+        let names: Vec<&str> = found.iter().map(|info| info.username.as_str()).collect();
+
+        assert_eq!(names, ["Alice", "Alicia"]);
+        assert_eq!(store.search_accounts("example", 2).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn tracks_bans_and_sessions() {
+        let store = store_with(&["Alice"]).await;
+
+        store.update_current_server(1, Some("World")).await.unwrap();
+        store
+            .update_reconnect_code(1, Some("code".into()))
+            .await
+            .unwrap();
+
+        let banned = store.ban_player("Alice", None).await.unwrap().unwrap();
+
+        assert_eq!(banned.current_server.as_deref(), Some("World"));
+        assert!(store
+            .find_login("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_banned(Utc::now()));
+        assert_eq!(store.find_online_accounts(10).await.unwrap().len(), 1);
+
+        assert!(store.unban_player(1).await.unwrap());
+        assert!(store.force_password_reset(1, "reset").await.unwrap());
+
+        let login = store
+            .find_login("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(!login.is_banned(Utc::now()));
+        assert_eq!(login.reconnect_code, None);
+        assert!(!store.unban_player(2).await.unwrap());
+        assert!(store.ban_player("Nobody", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn returns_the_newest_logs_first() {
+        let store = store_with(&["Alice"]).await;
+
+        for message in ["first", "second", "third"] {
+            let log = PGLog::new(0, 1, LogType::Login, message.into(), "127.0.0.1".into());
+            store.insert_log(&log).await.unwrap();
+        }
+
+        let log = PGLog::new(0, 1, LogType::Admin, "admin".into(), String::new());
+        store.insert_log(&log).await.unwrap();
+
+        let logs = store.find_logs(1, LogType::Login, 2).await.unwrap();
+        let messages: Vec<&str> = logs.iter().map(|log| log.message.as_str()).collect();
+
+        assert_eq!(messages, ["third", "second"]);
+    }
+}
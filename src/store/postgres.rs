@@ -0,0 +1,127 @@
+use super::AccountStore;
+use crate::{
+    containers::{establish_connection, Config},
+    gametypes::*,
+    players::Player,
+    sql::{self, AccountInfo, BannedPlayer, LogEntry, PGLog, PlayerWithPassword},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+/// Accounts in the PostgreSQL database, through the queries in `sql`.
+#[derive(Clone, Debug)]
+pub struct PgAccountStore {
+    conn: PgPool,
+}
+
+impl PgAccountStore {
+    pub fn new(conn: PgPool) -> Self {
+        Self { conn }
+    }
+
+    /// Connects with the database settings and creates any missing tables.
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let conn = establish_connection(config).await?;
+        sql::initiate(&conn).await?;
+
+        Ok(Self::new(conn))
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.conn
+    }
+}
+
+#[async_trait]
+impl AccountStore for PgAccountStore {
+    async fn find_login(&self, email: &str) -> Result<Option<PlayerWithPassword>> {
+        sql::find_login(&self.conn, email).await
+    }
+
+    async fn find_account(&self, uid: i64) -> Result<Option<AccountInfo>> {
+        sql::find_account(&self.conn, uid).await
+    }
+
+    async fn find_account_by_name(&self, username: &str) -> Result<Option<AccountInfo>> {
+        sql::find_account_by_name(&self.conn, username).await
+    }
+
+    async fn search_accounts(&self, search: &str, limit: i64) -> Result<Vec<AccountInfo>> {
+        sql::search_accounts(&self.conn, search, limit).await
+    }
+
+    async fn find_online_accounts(&self, limit: i64) -> Result<Vec<AccountInfo>> {
+        sql::find_online_accounts(&self.conn, limit).await
+    }
+
+    async fn check_existance(&self, username: &str, email: &str) -> Result<i64> {
+        sql::check_existance(&self.conn, username, email).await
+    }
+
+    async fn insert_player(
+        &self,
+        address: &str,
+        player: &Player,
+        hashed_password: String,
+    ) -> Result<i64> {
+        sql::insert_player(&self.conn, address, player, hashed_password).await
+    }
+
+    async fn ban_player(
+        &self,
+        username: &str,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<Option<BannedPlayer>> {
+        sql::ban_player(&self.conn, username, expires).await
+    }
+
+    async fn unban_player(&self, uid: i64) -> Result<bool> {
+        sql::unban_player(&self.conn, uid).await
+    }
+
+    async fn update_access(&self, uid: i64, access: UserAccess) -> Result<bool> {
+        sql::update_access(&self.conn, uid, access).await
+    }
+
+    async fn force_password_reset(&self, uid: i64, code: &str) -> Result<bool> {
+        sql::force_password_reset(&self.conn, uid, code).await
+    }
+
+    async fn complete_password_reset(&self, uid: i64, hashed_password: String) -> Result<()> {
+        sql::complete_password_reset(&self.conn, uid, hashed_password).await
+    }
+
+    async fn update_reconnect_code(&self, uid: i64, reconnect_code: Option<String>) -> Result<()> {
+        sql::update_reconnect_code(&self.conn, uid, reconnect_code).await
+    }
+
+    async fn update_current_server(&self, uid: i64, server_name: Option<&str>) -> Result<()> {
+        sql::update_current_server(&self.conn, uid, server_name).await
+    }
+
+    async fn insert_log(&self, log: &PGLog) -> Result<()> {
+        sql::insert_log(&self.conn, log).await
+    }
+
+    async fn find_logs(&self, uid: i64, logtype: LogType, limit: i64) -> Result<Vec<LogEntry>> {
+        sql::find_logs(&self.conn, uid, logtype, limit).await
+    }
+
+    async fn find_revoked_certs(&self) -> Result<HashSet<String>> {
+        sql::find_revoked_certs(&self.conn).await
+    }
+
+    async fn is_cert_revoked(&self, fingerprint: &str) -> Result<bool> {
+        sql::is_cert_revoked(&self.conn, fingerprint).await
+    }
+
+    async fn revoke_cert(&self, fingerprint: &str, reason: &str) -> Result<bool> {
+        sql::revoke_cert(&self.conn, fingerprint, reason).await
+    }
+
+    async fn close(&self) {
+        self.conn.close().await;
+    }
+}
@@ -0,0 +1,71 @@
+mod account;
+mod end_to_end;
+mod fake_client;
+mod fake_game_server;
+mod handler;
+mod server;
+
+pub use account::*;
+pub use fake_client::*;
+pub use fake_game_server::*;
+pub use handler::*;
+pub use server::*;
+
+use crate::socket::*;
+use mmap_bytey::{MByteBuffer, MByteBufferRead, MByteBufferWrite, BUFFER_SIZE};
+use std::io::{Read, Write};
+
+/// Writes one packet in the frame format the server reads.
+fn write_packet<P: Packet + MByteBufferWrite>(stream: &mut impl Write, packet: P) {
+    let mut buf = encode(packet).expect("packet should encode");
+
+    stream
+        .write_all(buf.as_slice())
+        .and_then(|_| stream.flush())
+        .expect("failed to send a packet");
+}
+
+/// Reads the next packet ID and body, buffering whatever arrives after it in `pending`.
+/// Panics when nothing arrives within `TIMEOUT` or the connection closes.
+fn read_packet<Id: MByteBufferRead>(
+    stream: &mut impl Read,
+    pending: &mut Vec<u8>,
+) -> (Id, MByteBuffer) {
+    let codec = FrameCodec::new(BUFFER_SIZE);
+
+    loop {
+        if let Some(frame) = codec.decode(pending).expect("server sent an invalid frame") {
+            assert!(!frame.compressed, "server compressed a frame unasked");
+
+            let mut packet = MByteBuffer::new().unwrap();
+            packet.write_slice(frame.body).unwrap();
+            packet.move_cursor_to_start();
+
+            let used = LENGTH_PREFIX + frame.body.len();
+            pending.drain(..used);
+
+            let id = packet
+                .read::<Id>()
+                .expect("server sent an unknown packet ID");
+            return (id, packet);
+        }
+
+        let mut chunk = [0; 4096];
+
+        match stream.read(&mut chunk) {
+            Ok(0) => panic!("server closed the connection"),
+            Ok(read) => pending.extend_from_slice(&chunk[..read]),
+            Err(e) => panic!("no packet received: {}", e),
+        }
+    }
+}
+
+/// Reads the body of a packet, panicking when the server sent another one.
+fn expect_packet<P>(id: P::Id, mut data: MByteBuffer) -> P
+where
+    P: Packet + MByteBufferRead,
+    P::Id: PartialEq,
+{
+    assert_eq!(id, P::ID, "server sent {:?} instead of {:?}", id, P::ID);
+    decode::<P>(&mut data).expect("packet should decode")
+}
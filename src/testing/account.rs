@@ -0,0 +1,62 @@
+use crate::{gametypes::*, socket::*};
+use rand::distributions::{Alphanumeric, DistString};
+
+pub const TEST_PASSWORD: &str = "Passw0rd!";
+
+/// Credentials no other test uses, so tests can share a database.
+#[derive(Clone, Debug)]
+pub struct Account {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl Account {
+    pub fn unique() -> Self {
+        let id = Alphanumeric
+            .sample_string(&mut rand::thread_rng(), 16)
+            .to_lowercase();
+
+        Self {
+            username: format!("test_{}", id),
+            email: format!("{}@example.com", id),
+            password: TEST_PASSWORD.into(),
+        }
+    }
+
+    pub fn register(&self, server_name: &str) -> from_client::Register {
+        from_client::Register {
+            username: self.username.clone(),
+            password: self.password.clone(),
+            email: self.email.clone(),
+            sprite_id: 0,
+            app_major: APP_MAJOR as u16,
+            app_minor: APP_MINOR as u16,
+            app_revision: APP_REVISION as u16,
+            server_name: server_name.into(),
+        }
+    }
+
+    /// Logs in to `server_name`. `reconnect_code` is the relogin code from the last
+    /// login, or empty.
+    pub fn login(&self, server_name: &str, reconnect_code: &str) -> from_client::Login {
+        from_client::Login {
+            email: self.email.clone(),
+            password: self.password.clone(),
+            app_major: APP_MAJOR as u16,
+            app_minor: APP_MINOR as u16,
+            app_revision: APP_REVISION as u16,
+            reconnect_code: reconnect_code.into(),
+            server_name: server_name.into(),
+        }
+    }
+
+    /// Completes a forced reset with `reset_code`, changing the password to `password`.
+    pub fn reset_password(&self, reset_code: &str, password: &str) -> from_client::PasswordReset {
+        from_client::PasswordReset {
+            email: self.email.clone(),
+            reset_code: reset_code.into(),
+            password: password.into(),
+        }
+    }
+}
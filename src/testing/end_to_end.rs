@@ -0,0 +1,396 @@
+use super::*;
+use crate::{
+    certs::fingerprint,
+    containers::{ClientAuth, ProxyProtocolConfig, WebSocketConfig},
+    gametypes::*,
+};
+use rustls::{pki_types::ServerName, ClientConnection};
+use std::{
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+/// Starts a client that has agreed on the protocol and sees `server_name` listed.
+fn client_for(server: &TestServer, server_name: &str) -> FakeClient {
+    let mut client = FakeClient::connect(server);
+
+    client.hello();
+    client.wait_for_servers(|servers| servers.iter().any(|info| info.name == server_name));
+    client
+}
+
+/// Registers a new account for `server_name` and returns it with its login.
+fn register(server: &TestServer, server_name: &str) -> (Account, to_client::Login) {
+    let account = Account::unique();
+    let mut client = FakeClient::connect(server);
+
+    client.hello();
+    client.send(account.register(server_name));
+
+    (account, client.expect::<to_client::Login>())
+}
+
+#[test]
+fn registers_new_accounts() {
+    let server = TestServer::start();
+    let account = Account::unique();
+    let mut client = FakeClient::connect(&server);
+
+    client.hello();
+    client.send(account.register("Register"));
+
+    let login = client.expect::<to_client::Login>();
+
+    assert!(!login.token.is_empty());
+    assert!(!login.relogin_code.is_empty());
+
+    client.send(account.register("Register"));
+
+    assert_eq!(client.expect_error(), ErrorCode::UsernameTaken);
+
+    let mut client = FakeClient::connect(&server);
+    let taken_email = Account {
+        username: Account::unique().username,
+        ..account
+    };
+
+    client.hello();
+    client.send(taken_email.register("Register"));
+
+    assert_eq!(client.expect_error(), ErrorCode::EmailTaken);
+}
+
+#[test]
+fn logs_in_to_listed_game_servers() {
+    let server = TestServer::start();
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.announce("Login");
+
+    let (account, _) = register(&server, "Login");
+
+    let mut client = client_for(&server, "Login");
+    client.send(account.login("Login", ""));
+
+    let login = client.expect::<to_client::Login>();
+    let verification = game_server.verify(&login.token);
+
+    assert!(verification.verified);
+
+    let mut client = client_for(&server, "Login");
+    client.send(
+        Account {
+            password: "Wr0ngPassword".into(),
+            ..account.clone()
+        }
+        .login("Login", ""),
+    );
+
+    assert_eq!(client.expect_error(), ErrorCode::BadCredentials);
+
+    let mut client = client_for(&server, "Login");
+    client.send(account.login("Elsewhere", ""));
+
+    assert_eq!(client.expect_error(), ErrorCode::UnknownServer);
+}
+
+#[test]
+fn lists_connected_game_servers() {
+    let server = TestServer::start();
+    let mut first = FakeGameServer::connect(&server);
+    let mut second = FakeGameServer::connect(&server);
+    first.announce("First");
+    second.announce("Second");
+
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+
+    let servers = client.wait_for_servers(|servers| servers.len() == 2);
+    let first_info = servers.iter().find(|info| info.name == "First").unwrap();
+
+    assert_eq!(first_info.players_on, 0);
+    assert_eq!(first_info.max_players, TEST_MAX_PLAYERS);
+    assert!(servers.iter().any(|info| info.name == "Second"));
+
+    first.send(from_game_server::UpdateCount {
+        players_on: 7,
+        max_players: TEST_MAX_PLAYERS,
+    });
+    client.wait_for_servers(|servers| {
+        servers
+            .iter()
+            .any(|info| info.name == "First" && info.players_on == 7)
+    });
+
+    drop(second);
+
+    let servers = client.wait_for_servers(|servers| servers.len() == 1);
+
+    assert_eq!(servers[0].name, "First");
+}
+
+#[test]
+fn verifies_tokens_for_their_game_server_only() {
+    let server = TestServer::start();
+    let mut home = FakeGameServer::connect(&server);
+    let mut other = FakeGameServer::connect(&server);
+    home.announce("Home");
+    other.announce("Other");
+
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+    client.wait_for_servers(|servers| servers.len() == 2);
+
+    let (_, login) = register(&server, "Home");
+
+    let verification = home.verify(&login.token);
+
+    assert!(verification.verified);
+    assert!(verification.uid > 0);
+
+    let verification = other.verify(&login.token);
+
+    assert!(!verification.verified);
+    assert_eq!(verification.uid, 0);
+
+    assert!(!home.verify("not a token").verified);
+}
+
+#[test]
+fn relogin_kills_the_old_session() {
+    let server = TestServer::start();
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.announce("Relogin");
+
+    let (account, _) = register(&server, "Relogin");
+
+    let mut client = client_for(&server, "Relogin");
+    client.send(account.login("Relogin", ""));
+
+    let login = client.expect::<to_client::Login>();
+    let uid = game_server.verify(&login.token).uid;
+
+    // The game server records where the player is once they join it.
+    server.set_current_server(uid, Some("Relogin"));
+
+    let mut client = client_for(&server, "Relogin");
+    client.send(account.login("Relogin", "wrong code"));
+
+    assert_eq!(client.expect_error(), ErrorCode::ReloginFailed);
+
+    let mut client = client_for(&server, "Relogin");
+    client.send(account.login("Relogin", &login.relogin_code));
+
+    let relogin = client.expect::<to_client::Login>();
+    let kill = game_server.expect::<to_game_server::KillClient>();
+
+    assert_eq!(kill.uid, uid);
+    assert_ne!(relogin.relogin_code, login.relogin_code);
+}
+
+#[test]
+fn bans_end_when_the_clock_passes_them() {
+    let server = TestServer::start();
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.announce("Banned");
+
+    let (account, _) = register(&server, "Banned");
+    server.ban(&account.username, 1);
+
+    let mut client = client_for(&server, "Banned");
+    client.send(account.login("Banned", ""));
+
+    assert_eq!(client.expect_error(), ErrorCode::Banned);
+
+    server
+        .clock
+        .advance(chrono::Duration::try_hours(2).unwrap());
+
+    let mut client = client_for(&server, "Banned");
+    client.send(account.login("Banned", ""));
+    client.expect::<to_client::Login>();
+}
+
+#[test]
+fn forced_password_resets_can_be_completed() {
+    let server = TestServer::start();
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.announce("Reset");
+
+    let (account, login) = register(&server, "Reset");
+    let uid = game_server.verify(&login.token).uid;
+    server.force_password_reset(uid, "reset-code");
+
+    let mut client = client_for(&server, "Reset");
+    client.send(account.login("Reset", ""));
+
+    assert_eq!(client.expect_error(), ErrorCode::PasswordResetRequired);
+
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+    client.send(account.reset_password("wrong-code", "N3wPassword!"));
+
+    assert_eq!(client.expect_error(), ErrorCode::InvalidResetCode);
+
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+    client.send(account.reset_password("reset-code", "N3wPassword!"));
+
+    let changed = client.expect::<to_client::AlertMsg>();
+
+    assert_eq!(changed.code, ErrorCode::None);
+    assert!(!changed.close_socket);
+
+    let account = Account {
+        password: "N3wPassword!".into(),
+        ..account
+    };
+
+    client.wait_for_servers(|servers| servers.iter().any(|info| info.name == "Reset"));
+    client.send(account.login("Reset", ""));
+    client.expect::<to_client::Login>();
+
+    // The code only works once.
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+    client.send(account.reset_password("reset-code", "An0therPassword!"));
+
+    assert_eq!(client.expect_error(), ErrorCode::InvalidResetCode);
+}
+
+#[test]
+fn refuses_names_of_connected_game_servers() {
+    let server = TestServer::start();
+    let mut owner = FakeGameServer::connect(&server);
+    let mut impostor = FakeGameServer::connect(&server);
+    owner.announce("Taken");
+
+    let mut client = client_for(&server, "Taken");
+
+    impostor.hello();
+    impostor.send(from_game_server::UpdateInfo {
+        name: "Taken".into(),
+        ip: "127.0.0.2".into(),
+        port: 7013,
+        players_on: 0,
+        max_players: TEST_MAX_PLAYERS,
+    });
+    impostor.expect_closed();
+
+    let servers = client.server_list();
+
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].ip, "127.0.0.1");
+
+    let (_, login) = register(&server, "Taken");
+
+    assert!(owner.verify(&login.token).verified);
+}
+
+#[test]
+fn revoked_certificates_are_refused_at_login() {
+    let server = TestServer::start_with(|config| config.tls.client_auth = ClientAuth::Optional);
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.announce("Revoked");
+
+    let identity = server.issue_client_cert();
+    let tls_config = FakeClient::tls_config(&server, Some(&identity));
+    let account = Account::unique();
+
+    let mut client = FakeClient::connect_with(&server, tls_config.clone());
+    client.hello();
+    client.send(account.register("Revoked"));
+    client.expect::<to_client::Login>();
+
+    let cert = rustls_pemfile::certs(&mut identity.cert.as_bytes())
+        .next()
+        .unwrap()
+        .unwrap();
+    server.revoke_cert(&fingerprint(&cert));
+
+    // The server has not reloaded its revocation list, and this session is resumed.
+    let mut client = FakeClient::connect_with(&server, tls_config);
+    client.hello();
+    client.send(account.login("Revoked", ""));
+
+    assert_eq!(client.expect_error(), ErrorCode::CertificateRevoked);
+}
+
+#[test]
+fn limits_connections_per_client_behind_a_proxy() {
+    let server = TestServer::start_with(|config| {
+        config.connections.max_per_ip = 1;
+        config.proxy_protocol = Some(ProxyProtocolConfig {
+            trusted_proxies: vec!["127.0.0.1".into()],
+            clients: true,
+            game_servers: false,
+        });
+    });
+    let player = "203.0.113.9:51000".parse().unwrap();
+
+    let mut first = FakeClient::connect_via_proxy(&server, player);
+    first.hello();
+
+    let mut second = FakeClient::connect_via_proxy(&server, player);
+    second.expect_closed();
+
+    let mut other = FakeClient::connect_via_proxy(&server, "203.0.113.10:51000".parse().unwrap());
+    other.hello();
+}
+
+#[test]
+fn refuses_packets_before_hello() {
+    let server = TestServer::start();
+    let account = Account::unique();
+
+    let mut client = FakeClient::connect(&server);
+    client.send(account.register("Hello"));
+    client.send(account.login("Hello", ""));
+
+    assert_eq!(client.expect_error(), ErrorCode::Outdated);
+    client.expect_closed();
+
+    let mut game_server = FakeGameServer::connect(&server);
+    game_server.send(from_game_server::UpdateCount {
+        players_on: 1,
+        max_players: 10,
+    });
+    game_server.expect_closed();
+}
+
+#[test]
+fn shutdown_does_not_wait_for_unfinished_handshakes() {
+    let websocket_addr = free_address();
+    let server = TestServer::start_with(|config| {
+        config.shutdown_timeout = 30;
+        config.websocket = Some(WebSocketConfig {
+            listen: vec![websocket_addr.to_string()],
+            allowed_origins: Vec::new(),
+            allow_any_origin: true,
+        });
+    });
+
+    // Finishes TLS, so the server has accepted it, but never asks for the upgrade.
+    let mut stream = TcpStream::connect(websocket_addr).unwrap();
+    let mut tls = ClientConnection::new(
+        FakeClient::tls_config(&server, None),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+
+    while tls.is_handshaking() {
+        tls.complete_io(&mut stream).unwrap();
+    }
+
+    let mut client = FakeClient::connect(&server);
+    client.hello();
+
+    let started = Instant::now();
+    drop(server);
+
+    assert!(
+        started.elapsed() < Duration::from_secs(10),
+        "shutdown waited {:?} for a client that never finished its handshake",
+        started.elapsed()
+    );
+    client.expect::<to_client::Shutdown>();
+}
@@ -0,0 +1,202 @@
+use super::*;
+use crate::{
+    certs::{load_certs, IssuedCert},
+    containers::GameServerInfo,
+    gametypes::*,
+};
+use rustls::{
+    pki_types::{CertificateDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A game client scripted by a test, connected over TLS and trusting the test server's
+/// development CA. Every helper panics when the server does not answer as expected.
+pub struct FakeClient {
+    stream: StreamOwned<ClientConnection, TcpStream>,
+    pending: Vec<u8>,
+}
+
+impl FakeClient {
+    pub fn connect(server: &TestServer) -> Self {
+        Self::connect_with(server, Self::tls_config(server, None))
+    }
+
+    /// TLS settings trusting the test server, sending `identity` as the client
+    /// certificate. Clients connected with the same settings resume their TLS sessions.
+    pub fn tls_config(server: &TestServer, identity: Option<&IssuedCert>) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+
+        for cert in load_certs(&server.config.ca_root).unwrap() {
+            roots.add(cert).unwrap();
+        }
+
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+
+        Arc::new(match identity {
+            Some(identity) => {
+                let cert: CertificateDer = rustls_pemfile::certs(&mut identity.cert.as_bytes())
+                    .next()
+                    .unwrap()
+                    .unwrap();
+                let key = rustls_pemfile::private_key(&mut identity.key.as_bytes())
+                    .unwrap()
+                    .unwrap();
+
+                builder.with_client_auth_cert(vec![cert], key).unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        })
+    }
+
+    pub fn connect_with(server: &TestServer, tls_config: Arc<ClientConfig>) -> Self {
+        Self::connect_with_header(server, tls_config, &[])
+    }
+
+    /// Connects as a load balancer would, sending a PROXY v1 header for `source` before
+    /// the TLS handshake.
+    pub fn connect_via_proxy(server: &TestServer, source: SocketAddr) -> Self {
+        let header = format!(
+            "PROXY TCP4 {} {} {} {}\r\n",
+            source.ip(),
+            server.client_addr.ip(),
+            source.port(),
+            server.client_addr.port()
+        );
+
+        Self::connect_with_header(server, Self::tls_config(server, None), header.as_bytes())
+    }
+
+    fn connect_with_header(
+        server: &TestServer,
+        tls_config: Arc<ClientConfig>,
+        header: &[u8],
+    ) -> Self {
+        let name = ServerName::try_from("localhost").unwrap();
+        let tls = ClientConnection::new(tls_config, name).unwrap();
+
+        let mut stream = TcpStream::connect(server.client_addr).expect("could not connect");
+        stream.write_all(header).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        Self {
+            stream: StreamOwned::new(tls, stream),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn send<P: Packet<Id = ClientPacket> + MByteBufferWrite>(&mut self, packet: P) {
+        write_packet(&mut self.stream, packet);
+    }
+
+    pub fn receive(&mut self) -> (ServerToClientPackets, MByteBuffer) {
+        read_packet(&mut self.stream, &mut self.pending)
+    }
+
+    /// Reads the next packet, which must be a `P`. An unexpected alert is shown in the
+    /// panic message.
+    pub fn expect<P: Packet<Id = ServerToClientPackets> + MByteBufferRead>(&mut self) -> P {
+        let (id, data) = self.receive();
+
+        if id == ServerToClientPackets::AlertMsg && P::ID != id {
+            let alert = expect_packet::<to_client::AlertMsg>(id, data);
+            panic!("expected {:?} but the server refused: {:?}", P::ID, alert);
+        }
+
+        expect_packet(id, data)
+    }
+
+    /// Waits for the server to close this connection, panicking when it answers.
+    pub fn expect_closed(&mut self) {
+        let mut chunk = [0; 64];
+
+        match self.stream.read(&mut chunk) {
+            Ok(0) => {}
+            Ok(_) => panic!("server sent data instead of closing the connection"),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof
+                ) => {}
+            Err(e) => panic!("server did not close the connection: {}", e),
+        }
+    }
+
+    /// Reads an alert that tells the client to disconnect and returns its code.
+    pub fn expect_error(&mut self) -> ErrorCode {
+        let alert = self.expect::<to_client::AlertMsg>();
+
+        assert!(alert.close_socket, "alert does not disconnect the client");
+        alert.code
+    }
+
+    /// Agrees on this server's protocol without compression.
+    pub fn hello(&mut self) -> to_client::Hello {
+        self.send(from_client::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            protocol_hash: protocol_hash().to_string(),
+            locale: "en".into(),
+            compression: Compression::None,
+        });
+
+        let hello = self.expect::<to_client::Hello>();
+
+        assert!(hello.accepted, "server refused the protocol");
+        hello
+    }
+
+    /// Requests the server list and reads every page of it.
+    pub fn server_list(&mut self) -> Vec<GameServerInfo> {
+        self.send(from_client::RequestServers {});
+
+        let mut servers = Vec::new();
+
+        // The test server always has a message of the day, which follows the last page.
+        loop {
+            let (id, mut data) = self.receive();
+
+            match id {
+                ServerToClientPackets::ServerList => {
+                    let count = data.read::<u64>().unwrap();
+
+                    for _ in 0..count {
+                        servers.push(data.read::<GameServerInfo>().unwrap());
+                    }
+                }
+                ServerToClientPackets::FltAlert => return servers,
+                id => panic!("expected the server list but received {:?}", id),
+            }
+        }
+    }
+
+    /// Requests the server list until `ready` accepts it, as game servers are listed
+    /// once the server has handled their `UpdateInfo`.
+    pub fn wait_for_servers(
+        &mut self,
+        ready: impl Fn(&[GameServerInfo]) -> bool,
+    ) -> Vec<GameServerInfo> {
+        let start = Instant::now();
+
+        loop {
+            let servers = self.server_list();
+
+            if ready(&servers) {
+                return servers;
+            }
+
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "server list stayed {:?}",
+                servers
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+use crate::gametypes::*;
+use std::{
+    io::{ErrorKind, Read},
+    net::TcpStream,
+};
+
+/// Players `announce` reports, leaving room for logins.
+pub const TEST_MAX_PLAYERS: u64 = 100;
+
+/// A game server scripted by a test, connected to the game server listener. Every
+/// helper panics when the server does not answer as expected.
+pub struct FakeGameServer {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl FakeGameServer {
+    pub fn connect(server: &TestServer) -> Self {
+        let stream = TcpStream::connect(server.game_server_addr).expect("could not connect");
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        Self {
+            stream,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn send<P: Packet<Id = GameServerPacket> + MByteBufferWrite>(&mut self, packet: P) {
+        write_packet(&mut self.stream, packet);
+    }
+
+    pub fn receive(&mut self) -> (ServerToServerPackets, MByteBuffer) {
+        read_packet(&mut self.stream, &mut self.pending)
+    }
+
+    /// Reads the next packet, which must be a `P`.
+    pub fn expect<P: Packet<Id = ServerToServerPackets> + MByteBufferRead>(&mut self) -> P {
+        let (id, data) = self.receive();
+
+        expect_packet(id, data)
+    }
+
+    /// Agrees on this server's protocol without compression.
+    pub fn hello(&mut self) -> to_game_server::Hello {
+        self.send(from_game_server::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            protocol_hash: protocol_hash().to_string(),
+            compression: Compression::None,
+        });
+
+        let hello = self.expect::<to_game_server::Hello>();
+
+        assert!(hello.accepted, "server refused the protocol");
+        hello
+    }
+
+    /// Says hello and adds this game server to the server list as `name`. Clients see
+    /// it once the server has handled the update, see `FakeClient::wait_for_servers`.
+    pub fn announce(&mut self, name: &str) {
+        self.hello();
+        self.send(from_game_server::UpdateInfo {
+            name: name.into(),
+            ip: "127.0.0.1".into(),
+            port: 7012,
+            players_on: 0,
+            max_players: TEST_MAX_PLAYERS,
+        });
+    }
+
+    /// Waits for the server to close this connection, panicking when it sends anything.
+    pub fn expect_closed(&mut self) {
+        let mut chunk = [0; 64];
+
+        match self.stream.read(&mut chunk) {
+            Ok(0) => {}
+            Ok(_) => panic!("server sent data instead of closing the connection"),
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => {}
+            Err(e) => panic!("server did not close the connection: {}", e),
+        }
+    }
+
+    /// Asks whether a client's login token was issued for this game server.
+    pub fn verify(&mut self, token: &str) -> to_game_server::Verification {
+        self.send(from_game_server::Verification {
+            token: token.into(),
+        });
+
+        self.expect::<to_game_server::Verification>()
+    }
+}
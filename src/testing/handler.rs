@@ -0,0 +1,125 @@
+use super::{expect_packet, read_packet, test_config, test_dir};
+use crate::{
+    certs,
+    containers::{GameServerInfo, Storage},
+    gametypes::*,
+    server::{ManualClock, NoMailer},
+    socket::*,
+    store::MemoryAccountStore,
+};
+use mmap_bytey::MByteBufferRead;
+use std::{fs, io, net::TcpListener, path::PathBuf, sync::Arc};
+
+/// A `Storage` on a `MemoryAccountStore` and one connected client, so packet handlers can
+/// be called directly and what they send read back from the client's send queue.
+pub struct HandlerTest {
+    pub storage: Storage,
+    pub client: Client,
+    pub clock: Arc<ManualClock>,
+    /// The other end of the client's connection, which nothing reads.
+    _peer: std::net::TcpStream,
+    dir: PathBuf,
+}
+
+impl HandlerTest {
+    pub async fn new() -> Self {
+        let dir = test_dir();
+        let config = test_config(&dir);
+
+        certs::ensure_dev_certs(&config).expect("could not create test certificates");
+
+        let clock = Arc::new(ManualClock::default());
+        let storage = Storage::new(
+            config,
+            Arc::new(MemoryAccountStore::default()),
+            Arc::new(NoMailer),
+            clock.clone(),
+        )
+        .await
+        .expect("test storage should build");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let peer = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, addr) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+
+        let tls =
+            rustls::ServerConnection::new(storage.server.read().await.tls_config.clone()).unwrap();
+        let mut client = Client::new(
+            mio::net::TcpStream::from_std(stream),
+            mio::Token(usize::MAX - 1),
+            addr.to_string(),
+            tls,
+            Transport::Tls,
+            None,
+        )
+        .unwrap();
+
+        storage
+            .poll
+            .read()
+            .await
+            .registry()
+            .register(&mut client.stream, client.token, mio::Interest::READABLE)
+            .unwrap();
+
+        client.locale = "en".into();
+        client.hello_accepted = true;
+
+        Self {
+            storage,
+            client,
+            clock,
+            _peer: peer,
+            dir,
+        }
+    }
+
+    /// Lists a game server as if it had announced itself.
+    pub fn list_server(&mut self, name: &str, players_on: u64, max_players: u64) {
+        let token = mio::Token(usize::MAX - 2 - self.storage.servers.len());
+
+        self.storage.servers.insert(
+            token,
+            GameServerInfo {
+                name: name.into(),
+                ip: "127.0.0.1".into(),
+                port: 7010,
+                players_on,
+                max_players,
+            },
+        );
+        self.storage.server_names.insert(name.into(), token);
+    }
+
+    /// Takes the next packet sent to the client, panicking when there is none.
+    pub fn expect<P: Packet<Id = ServerToClientPackets> + MByteBufferRead>(&mut self) -> P {
+        let mut sent = self
+            .client
+            .sends
+            .pop_front()
+            .expect("handler sent nothing to the client");
+        let mut pending = sent.as_slice().to_vec();
+        let (id, data) = read_packet::<ServerToClientPackets>(&mut io::empty(), &mut pending);
+
+        expect_packet(id, data)
+    }
+
+    /// Takes the error the handler refused the client with and checks it disconnects them.
+    pub fn expect_error(&mut self) -> ErrorCode {
+        let alert = self.expect::<to_client::AlertMsg>();
+
+        assert!(alert.close_socket, "alert does not disconnect the client");
+        assert!(
+            self.client.sends.is_empty(),
+            "handler sent more after the error"
+        );
+        alert.code
+    }
+}
+
+impl Drop for HandlerTest {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    certs::{self, CertKind, CertificateAuthority, IssuedCert},
+    containers::{AccountStoreKind, Config, Profile},
+    server::{Clock, LoginServer, LoginServerHandle, ManualClock},
+    store::{open_account_store, AccountStore},
+};
+use std::{
+    env, fs,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How long the helpers wait for the server to start or answer.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sent after every server list, which tells the helpers the list is complete.
+pub const TEST_MOTD: &str = "Welcome to the test server.";
+
+static NEXT_SERVER: AtomicUsize = AtomicUsize::new(0);
+
+/// A login server running on its own thread with client and game server listeners on
+/// free local ports and development certificates in a temporary directory. Its clock
+/// only moves when a test advances it. It is shut down when dropped.
+///
+/// Accounts are kept in memory unless `ASCENDING_TEST_DATABASE` is set, then they are
+/// kept in that database with `ASCENDING_TEST_USERNAME`, `ASCENDING_TEST_PASSWORD`,
+/// `ASCENDING_TEST_HOST` and `ASCENDING_TEST_PORT`. Tests share it, so they use
+/// accounts from `Account::unique`.
+pub struct TestServer {
+    pub config: Config,
+    pub client_addr: SocketAddr,
+    pub game_server_addr: SocketAddr,
+    pub clock: Arc<ManualClock>,
+    accounts: Arc<dyn AccountStore>,
+    runtime: tokio::runtime::Handle,
+    handle: LoginServerHandle,
+    thread: Option<JoinHandle<()>>,
+    dir: PathBuf,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    /// Starts the server after `configure` has changed the test configuration.
+    pub fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = test_dir();
+        let mut config = test_config(&dir);
+        database_from_env(&mut config);
+        configure(&mut config);
+
+        let errors = config.validate();
+        assert!(
+            errors.is_empty(),
+            "invalid test configuration: {:?}",
+            errors
+        );
+
+        certs::ensure_dev_certs(&config).expect("could not create test certificates");
+
+        let (ready, started) = mpsc::channel();
+        let server_config = config.clone();
+        let clock = Arc::new(ManualClock::default());
+        let server_clock = clock.clone();
+
+        let thread = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let handle = runtime.handle().clone();
+
+            runtime.block_on(async move {
+                let accounts = open_account_store(&server_config)
+                    .await
+                    .expect("could not open the test account store");
+                let server = LoginServer::builder()
+                    .config(server_config)
+                    .store(accounts.clone())
+                    .clock(server_clock)
+                    .build()
+                    .await
+                    .expect("test server should start");
+
+                let _ = ready.send((server.handle(), accounts, handle));
+                server.run().await;
+            });
+        });
+
+        let (handle, accounts, runtime) = started
+            .recv_timeout(TIMEOUT)
+            .expect("test server did not start, is the ASCENDING_TEST_ database reachable?");
+
+        Self {
+            client_addr: config.clients_listen[0].parse().unwrap(),
+            game_server_addr: config.servers_listen[0].parse().unwrap(),
+            config,
+            clock,
+            accounts,
+            runtime,
+            handle,
+            thread: Some(thread),
+            dir,
+        }
+    }
+
+    /// Sets the game server the account store says a player is on. Game servers write
+    /// this themselves, the login server only reads it when deciding on a relogin.
+    pub fn set_current_server(&self, uid: i64, server_name: Option<&str>) {
+        self.runtime
+            .block_on(self.accounts.update_current_server(uid, server_name))
+            .unwrap();
+    }
+
+    /// Forces a password reset with `code`, as the admin API does.
+    pub fn force_password_reset(&self, uid: i64, code: &str) {
+        let found = self
+            .runtime
+            .block_on(self.accounts.force_password_reset(uid, code))
+            .unwrap();
+
+        assert!(found, "no account to reset");
+    }
+
+    /// Issues a client certificate from the test server's development CA.
+    pub fn issue_client_cert(&self) -> IssuedCert {
+        let ca_cert = PathBuf::from(&self.config.ca_root);
+        let ca = CertificateAuthority::load(&ca_cert, &certs::ca_key_path(&self.config)).unwrap();
+
+        ca.issue(CertKind::Client, &["client".into()], 1).unwrap()
+    }
+
+    /// Revokes a client certificate in the account store, as `revoke-cert` does, without
+    /// reloading the server's revocation list.
+    pub fn revoke_cert(&self, fingerprint: &str) {
+        let revoked = self
+            .runtime
+            .block_on(self.accounts.revoke_cert(fingerprint, "test"))
+            .unwrap();
+
+        assert!(revoked, "certificate was already revoked");
+    }
+
+    /// Bans `username` for `hours` from the server's clock, as the `ban` command does.
+    pub fn ban(&self, username: &str, hours: i64) {
+        let expires = self.clock.now() + chrono::Duration::try_hours(hours).unwrap();
+
+        self.runtime
+            .block_on(self.accounts.ban_player(username, Some(expires)))
+            .unwrap()
+            .expect("no account to ban");
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.shutdown();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A local address nothing listens on, found by letting the OS pick a port.
+/// The configuration tests start from, with listeners on free local ports, certificates
+/// and logs in `dir` and accounts in memory.
+pub fn test_config(dir: &Path) -> Config {
+    let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+
+    let mut config = Config {
+        profile: Profile::Dev,
+        clients_listen: vec![free_address().to_string()],
+        servers_listen: vec![free_address().to_string()],
+        server_cert: path("server.crt"),
+        server_key: path("server-key.pem"),
+        ca_root: path("ca-crt.pem"),
+        maxconnections: 64,
+        account_store: AccountStoreKind::Memory,
+        database: "ascending_test".into(),
+        shutdown_timeout: 1,
+        motd: TEST_MOTD.into(),
+        ..Config::default()
+    };
+    config.tls.watch_certs = false;
+    config.logging.file_path = path("ServerLog.txt");
+    config
+}
+
+/// A directory of its own under the system's temporary directory.
+pub fn test_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "ascending-test-{}-{}",
+        process::id(),
+        NEXT_SERVER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).expect("could not create the test directory");
+    dir
+}
+
+/// A local address nothing is listening on.
+pub fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free local port")
+}
+
+fn database_from_env(config: &mut Config) {
+    let var = |name: &str| env::var(format!("ASCENDING_TEST_{}", name)).ok();
+
+    if let Some(database) = var("DATABASE") {
+        config.account_store = AccountStoreKind::Postgres;
+        config.database = database;
+    }
+
+    if let Some(username) = var("USERNAME") {
+        config.username = username;
+    }
+
+    if let Some(password) = var("PASSWORD") {
+        config.password = password;
+    }
+
+    if let Some(host) = var("HOST") {
+        config.host = host;
+    }
+
+    if let Some(port) = var("PORT") {
+        config.port = port.parse().expect("ASCENDING_TEST_PORT is not a port");
+    }
+}